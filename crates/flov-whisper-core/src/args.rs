@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug)]
+pub struct Args {
+    pub model: PathBuf,
+    pub language: String,
+    /// `--segments`: print timed segments instead of plain text.
+    pub segments: bool,
+}
+
+#[derive(Debug)]
+pub enum Mode {
+    Transcribe(Args),
+    Probe,
+}
+
+/// Parses the sidecar command line, program name already stripped.
+/// `--probe` wins over everything else; otherwise `--model` is required
+/// and must exist, the language defaults to Russian and `--segments` is
+/// off.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Mode> {
+    let mut model: Option<PathBuf> = None;
+    let mut language = String::from("ru");
+    let mut segments = false;
+    let mut iter = args.into_iter();
+    while let Some(a) = iter.next() {
+        match a.as_str() {
+            "--probe" => return Ok(Mode::Probe),
+            "--model" => {
+                model = Some(PathBuf::from(
+                    iter.next().context("--model requires a value")?,
+                ));
+            }
+            "--language" => {
+                language = iter.next().context("--language requires a value")?;
+            }
+            "--segments" => segments = true,
+            other => bail!("unknown argument: {}", other),
+        }
+    }
+    let model = model.context("--model is required")?;
+    if !model.exists() {
+        bail!("model file not found: {}", model.display());
+    }
+    Ok(Mode::Transcribe(Args {
+        model,
+        language,
+        segments,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn probe_ignores_other_arguments() {
+        let mode = parse_args(args(&["--probe", "--model"])).unwrap();
+
+        assert!(matches!(mode, Mode::Probe));
+    }
+
+    #[test]
+    fn language_defaults_to_russian() {
+        let model = std::env::current_exe().unwrap();
+        let mode = parse_args(args(&["--model", model.to_str().unwrap()])).unwrap();
+
+        let Mode::Transcribe(a) = mode else {
+            panic!("expected transcribe mode");
+        };
+        assert_eq!(a.language, "ru");
+        assert_eq!(a.model, model);
+        assert!(!a.segments);
+    }
+
+    #[test]
+    fn segments_flag_is_parsed() {
+        let model = std::env::current_exe().unwrap();
+        let mode = parse_args(args(&["--segments", "--model", model.to_str().unwrap()])).unwrap();
+
+        let Mode::Transcribe(a) = mode else {
+            panic!("expected transcribe mode");
+        };
+        assert!(a.segments);
+    }
+
+    #[test]
+    fn rejects_missing_values_and_unknown_flags() {
+        let err = parse_args(args(&["--language"])).unwrap_err();
+        assert!(err.to_string().contains("--language requires a value"));
+
+        let err = parse_args(args(&["--verbose"])).unwrap_err();
+        assert!(err.to_string().contains("unknown argument: --verbose"));
+
+        let err = parse_args(args(&["--language", "en"])).unwrap_err();
+        assert!(err.to_string().contains("--model is required"));
+    }
+}
@@ -0,0 +1,157 @@
+// flov-whisper-core — the protocol shared by every transcription sidecar.
+//
+// Protocol:
+//   args:   --model <path> --language <code> [--segments]
+//   stdin:  raw f32 LE PCM, 16 kHz mono. Parent closes stdin to signal end.
+//   stdout: transcribed text (trimmed, no trailing newline). With
+//           --segments instead one line per whisper segment,
+//           `<start_ms>\t<end_ms>\t<text>`, times relative to the start
+//           of stdin and the text untrimmed (file import / subtitles).
+//   stderr: human-readable progress / errors. Never on stdout. On success
+//           the last line is `flov-timings: load_ms=<n> decode_ms=<n>`
+//           (model load and inference wall time, for the benchmark).
+//   exit:   0 on success, 1 on failure.
+//
+// Probe (hardware detection, no model needed):
+//   args:   --probe
+//   stdout: one line per ggml device this build registered, tab-separated:
+//           <kind: cpu|gpu|igpu|accel> <init: 1|0> <total bytes> <free bytes> <description>
+//   exit:   0 if at least one device of this sidecar's kind initialised,
+//           1 otherwise (reason on stderr).
+//
+// Each `flov-whisper-<backend>` binary is a one-call `main` that names
+// itself and says whether it needs a GPU. Everything else lives here so the
+// four binaries can't drift apart. The whisper-rs glue is in `whisper.rs`;
+// the protocol itself only talks to the `Engine` trait, which is how
+// tests/protocol.rs drives it without a model or a GPU. That glue sits
+// behind the default `engine` feature, so the protocol builds and tests
+// without whisper-rs's native toolchain.
+
+mod args;
+mod output;
+mod params;
+mod pcm;
+#[cfg(feature = "engine")]
+mod whisper;
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+
+pub use args::{parse_args, Args, Mode};
+pub use output::{
+    join_segments, probe_line, segment_line, timings_line, DeviceKind, ProbeDevice, Segment,
+};
+pub use params::DecodeParams;
+pub use pcm::{decode_pcm, read_pcm};
+#[cfg(feature = "engine")]
+pub use whisper::WhisperEngine;
+
+/// Identity of one sidecar binary.
+pub struct Sidecar {
+    /// Binary name, used as the stderr error prefix.
+    pub name: &'static str,
+    /// Whether `--probe` must find a GPU device. The CPU build only ever
+    /// registers the CPU device; a GPU build that only sees the CPU device
+    /// means the driver / runtime didn't come up.
+    pub requires_gpu: bool,
+}
+
+/// What the protocol needs from a speech-to-text implementation.
+pub trait Engine {
+    type Model;
+
+    fn load(&self, model: &Path) -> Result<Self::Model>;
+
+    /// Returns the transcript as whisper's segments, text untrimmed.
+    fn decode(
+        &self,
+        model: &Self::Model,
+        params: &DecodeParams,
+        samples: &[f32],
+    ) -> Result<Vec<Segment>>;
+
+    /// Every device the build registered, usable or not.
+    fn devices(&self) -> Vec<ProbeDevice>;
+}
+
+/// Entry point for the sidecar binaries: runs the protocol on the process
+/// stdio with the whisper engine and exits 1 on failure.
+#[cfg(feature = "engine")]
+pub fn main(sidecar: Sidecar) {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let result = run(
+        &WhisperEngine,
+        sidecar.requires_gpu,
+        std::env::args().skip(1),
+        stdin.lock(),
+        stdout.lock(),
+        std::io::stderr(),
+    );
+    if let Err(e) = result {
+        let _ = writeln!(std::io::stderr(), "{} error: {e:#}", sidecar.name);
+        std::process::exit(1);
+    }
+}
+
+/// One protocol exchange: `args` without the program name, then either the
+/// probe or a transcription of everything on `stdin`.
+pub fn run<E: Engine>(
+    engine: &E,
+    requires_gpu: bool,
+    args: impl IntoIterator<Item = String>,
+    stdin: impl Read,
+    mut stdout: impl Write,
+    mut stderr: impl Write,
+) -> Result<()> {
+    let args = match parse_args(args)? {
+        Mode::Probe => return probe(engine, requires_gpu, stdout),
+        Mode::Transcribe(args) => args,
+    };
+
+    let load_start = Instant::now();
+    let model = engine
+        .load(&args.model)
+        .context("failed to load whisper model")?;
+    let load_ms = load_start.elapsed().as_millis();
+
+    let samples = read_pcm(stdin)?;
+    let params = DecodeParams::for_args(&args);
+
+    let decode_start = Instant::now();
+    let segments = engine
+        .decode(&model, &params, &samples)
+        .context("transcription failed")?;
+    let decode_ms = decode_start.elapsed().as_millis();
+
+    if args.segments {
+        for segment in &segments {
+            writeln!(stdout, "{}", segment_line(segment)).context("failed to write stdout")?;
+        }
+    } else {
+        stdout
+            .write_all(join_segments(&segments).as_bytes())
+            .context("failed to write stdout")?;
+    }
+    stdout.flush().ok();
+    let _ = writeln!(stderr, "{}", timings_line(load_ms, decode_ms));
+    Ok(())
+}
+
+fn probe<E: Engine>(engine: &E, requires_gpu: bool, mut stdout: impl Write) -> Result<()> {
+    let devices = engine.devices();
+    for device in &devices {
+        writeln!(stdout, "{}", probe_line(device)).context("failed to write stdout")?;
+    }
+    stdout.flush().ok();
+    if !devices.iter().any(|d| d.is_usable(requires_gpu)) {
+        bail!(
+            "no usable {} device found",
+            if requires_gpu { "GPU" } else { "CPU" }
+        );
+    }
+    Ok(())
+}
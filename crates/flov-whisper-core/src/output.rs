@@ -0,0 +1,152 @@
+/// One whisper segment with its position in the decoded audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// As whisper returned it, leading space included.
+    pub text: String,
+}
+
+impl Segment {
+    /// A segment without timing, for engines that don't report any.
+    pub fn untimed(text: impl Into<String>) -> Self {
+        Self {
+            start_ms: 0,
+            end_ms: 0,
+            text: text.into(),
+        }
+    }
+}
+
+/// Whisper segments concatenated and trimmed — what goes to stdout.
+pub fn join_segments(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|s| s.text.as_str())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// One `--segments` stdout line: `<start_ms>\t<end_ms>\t<text>`. The text
+/// keeps whisper's leading space so the app can concatenate segments the
+/// same way `join_segments` does; tabs and newlines become spaces.
+pub fn segment_line(segment: &Segment) -> String {
+    format!(
+        "{}\t{}\t{}",
+        segment.start_ms,
+        segment.end_ms,
+        segment.text.replace(['\t', '\n', '\r'], " ")
+    )
+}
+
+/// Final stderr line on success; parsed by `transcribe::parse_timings`
+/// in the app.
+pub fn timings_line(load_ms: u128, decode_ms: u128) -> String {
+    format!("flov-timings: load_ms={} decode_ms={}", load_ms, decode_ms)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Cpu,
+    Gpu,
+    Igpu,
+    Accel,
+}
+
+impl DeviceKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceKind::Cpu => "cpu",
+            DeviceKind::Gpu => "gpu",
+            DeviceKind::Igpu => "igpu",
+            DeviceKind::Accel => "accel",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeDevice {
+    pub kind: DeviceKind,
+    pub initialised: bool,
+    pub memory_total: u64,
+    pub memory_free: u64,
+    pub description: String,
+}
+
+impl ProbeDevice {
+    /// Counts toward a successful probe: initialised, and of the kind this
+    /// sidecar exists for.
+    pub fn is_usable(&self, requires_gpu: bool) -> bool {
+        let wanted = if requires_gpu {
+            matches!(self.kind, DeviceKind::Gpu | DeviceKind::Igpu)
+        } else {
+            self.kind == DeviceKind::Cpu
+        };
+        wanted && self.initialised
+    }
+}
+
+/// One `--probe` stdout line. Tabs and newlines in the driver's description
+/// would break the column split, so they become spaces.
+pub fn probe_line(device: &ProbeDevice) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}",
+        device.kind.as_str(),
+        u8::from(device.initialised),
+        device.memory_total,
+        device.memory_free,
+        device.description.replace(['\t', '\n'], " ").trim()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(kind: DeviceKind, initialised: bool) -> ProbeDevice {
+        ProbeDevice {
+            kind,
+            initialised,
+            memory_total: 8,
+            memory_free: 4,
+            description: "dev".into(),
+        }
+    }
+
+    #[test]
+    fn joins_and_trims_segments() {
+        let segments = vec![Segment::untimed(" Привет,"), Segment::untimed(" мир. ")];
+
+        assert_eq!(join_segments(&segments), "Привет, мир.");
+        assert_eq!(join_segments(&[]), "");
+    }
+
+    #[test]
+    fn segment_line_keeps_leading_space_and_flattens_whitespace() {
+        let segment = Segment {
+            start_ms: 1_200,
+            end_ms: 3_450,
+            text: " line one\nline\ttwo".into(),
+        };
+
+        assert_eq!(segment_line(&segment), "1200\t3450\t line one line two");
+    }
+
+    #[test]
+    fn probe_line_is_five_tab_separated_columns() {
+        let mut d = device(DeviceKind::Igpu, true);
+        d.description = "Intel\tArc\n ".into();
+
+        assert_eq!(probe_line(&d), "igpu\t1\t8\t4\tIntel Arc");
+    }
+
+    #[test]
+    fn usable_device_matches_sidecar_kind() {
+        assert!(device(DeviceKind::Cpu, true).is_usable(false));
+        assert!(!device(DeviceKind::Cpu, true).is_usable(true));
+        assert!(device(DeviceKind::Gpu, true).is_usable(true));
+        assert!(!device(DeviceKind::Gpu, false).is_usable(true));
+        assert!(!device(DeviceKind::Accel, true).is_usable(true));
+    }
+}
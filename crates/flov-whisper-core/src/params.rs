@@ -0,0 +1,80 @@
+use crate::args::Args;
+
+/// Decoder settings, independent of whisper-rs so they can be checked
+/// without loading the library. `whisper::full_params` maps them onto
+/// `FullParams`; greedy sampling with `best_of = 1` is fixed there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeParams {
+    pub language: String,
+    pub n_threads: i32,
+    /// Dictation wants the spoken language back, never English.
+    pub translate: bool,
+    /// Each recording is independent; carrying context across calls only
+    /// produces hallucinated continuations.
+    pub no_context: bool,
+    /// One push-to-talk utterance → one segment, no timestamps to stitch.
+    /// Off for `--segments`, where the boundaries are the point.
+    pub single_segment: bool,
+}
+
+impl DecodeParams {
+    pub fn for_args(args: &Args) -> Self {
+        Self::with_threads(args, num_cpus::get())
+    }
+
+    pub fn with_threads(args: &Args, threads: usize) -> Self {
+        Self {
+            language: args.language.clone(),
+            n_threads: threads.clamp(1, i32::MAX as usize) as i32,
+            translate: false,
+            no_context: true,
+            single_segment: !args.segments,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn args(language: &str) -> Args {
+        Args {
+            model: PathBuf::from("model.bin"),
+            language: language.to_string(),
+            segments: false,
+        }
+    }
+
+    #[test]
+    fn carries_language_and_dictation_defaults() {
+        let p = DecodeParams::with_threads(&args("en"), 8);
+
+        assert_eq!(
+            p,
+            DecodeParams {
+                language: "en".into(),
+                n_threads: 8,
+                translate: false,
+                no_context: true,
+                single_segment: true,
+            }
+        );
+    }
+
+    #[test]
+    fn segments_mode_lets_whisper_split() {
+        let a = Args {
+            segments: true,
+            ..args("en")
+        };
+
+        assert!(!DecodeParams::with_threads(&a, 4).single_segment);
+    }
+
+    #[test]
+    fn always_uses_at_least_one_thread() {
+        assert_eq!(DecodeParams::with_threads(&args("ru"), 0).n_threads, 1);
+        assert!(DecodeParams::for_args(&args("ru")).n_threads >= 1);
+    }
+}
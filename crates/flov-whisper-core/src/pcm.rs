@@ -0,0 +1,47 @@
+use std::io::Read;
+
+use anyhow::{bail, Context, Result};
+
+/// Reads stdin to EOF (the parent closes the pipe when it's done) and
+/// decodes it as f32 LE PCM.
+pub fn read_pcm(mut input: impl Read) -> Result<Vec<f32>> {
+    let mut buf = Vec::with_capacity(64 * 1024);
+    input
+        .read_to_end(&mut buf)
+        .context("failed to read stdin")?;
+    decode_pcm(&buf)
+}
+
+pub fn decode_pcm(bytes: &[u8]) -> Result<Vec<f32>> {
+    if !bytes.len().is_multiple_of(4) {
+        bail!("stdin byte length {} is not a multiple of 4", bytes.len());
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_little_endian_f32() {
+        let bytes = [1.0f32.to_le_bytes(), (-0.5f32).to_le_bytes()].concat();
+
+        assert_eq!(decode_pcm(&bytes).unwrap(), vec![1.0, -0.5]);
+    }
+
+    #[test]
+    fn empty_input_is_no_samples() {
+        assert!(read_pcm(&[][..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_sample() {
+        let err = decode_pcm(&[0, 0, 128]).unwrap_err();
+
+        assert!(err.to_string().contains("not a multiple of 4"));
+    }
+}
@@ -0,0 +1,103 @@
+// whisper-rs implementation of `Engine`. The only module that touches
+// whisper.cpp; which GPU backend it runs on is decided by the feature the
+// sidecar crate enabled on whisper-rs.
+
+use std::ffi::CStr;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use whisper_rs::whisper_rs_sys as sys;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use crate::{DecodeParams, DeviceKind, Engine, ProbeDevice, Segment};
+
+pub struct WhisperEngine;
+
+impl Engine for WhisperEngine {
+    type Model = WhisperContext;
+
+    fn load(&self, model: &Path) -> Result<WhisperContext> {
+        Ok(WhisperContext::new_with_params(
+            model.to_str().context("invalid model path")?,
+            WhisperContextParameters::default(),
+        )?)
+    }
+
+    fn decode(
+        &self,
+        ctx: &WhisperContext,
+        params: &DecodeParams,
+        samples: &[f32],
+    ) -> Result<Vec<Segment>> {
+        let mut state = ctx.create_state().context("failed to create state")?;
+        state.full(full_params(params), samples)?;
+
+        let mut segments = Vec::new();
+        for i in 0..state.full_n_segments() {
+            if let Some(segment) = state.get_segment(i) {
+                if let Ok(s) = segment.to_str_lossy() {
+                    // whisper.cpp timestamps are in centiseconds.
+                    segments.push(Segment {
+                        start_ms: segment.start_timestamp().max(0) as u64 * 10,
+                        end_ms: segment.end_timestamp().max(0) as u64 * 10,
+                        text: s.into_owned(),
+                    });
+                }
+            }
+        }
+        Ok(segments)
+    }
+
+    fn devices(&self) -> Vec<ProbeDevice> {
+        let mut devices = Vec::new();
+        // SAFETY: the ggml device registry is a process-global that lives for
+        // the whole program; description strings are owned by the registry.
+        unsafe {
+            for i in 0..sys::ggml_backend_dev_count() {
+                let dev = sys::ggml_backend_dev_get(i);
+                let kind = match sys::ggml_backend_dev_type(dev) {
+                    sys::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_CPU => DeviceKind::Cpu,
+                    sys::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_GPU => DeviceKind::Gpu,
+                    sys::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_IGPU => DeviceKind::Igpu,
+                    _ => DeviceKind::Accel,
+                };
+                let (mut free, mut total) = (0usize, 0usize);
+                sys::ggml_backend_dev_memory(dev, &mut free, &mut total);
+                let description = CStr::from_ptr(sys::ggml_backend_dev_description(dev))
+                    .to_string_lossy()
+                    .into_owned();
+
+                // Actually bring the backend up — enumeration alone succeeds
+                // even when e.g. the CUDA context can't be created.
+                let backend = sys::ggml_backend_dev_init(dev, std::ptr::null());
+                let initialised = !backend.is_null();
+                if initialised {
+                    sys::ggml_backend_free(backend);
+                }
+
+                devices.push(ProbeDevice {
+                    kind,
+                    initialised,
+                    memory_total: total as u64,
+                    memory_free: free as u64,
+                    description,
+                });
+            }
+        }
+        devices
+    }
+}
+
+fn full_params(params: &DecodeParams) -> FullParams<'_, '_> {
+    let mut full = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    full.set_n_threads(params.n_threads);
+    full.set_translate(params.translate);
+    full.set_no_context(params.no_context);
+    full.set_single_segment(params.single_segment);
+    full.set_print_special(false);
+    full.set_print_progress(false);
+    full.set_print_realtime(false);
+    full.set_print_timestamps(false);
+    full.set_language(Some(&params.language));
+    full
+}
@@ -0,0 +1,224 @@
+// End-to-end protocol tests: `run` with a fake engine, in-memory stdio,
+// and the same argv the app passes. No model, no GPU.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use flov_whisper_core::{run, DecodeParams, DeviceKind, Engine, ProbeDevice, Segment};
+
+#[derive(Default)]
+struct FakeEngine {
+    devices: Vec<ProbeDevice>,
+    fail_decode: bool,
+    /// Samples and params the last decode saw.
+    seen: RefCell<Option<(Vec<f32>, DecodeParams)>>,
+}
+
+impl Engine for FakeEngine {
+    type Model = PathBuf;
+
+    fn load(&self, model: &Path) -> Result<PathBuf> {
+        Ok(model.to_path_buf())
+    }
+
+    fn decode(&self, _: &PathBuf, params: &DecodeParams, samples: &[f32]) -> Result<Vec<Segment>> {
+        if self.fail_decode {
+            bail!("out of memory");
+        }
+        *self.seen.borrow_mut() = Some((samples.to_vec(), params.clone()));
+        Ok(vec![
+            Segment {
+                start_ms: 0,
+                end_ms: 1_500,
+                text: format!(" heard {} samples,", samples.len()),
+            },
+            Segment {
+                start_ms: 1_500,
+                end_ms: 2_000,
+                text: " done. ".to_string(),
+            },
+        ])
+    }
+
+    fn devices(&self) -> Vec<ProbeDevice> {
+        self.devices.clone()
+    }
+}
+
+struct Output {
+    result: Result<()>,
+    stdout: String,
+    stderr: String,
+}
+
+fn model_file() -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fake-model.bin");
+    std::fs::write(&path, b"not a real model").unwrap();
+    path
+}
+
+fn pcm(samples: &[f32]) -> Vec<u8> {
+    samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+}
+
+fn exec(engine: &FakeEngine, requires_gpu: bool, args: &[&str], stdin: &[u8]) -> Output {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let result = run(
+        engine,
+        requires_gpu,
+        args.iter().map(|s| s.to_string()),
+        stdin,
+        &mut stdout,
+        &mut stderr,
+    );
+    Output {
+        result,
+        stdout: String::from_utf8(stdout).unwrap(),
+        stderr: String::from_utf8(stderr).unwrap(),
+    }
+}
+
+fn device(kind: DeviceKind, initialised: bool, description: &str) -> ProbeDevice {
+    ProbeDevice {
+        kind,
+        initialised,
+        memory_total: 8_589_934_592,
+        memory_free: 4_294_967_296,
+        description: description.into(),
+    }
+}
+
+#[test]
+fn transcribes_stdin_to_trimmed_stdout_with_timings_on_stderr() {
+    let model = model_file();
+    let engine = FakeEngine::default();
+
+    let out = exec(
+        &engine,
+        false,
+        &["--model", model.to_str().unwrap(), "--language", "en"],
+        &pcm(&[0.25, -0.5, 1.0]),
+    );
+
+    out.result.unwrap();
+    assert_eq!(out.stdout, "heard 3 samples, done.");
+    let last = out.stderr.lines().last().unwrap();
+    assert!(last.starts_with("flov-timings: load_ms="), "{last}");
+    assert!(last.contains(" decode_ms="), "{last}");
+
+    let (samples, params) = engine.seen.borrow().clone().unwrap();
+    assert_eq!(samples, vec![0.25, -0.5, 1.0]);
+    assert_eq!(params.language, "en");
+    assert!(!params.translate);
+}
+
+#[test]
+fn segments_mode_prints_one_timed_line_per_segment() {
+    let model = model_file();
+    let engine = FakeEngine::default();
+
+    let out = exec(
+        &engine,
+        false,
+        &["--model", model.to_str().unwrap(), "--segments"],
+        &pcm(&[0.0; 4]),
+    );
+
+    out.result.unwrap();
+    assert_eq!(
+        out.stdout,
+        "0\t1500\t heard 4 samples,\n1500\t2000\t done. \n"
+    );
+    assert!(!engine.seen.borrow().as_ref().unwrap().1.single_segment);
+}
+
+#[test]
+fn truncated_pcm_fails_without_stdout() {
+    let model = model_file();
+
+    let out = exec(
+        &FakeEngine::default(),
+        false,
+        &["--model", model.to_str().unwrap()],
+        &[0, 0, 0, 0, 1],
+    );
+
+    let err = out.result.unwrap_err();
+    assert!(format!("{err:#}").contains("not a multiple of 4"));
+    assert!(out.stdout.is_empty());
+}
+
+#[test]
+fn missing_model_is_reported() {
+    let out = exec(
+        &FakeEngine::default(),
+        false,
+        &["--model", "/nonexistent/ggml-tiny.bin"],
+        &[],
+    );
+
+    let err = out.result.unwrap_err();
+    assert!(format!("{err:#}").contains("model file not found"));
+}
+
+#[test]
+fn decode_failure_keeps_stdout_empty() {
+    let model = model_file();
+    let engine = FakeEngine {
+        fail_decode: true,
+        ..Default::default()
+    };
+
+    let out = exec(
+        &engine,
+        false,
+        &["--model", model.to_str().unwrap()],
+        &pcm(&[0.0]),
+    );
+
+    let err = format!("{:#}", out.result.unwrap_err());
+    assert!(err.contains("transcription failed"), "{err}");
+    assert!(err.contains("out of memory"), "{err}");
+    assert!(out.stdout.is_empty());
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn probe_lists_every_device_and_succeeds_with_a_usable_gpu() {
+    let engine = FakeEngine {
+        devices: vec![
+            device(DeviceKind::Cpu, true, "CPU"),
+            device(DeviceKind::Gpu, true, "NVIDIA GeForce RTX 4070"),
+        ],
+        ..Default::default()
+    };
+
+    let out = exec(&engine, true, &["--probe"], &[]);
+
+    out.result.unwrap();
+    assert_eq!(
+        out.stdout,
+        "cpu\t1\t8589934592\t4294967296\tCPU\n\
+         gpu\t1\t8589934592\t4294967296\tNVIDIA GeForce RTX 4070\n"
+    );
+}
+
+#[test]
+fn gpu_probe_fails_when_only_the_cpu_came_up() {
+    let engine = FakeEngine {
+        devices: vec![
+            device(DeviceKind::Cpu, true, "CPU"),
+            device(DeviceKind::Gpu, false, "Radeon"),
+        ],
+        ..Default::default()
+    };
+
+    let out = exec(&engine, true, &["--probe"], &[]);
+
+    let err = out.result.unwrap_err();
+    assert!(err.to_string().contains("no usable GPU device"));
+    // The lines are still printed so the app can show what was found.
+    assert_eq!(out.stdout.lines().count(), 2);
+}
@@ -90,7 +90,8 @@ function Stage-CudaDlls {
 
 if ($Backend -eq "all") {
     foreach ($d in Get-ChildItem $cratesDir -Directory) {
-        if ($d.Name -like "flov-whisper-*") {
+        # flov-whisper-core is the shared protocol library, not a sidecar.
+        if ($d.Name -like "flov-whisper-*" -and $d.Name -ne "flov-whisper-core") {
             $name = $d.Name -replace "^flov-whisper-", ""
             Build-One $name
         }
@@ -0,0 +1,404 @@
+// Opt-in archive of raw recordings, for debugging and re-transcription.
+//
+// With `[archive] enabled = true` every recording that reaches the
+// transcriber is kept as `<data dir>/recordings/<unix ms>.wav` (16 kHz
+// mono 16-bit PCM, as captured — before denoise and gain) next to a
+// `<unix ms>.json` sidecar with what became of it: the raw transcript
+// and backend or the error, the text that was inserted, and any later
+// re-transcriptions with another model or backend. The id is the history
+// entry's link to its audio. Old files go once the archive is over
+// `max_mb` or older than `max_days`, oldest first.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::TRANSCRIBE_SAMPLE_RATE;
+use crate::levels::Levels;
+
+/// One archived recording, as stored in `<id>.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix milliseconds when it was saved; also the file stem.
+    pub id: String,
+    pub seconds: f64,
+    pub levels: Levels,
+    /// Raw transcript, before post-processing.
+    #[serde(default)]
+    pub transcript: Option<String>,
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// What was inserted, after post-processing.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Why transcription failed, when it did.
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub retranscriptions: Vec<Retranscription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Retranscription {
+    pub backend: String,
+    /// Catalog id of the model.
+    pub model: String,
+    pub text: String,
+    /// Unix milliseconds.
+    pub at: u64,
+    pub elapsed_ms: u64,
+}
+
+pub struct Archive {
+    dir: PathBuf,
+    enabled: AtomicBool,
+    /// 0 = no size limit.
+    max_bytes: u64,
+    /// None = kept forever.
+    max_age: Option<Duration>,
+    /// Serializes read-modify-write of the JSON sidecars between the
+    /// pipeline thread and re-transcriptions.
+    lock: Mutex<()>,
+}
+
+impl Archive {
+    pub fn new(dir: PathBuf, enabled: bool, max_mb: u64, max_days: u64) -> Self {
+        Self {
+            dir,
+            enabled: AtomicBool::new(enabled),
+            max_bytes: max_mb * 1024 * 1024,
+            max_age: (max_days > 0).then(|| Duration::from_secs(max_days * 24 * 60 * 60)),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// `[archive]` from flov.toml, under `<data dir>/recordings/`.
+    pub fn from_config(cfg: &crate::config::ArchiveConfig) -> Self {
+        let dir = crate::paths::recordings_dir().unwrap_or_else(|e| {
+            tracing::warn!("recordings dir unavailable: {:#}", e);
+            PathBuf::from("recordings")
+        });
+        Self::new(dir, cfg.enabled, cfg.max_mb, cfg.max_days)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Archives a recording and returns its id; `None` while the archive
+    /// is off. Prunes old recordings afterwards.
+    pub fn save(&self, samples: &[f32], levels: Levels) -> Result<Option<String>> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("create {:?}", self.dir))?;
+        let _guard = self.lock.lock().unwrap();
+        // Two saves within the same millisecond would share a name.
+        let mut stamp = now_ms();
+        while self.wav_path(&stamp.to_string()).exists() {
+            stamp += 1;
+        }
+        let id = stamp.to_string();
+        write_pcm16(&self.wav_path(&id), samples)?;
+        self.write_entry(&Entry {
+            id: id.clone(),
+            seconds: samples.len() as f64 / TRANSCRIBE_SAMPLE_RATE as f64,
+            levels,
+            ..Entry::default()
+        })?;
+        if let Err(e) = self.prune_locked(SystemTime::now()) {
+            tracing::warn!("recording archive prune failed: {:#}", e);
+        }
+        Ok(Some(id))
+    }
+
+    /// Changes an entry's sidecar in place.
+    pub fn update(&self, id: &str, change: impl FnOnce(&mut Entry)) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entry = self.read_entry(id)?;
+        change(&mut entry);
+        self.write_entry(&entry)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Entry> {
+        let _guard = self.lock.lock().unwrap();
+        self.read_entry(id)
+    }
+
+    /// All entries, newest first. Unreadable sidecars are skipped.
+    pub fn list(&self) -> Vec<Entry> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries: Vec<Entry> = self
+            .ids()
+            .iter()
+            .filter_map(|id| {
+                self.read_entry(id)
+                    .map_err(|e| tracing::warn!("skipping archived recording {}: {:#}", id, e))
+                    .ok()
+            })
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.id.parse::<u64>().unwrap_or(0)));
+        entries
+    }
+
+    /// The recording's samples, as saved.
+    pub fn load_samples(&self, id: &str) -> Result<Vec<f32>> {
+        let path = self.wav_path(id);
+        let reader = hound::WavReader::open(&path).with_context(|| format!("open {:?}", path))?;
+        reader
+            .into_samples::<i16>()
+            .map(|s| s.map(|s| s as f32 / i16::MAX as f32))
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("read {:?}", path))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        self.remove(id)
+    }
+
+    /// Drops recordings older than `max_age`, then the oldest ones until
+    /// the archive fits in `max_bytes`. The newest always stays.
+    pub fn prune(&self, now: SystemTime) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        self.prune_locked(now)
+    }
+
+    fn prune_locked(&self, now: SystemTime) -> Result<()> {
+        let now_ms = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        // Oldest first.
+        let mut ids: Vec<(u64, String)> = self
+            .ids()
+            .into_iter()
+            .filter_map(|id| Some((id.parse().ok()?, id)))
+            .collect();
+        ids.sort();
+        let Some(((_, newest), older)) = ids.split_last() else {
+            return Ok(());
+        };
+
+        let mut kept = Vec::new();
+        for (stamp, id) in older {
+            let expired = self
+                .max_age
+                .is_some_and(|age| now_ms.saturating_sub(*stamp) > age.as_millis() as u64);
+            if expired {
+                self.remove(id)?;
+            } else {
+                kept.push(id);
+            }
+        }
+        if self.max_bytes == 0 {
+            return Ok(());
+        }
+        let size = |id: &str| {
+            [self.wav_path(id), self.json_path(id)]
+                .iter()
+                .filter_map(|p| std::fs::metadata(p).ok())
+                .map(|m| m.len())
+                .sum::<u64>()
+        };
+        let mut total: u64 = kept.iter().map(|id| size(id)).sum::<u64>() + size(newest);
+        for id in kept {
+            if total <= self.max_bytes {
+                break;
+            }
+            total -= size(id);
+            self.remove(id)?;
+        }
+        Ok(())
+    }
+
+    /// Ids of the recordings on disk, in no particular order.
+    fn ids(&self) -> Vec<String> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        dir.filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| p.file_stem()?.to_str().map(str::to_string))
+            .collect()
+    }
+
+    fn remove(&self, id: &str) -> Result<()> {
+        for path in [self.wav_path(id), self.json_path(id)] {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("remove {:?}", path)),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_entry(&self, id: &str) -> Result<Entry> {
+        let path = self.json_path(id);
+        let json = std::fs::read_to_string(&path).with_context(|| format!("read {:?}", path))?;
+        serde_json::from_str(&json).with_context(|| format!("parse {:?}", path))
+    }
+
+    fn write_entry(&self, entry: &Entry) -> Result<()> {
+        let path = self.json_path(&entry.id);
+        let json = serde_json::to_string_pretty(entry)?;
+        std::fs::write(&path, json).with_context(|| format!("write {:?}", path))
+    }
+
+    /// Ids are unix-ms stamps; anything else (`../x` from a command
+    /// argument) is refused a path.
+    fn wav_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.wav", sanitize(id)))
+    }
+
+    fn json_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", sanitize(id)))
+    }
+}
+
+fn sanitize(id: &str) -> &str {
+    if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) {
+        id
+    } else {
+        "invalid"
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 16 kHz mono 16-bit PCM WAV: half the size of the float dumps, and
+/// nothing whisper can hear is lost.
+fn write_pcm16(path: &Path, samples: &[f32]) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: TRANSCRIBE_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer =
+        hound::WavWriter::create(path, spec).with_context(|| format!("create {:?}", path))?;
+    for &s in samples {
+        writer.write_sample((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer
+        .finalize()
+        .with_context(|| format!("finalize {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("flov-archive-{}-{}", name, now_ms()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn at(ms: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn disabled_archive_saves_nothing() {
+        let dir = temp_dir("off");
+        let archive = Archive::new(dir.clone(), false, 0, 0);
+
+        assert_eq!(archive.save(&[0.1; 1600], Levels::default()).unwrap(), None);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn save_round_trips_samples_and_entry() {
+        let dir = temp_dir("roundtrip");
+        let archive = Archive::new(dir.clone(), true, 0, 0);
+        let samples: Vec<f32> = (0..16_000)
+            .map(|i| (i as f32 / 100.0).sin() * 0.5)
+            .collect();
+        let levels = Levels::measure(&samples);
+
+        let id = archive.save(&samples, levels).unwrap().unwrap();
+        archive
+            .update(&id, |e| {
+                e.transcript = Some("привет".into());
+                e.backend = Some("cpu".into());
+            })
+            .unwrap();
+
+        let loaded = archive.load_samples(&id).unwrap();
+        assert_eq!(loaded.len(), samples.len());
+        assert!(loaded
+            .iter()
+            .zip(&samples)
+            .all(|(a, b)| (a - b).abs() < 1e-4));
+        let entries = archive.list();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].seconds, 1.0);
+        assert_eq!(entries[0].levels, levels);
+        assert_eq!(entries[0].transcript.as_deref(), Some("привет"));
+
+        archive.delete(&id).unwrap();
+        assert!(archive.list().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prune_drops_old_then_oversized_but_keeps_newest() {
+        let dir = temp_dir("prune");
+        std::fs::create_dir_all(&dir).unwrap();
+        // 1 MB limit, 1 day max age.
+        let archive = Archive::new(dir.clone(), true, 1, 1);
+        let day = 24 * 60 * 60 * 1000;
+        let now = 10 * day;
+        // ~400 KB each.
+        let samples = vec![0.0f32; 200_000];
+        let ids = [now - 2 * day, now - 3000, now - 2000, now - 1000];
+        for stamp in ids {
+            let id = stamp.to_string();
+            write_pcm16(&archive.wav_path(&id), &samples).unwrap();
+            archive
+                .write_entry(&Entry {
+                    id,
+                    ..Entry::default()
+                })
+                .unwrap();
+        }
+
+        archive.prune(at(now)).unwrap();
+
+        let left: Vec<String> = archive.list().into_iter().map(|e| e.id).collect();
+        assert_eq!(left, vec![ids[3].to_string(), ids[2].to_string()]);
+
+        // A single recording over the limit still stays.
+        let unlimited = Archive::new(dir.clone(), true, 0, 0);
+        let huge = Archive {
+            max_bytes: 1,
+            ..unlimited
+        };
+        huge.prune(at(now)).unwrap();
+        assert_eq!(huge.list().len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn ids_outside_the_archive_are_refused() {
+        let archive = Archive::new(temp_dir("ids"), true, 0, 0);
+        assert!(archive.wav_path("../flov").ends_with("invalid.wav"));
+        assert!(archive.get("../flov").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+// Tauri commands for the Recordings view in Settings.
+//
+// Lists the archive (archive.rs) and re-runs an archived recording
+// through another backend/model pair. A re-transcription spawns a
+// sidecar and can take a while on CPU, so it runs on a worker thread and
+// reports back with `recording-retranscribed`; the entry on disk carries
+// the result, so the view just reloads.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::archive::{self, Archive, Entry, Retranscription};
+use crate::config::Config;
+use crate::models;
+use crate::transcribe::{self, BackendProbe, Transcriber};
+
+pub struct ArchiveState {
+    pub archive: Arc<Archive>,
+    pub transcriber: Arc<Transcriber>,
+    pub backend_probes: Arc<Mutex<Option<Vec<BackendProbe>>>>,
+    /// One re-transcription at a time.
+    pub running: Arc<AtomicBool>,
+}
+
+#[derive(Serialize)]
+pub struct ModelChoice {
+    pub id: String,
+    pub label: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct RecordingsView {
+    pub enabled: bool,
+    pub entries: Vec<Entry>,
+    /// What a re-transcription can run on.
+    pub backends: Vec<String>,
+    pub models: Vec<ModelChoice>,
+    pub running: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct RetranscribedEvent {
+    id: String,
+    error: Option<String>,
+}
+
+#[tauri::command]
+pub fn list_recordings(state: State<ArchiveState>) -> RecordingsView {
+    RecordingsView {
+        enabled: state.archive.enabled(),
+        entries: state.archive.list(),
+        backends: usable_backends(&state),
+        models: models::list(None)
+            .into_iter()
+            .filter(|m| m.downloaded)
+            .map(|m| ModelChoice {
+                id: m.id,
+                label: m.label,
+            })
+            .collect(),
+        running: state.running.load(Ordering::SeqCst),
+    }
+}
+
+/// Turns the archive on or off, live and in flov.toml.
+#[tauri::command]
+pub fn set_archive_enabled(enabled: bool, state: State<ArchiveState>) -> Result<(), String> {
+    Config::write_archive_enabled(enabled).map_err(|e| e.to_string())?;
+    state.archive.set_enabled(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_recording(id: String, state: State<ArchiveState>) -> Result<(), String> {
+    state.archive.delete(&id).map_err(|e| e.to_string())
+}
+
+/// Transcribes archived recording `id` again on `backend` with catalog
+/// model `model` and appends the result to its entry.
+#[tauri::command]
+pub fn retranscribe(
+    id: String,
+    backend: String,
+    model: String,
+    app: AppHandle,
+    state: State<ArchiveState>,
+) -> Result<(), String> {
+    let model_path = models::entry_local_path(&model).map_err(|e| e.to_string())?;
+    if !model_path.exists() {
+        return Err(format!("model {} is not downloaded", model));
+    }
+    let samples = state.archive.load_samples(&id).map_err(|e| e.to_string())?;
+    if state.running.swap(true, Ordering::SeqCst) {
+        return Err("a re-transcription is already running".into());
+    }
+
+    let archive = state.archive.clone();
+    let transcriber = state.transcriber.clone();
+    let running = state.running.clone();
+    std::thread::Builder::new()
+        .name("flov-retranscribe".into())
+        .spawn(move || {
+            let result = transcriber
+                .transcribe_on(&backend, &model_path, &samples)
+                .and_then(|out| {
+                    archive.update(&id, |entry| {
+                        entry.retranscriptions.push(Retranscription {
+                            backend: backend.clone(),
+                            model: model.clone(),
+                            text: out.text.trim().to_string(),
+                            at: archive::now_ms(),
+                            elapsed_ms: out.elapsed.as_millis() as u64,
+                        })
+                    })
+                });
+            let error = result.err().map(|e| {
+                tracing::error!("re-transcribe {} on {}/{}: {:#}", id, backend, model, e);
+                format!("{:#}", e)
+            });
+            running.store(false, Ordering::SeqCst);
+            let _ = app.emit("recording-retranscribed", RetranscribedEvent { id, error });
+        })
+        .map_err(|e| {
+            state.running.store(false, Ordering::SeqCst);
+            e.to_string()
+        })?;
+    Ok(())
+}
+
+/// Backends that passed the startup probe, or every installed one while
+/// it's still running.
+fn usable_backends(state: &ArchiveState) -> Vec<String> {
+    match state.backend_probes.lock().unwrap().as_deref() {
+        Some(probes) => transcribe::rank_backends(probes),
+        None => transcribe::available_backends(),
+    }
+}
@@ -1,16 +1,120 @@
 use anyhow::{Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::FromSample;
 use rustfft::{num_complex::Complex, FftPlanner};
-use std::sync::{Arc, Mutex};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::config::AudioConfig;
+use crate::levels::{Levels, Meter};
+use crate::resample::{Quality, Resampler};
 
 const SPECTRUM_FFT_SIZE: usize = 2048;
 const SPECTRUM_BANDS: usize = 20;
 pub const TRANSCRIBE_SAMPLE_RATE: u32 = 16_000;
+/// Ten seconds at the output rate; grows past that.
+const INITIAL_CAPTURE_CAPACITY: usize = TRANSCRIBE_SAMPLE_RATE as usize * 10;
+/// Pre-roll the tray toggle turns on when `[audio] preroll_ms` is 0.
+pub const DEFAULT_PREROLL_MS: u32 = 500;
+/// Longer than this is just more audio whisper has to skip.
+pub const MAX_PREROLL_MS: u32 = 2_000;
 
 pub struct AudioRecorder {
+    /// `[audio] device`; `None` → system default.
+    preferred: Mutex<Option<String>>,
+    prefs: Mutex<StreamPrefs>,
+    /// Re-resolved when the device list changes or a stream fails, so a
+    /// switch or an unplug doesn't need a restart.
+    input: Mutex<Input>,
+    output_sample_rate: u32,
+    /// `[audio] resample_quality` for the device → 16 kHz conversion.
+    quality: Quality,
+    /// Always-open stream feeding the pre-roll ring; `None` when off.
+    preroll: Mutex<Option<Preroll>>,
+    /// Held for the whole of a recording, so the device watcher can't
+    /// swap the stream out from under one.
+    recording: Mutex<()>,
+}
+
+#[derive(Clone)]
+struct Input {
+    name: String,
     device: cpal::Device,
     config: cpal::SupportedStreamConfig,
-    output_sample_rate: u32,
+    /// 0-based channel to record; `None` averages them all.
+    channel: Option<usize>,
+}
+
+/// The `[audio]` choices that shape how a device is opened.
+#[derive(Clone, Default)]
+struct StreamPrefs {
+    sample_rate: Option<u32>,
+    sample_format: Option<cpal::SampleFormat>,
+    /// Device name → 1-based channel; 0 or absent averages all.
+    channels: BTreeMap<String, u16>,
+}
+
+impl StreamPrefs {
+    fn from_config(cfg: &AudioConfig) -> Self {
+        let sample_format = cfg.stream_format.as_deref().and_then(|name| {
+            let parsed = parse_sample_format(name);
+            if parsed.is_none() {
+                tracing::warn!(
+                    "unknown audio.stream_format '{}', using the device default",
+                    name
+                );
+            }
+            parsed
+        });
+        Self {
+            sample_rate: cfg.stream_sample_rate,
+            sample_format,
+            channels: cfg.channels.clone(),
+        }
+    }
+}
+
+pub fn parse_sample_format(name: &str) -> Option<cpal::SampleFormat> {
+    use cpal::SampleFormat as F;
+    Some(match name {
+        "i8" => F::I8,
+        "i16" => F::I16,
+        "i32" => F::I32,
+        "i64" => F::I64,
+        "u8" => F::U8,
+        "u16" => F::U16,
+        "u32" => F::U32,
+        "u64" => F::U64,
+        "f32" => F::F32,
+        "f64" => F::F64,
+        _ => return None,
+    })
+}
+
+/// The supported config matching `rate` / `format`, where either may be
+/// unset (the default's rate; any format). Among matches it prefers the
+/// default's format, then its channel count, then more channels. `None`
+/// when nothing matches.
+fn pick_config(
+    ranges: &[cpal::SupportedStreamConfigRange],
+    default: &cpal::SupportedStreamConfig,
+    rate: Option<u32>,
+    format: Option<cpal::SampleFormat>,
+) -> Option<cpal::SupportedStreamConfig> {
+    let rate = rate.unwrap_or(default.sample_rate().0);
+    ranges
+        .iter()
+        .filter(|r| format.is_none_or(|f| r.sample_format() == f))
+        .filter(|r| r.min_sample_rate().0 <= rate && rate <= r.max_sample_rate().0)
+        .max_by_key(|r| {
+            (
+                r.sample_format() == default.sample_format(),
+                r.channels() == default.channels(),
+                r.channels(),
+            )
+        })
+        .map(|r| r.with_sample_rate(cpal::SampleRate(rate)))
 }
 
 struct SpectrumRing {
@@ -46,9 +150,55 @@ impl SpectrumRing {
     }
 }
 
+/// The last `capacity` mono samples at the output rate, kept between
+/// recordings so the first syllable survives device warm-up and a late
+/// hotkey press.
+struct PrerollRing {
+    data: Vec<f32>,
+    next: usize,
+    len: usize,
+}
+
+impl PrerollRing {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: vec![0.0; capacity],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        if self.data.is_empty() {
+            return;
+        }
+        self.data[self.next] = sample;
+        self.next = (self.next + 1) % self.data.len();
+        self.len = (self.len + 1).min(self.data.len());
+    }
+
+    /// Oldest-first contents; leaves the ring empty.
+    fn take_ordered(&mut self) -> Vec<f32> {
+        let mut out = Vec::with_capacity(self.len);
+        if self.len < self.data.len() {
+            out.extend_from_slice(&self.data[..self.len]);
+        } else {
+            out.extend_from_slice(&self.data[self.next..]);
+            out.extend_from_slice(&self.data[..self.next]);
+        }
+        self.next = 0;
+        self.len = 0;
+        out
+    }
+}
+
 struct CaptureState {
+    /// At the output rate.
     samples: Vec<f32>,
+    /// At the device rate; only drives the pill's bars.
     spectrum: SpectrumRing,
+    /// Levels of `samples` so far, for the pill's meter.
+    meter: Meter,
 }
 
 impl CaptureState {
@@ -56,18 +206,202 @@ impl CaptureState {
         Self {
             samples: Vec::with_capacity(sample_capacity),
             spectrum: SpectrumRing::new(),
+            meter: Meter::default(),
         }
     }
+}
 
-    fn push(&mut self, sample: f32) {
-        self.samples.push(sample);
-        self.spectrum.push(sample);
+/// Where the input callback writes: the pre-roll ring between
+/// recordings, the capture while one runs. Resampling happens here, in
+/// the callback, so both hold 16 kHz audio and the filter state carries
+/// across the pre-roll / capture boundary.
+struct Tap {
+    /// Recorded channel, as in `Input::channel`.
+    channel: Option<usize>,
+    resampler: Resampler,
+    /// Reused per callback: device-rate mono in, output-rate out.
+    mono: Vec<f32>,
+    resampled: Vec<f32>,
+    preroll: PrerollRing,
+    capture: Option<CaptureState>,
+    /// Set by the stream's error callback when the device went away.
+    lost: bool,
+}
+
+impl Tap {
+    fn idle(preroll_capacity: usize, channel: Option<usize>, resampler: Resampler) -> Self {
+        Self {
+            channel,
+            resampler,
+            mono: Vec::new(),
+            resampled: Vec::new(),
+            preroll: PrerollRing::new(preroll_capacity),
+            capture: None,
+            lost: false,
+        }
     }
+
+    /// Takes one callback's worth of device-rate mono samples.
+    fn push_block(&mut self, block: &[f32]) {
+        if let Some(capture) = &mut self.capture {
+            for &sample in block {
+                capture.spectrum.push(sample);
+            }
+        }
+        self.resampled.clear();
+        self.resampler.process(block, &mut self.resampled);
+        match &mut self.capture {
+            Some(capture) => {
+                capture.meter.push(&self.resampled);
+                capture.samples.extend_from_slice(&self.resampled);
+            }
+            None => {
+                for &sample in &self.resampled {
+                    self.preroll.push(sample);
+                }
+            }
+        }
+    }
+
+    /// Takes the chosen channel of interleaved frames (or their average)
+    /// and pushes it.
+    fn push_frames<T: Copy>(&mut self, data: &[T], channels: usize, to_f32: impl Fn(T) -> f32) {
+        let mut mono = std::mem::take(&mut self.mono);
+        mono.clear();
+        match self.channel {
+            Some(c) => mono.extend(
+                data.chunks(channels)
+                    .map(|frame| frame.get(c).map_or(0.0, |&s| to_f32(s))),
+            ),
+            None => mono.extend(
+                data.chunks(channels)
+                    .map(|frame| frame.iter().map(|&s| to_f32(s)).sum::<f32>() / channels as f32),
+            ),
+        }
+        self.push_block(&mono);
+        self.mono = mono;
+    }
+
+    /// Starts a capture that opens with whatever the ring holds.
+    fn begin(&mut self, sample_capacity: usize) {
+        let mut capture = CaptureState::with_capacity(sample_capacity);
+        capture.samples.extend(self.preroll.take_ordered());
+        capture.meter.push(&capture.samples);
+        self.capture = Some(capture);
+    }
+
+    /// Ends the capture, draining the resampler's last few milliseconds.
+    fn finish(&mut self) -> Vec<f32> {
+        let Some(mut capture) = self.capture.take() else {
+            return Vec::new();
+        };
+        self.resampler.flush(&mut capture.samples);
+        capture.samples
+    }
+}
+
+/// Owns the always-open stream on its own thread (cpal streams aren't
+/// `Send` everywhere); dropping it closes the microphone.
+struct Preroll {
+    ms: u32,
+    tap: Arc<Mutex<Tap>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Drop for Preroll {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Opens and starts a stream on `input` feeding a new tap. `recording`
+/// starts the capture before the first callback; otherwise the tap fills
+/// a `preroll_ms` ring.
+fn open_stream(
+    input: &Input,
+    quality: Quality,
+    preroll_ms: u32,
+    recording: bool,
+) -> Result<(cpal::Stream, Arc<Mutex<Tap>>)> {
+    let mut tap = Tap::idle(
+        TRANSCRIBE_SAMPLE_RATE as usize * preroll_ms as usize / 1000,
+        input.channel,
+        Resampler::new(
+            input.config.sample_rate().0,
+            TRANSCRIBE_SAMPLE_RATE,
+            quality,
+        ),
+    );
+    if recording {
+        tap.begin(INITIAL_CAPTURE_CAPACITY);
+    }
+    let tap = Arc::new(Mutex::new(tap));
+    let stream = build_mono_stream(&input.device, &input.config, tap.clone())?;
+    stream.play()?;
+    Ok((stream, tap))
+}
+
+fn build_mono_stream(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    tap: Arc<Mutex<Tap>>,
+) -> Result<cpal::Stream> {
+    use cpal::SampleFormat as F;
+    match config.sample_format() {
+        F::I8 => build_typed::<i8>(device, config, tap),
+        F::I16 => build_typed::<i16>(device, config, tap),
+        F::I32 => build_typed::<i32>(device, config, tap),
+        F::I64 => build_typed::<i64>(device, config, tap),
+        F::U8 => build_typed::<u8>(device, config, tap),
+        F::U16 => build_typed::<u16>(device, config, tap),
+        F::U32 => build_typed::<u32>(device, config, tap),
+        F::U64 => build_typed::<u64>(device, config, tap),
+        F::F32 => build_typed::<f32>(device, config, tap),
+        F::F64 => build_typed::<f64>(device, config, tap),
+        other => Err(anyhow::anyhow!("Unsupported sample format {}", other)),
+    }
+}
+
+fn build_typed<T>(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    tap: Arc<Mutex<Tap>>,
+) -> Result<cpal::Stream>
+where
+    T: cpal::SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels() as usize;
+    let stream_config: cpal::StreamConfig = config.clone().into();
+    let tap_for_err = tap.clone();
+    let err_fn = move |err: cpal::StreamError| {
+        tracing::warn!("audio stream error: {}", err);
+        if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+            tap_for_err.lock().unwrap().lost = true;
+        }
+    };
+
+    let stream = device.build_input_stream(
+        &stream_config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            tap.lock()
+                .unwrap()
+                .push_frames(data, channels, f32::from_sample_);
+        },
+        err_fn,
+        None,
+    )?;
+    Ok(stream)
 }
 
 /// Enumerate currently-connected input devices by name (whatever cpal /
-/// WASAPI reports). Cheap to call but order can drift between calls if
-/// the user (un)plugs hardware — UI should re-fetch on demand.
+/// WASAPI reports). Not cheap — ALSA and WASAPI open each device to
+/// name it — and the order can drift between calls if the user
+/// (un)plugs hardware.
 pub fn list_input_devices() -> Vec<String> {
     let host = cpal::default_host();
     match host.input_devices() {
@@ -79,58 +413,118 @@ pub fn list_input_devices() -> Vec<String> {
     }
 }
 
-impl AudioRecorder {
-    pub fn new(target_sample_rate: u32, preferred_device: Option<&str>) -> Result<Self> {
-        let host = cpal::default_host();
-        let device = match preferred_device.and_then(|s| if s.is_empty() { None } else { Some(s) })
-        {
-            Some(name) => {
-                let found = host
-                    .input_devices()
-                    .ok()
-                    .and_then(|mut iter| iter.find(|d| d.name().ok().as_deref() == Some(name)));
-                match found {
-                    Some(d) => d,
-                    None => {
-                        tracing::warn!(
-                            "preferred input '{}' not found, falling back to default",
-                            name
-                        );
-                        host.default_input_device()
-                            .context("No input device available")?
-                    }
+/// The system default input's name, for noticing a default switch.
+pub fn default_input_name() -> Option<String> {
+    cpal::default_host()
+        .default_input_device()
+        .and_then(|d| d.name().ok())
+}
+
+/// Opens `preferred` if it's connected, otherwise the system default,
+/// with the stream config and channel `prefs` ask for.
+fn open_input(preferred: Option<&str>, prefs: &StreamPrefs) -> Result<Input> {
+    let host = cpal::default_host();
+    let device = match preferred.filter(|s| !s.is_empty()) {
+        Some(name) => {
+            let found = host
+                .input_devices()
+                .ok()
+                .and_then(|mut iter| iter.find(|d| d.name().ok().as_deref() == Some(name)));
+            match found {
+                Some(d) => d,
+                None => {
+                    tracing::warn!(
+                        "preferred input '{}' not found, falling back to default",
+                        name
+                    );
+                    host.default_input_device()
+                        .context("No input device available")?
                 }
             }
-            None => host
-                .default_input_device()
-                .context("No input device available")?,
-        };
+        }
+        None => host
+            .default_input_device()
+            .context("No input device available")?,
+    };
 
-        tracing::info!("Using input device: {}", device.name().unwrap_or_default());
+    let name = device.name().unwrap_or_default();
+    tracing::info!("Using input device: {}", name);
 
-        let config = device
-            .default_input_config()
-            .context("Failed to get default input config")?;
+    let default = device
+        .default_input_config()
+        .context("Failed to get default input config")?;
+    let config = if prefs.sample_rate.is_none() && prefs.sample_format.is_none() {
+        default
+    } else {
+        let ranges: Vec<_> = device
+            .supported_input_configs()
+            .map(|iter| iter.collect())
+            .unwrap_or_default();
+        pick_config(&ranges, &default, prefs.sample_rate, prefs.sample_format).unwrap_or_else(
+            || {
+                tracing::warn!(
+                    "'{}' has no {:?} Hz / {:?} input config, using its default",
+                    name,
+                    prefs.sample_rate,
+                    prefs.sample_format
+                );
+                default
+            },
+        )
+    };
 
-        tracing::info!(
-            "Audio config: {} Hz, {} channels, {:?}",
-            config.sample_rate().0,
-            config.channels(),
-            config.sample_format()
-        );
+    let channel = match prefs.channels.get(&name).copied().unwrap_or(0) {
+        0 => None,
+        n if n <= config.channels() => Some(n as usize - 1),
+        n => {
+            tracing::warn!(
+                "'{}' has {} channels, can't record channel {}; averaging all",
+                name,
+                config.channels(),
+                n
+            );
+            None
+        }
+    };
+
+    tracing::info!(
+        "Audio config: {} Hz, {} channels, {:?}, recording {}",
+        config.sample_rate().0,
+        config.channels(),
+        config.sample_format(),
+        channel.map_or("all channels".to_string(), |c| format!("channel {}", c + 1))
+    );
+
+    Ok(Input {
+        name,
+        device,
+        config,
+        channel,
+    })
+}
+
+impl AudioRecorder {
+    pub fn new(cfg: &AudioConfig) -> Result<Self> {
+        let preferred = cfg.device.clone().filter(|s| !s.is_empty());
+        let prefs = StreamPrefs::from_config(cfg);
+        let input = open_input(preferred.as_deref(), &prefs)?;
 
-        if target_sample_rate != TRANSCRIBE_SAMPLE_RATE {
+        if cfg.sample_rate != TRANSCRIBE_SAMPLE_RATE {
             tracing::warn!(
                 "configured audio.sample_rate={} ignored; whisper sidecars require {} Hz PCM",
-                target_sample_rate,
+                cfg.sample_rate,
                 TRANSCRIBE_SAMPLE_RATE
             );
         }
 
         Ok(Self {
-            device,
-            config,
+            preferred: Mutex::new(preferred),
+            prefs: Mutex::new(prefs),
+            input: Mutex::new(input),
             output_sample_rate: TRANSCRIBE_SAMPLE_RATE,
+            quality: cfg.resample_quality,
+            preroll: Mutex::new(None),
+            recording: Mutex::new(()),
         })
     }
 
@@ -138,7 +532,192 @@ impl AudioRecorder {
         self.output_sample_rate
     }
 
+    /// Name of the device recordings use right now, which differs from
+    /// the saved choice after a fallback.
+    pub fn device_name(&self) -> String {
+        self.input.lock().unwrap().name.clone()
+    }
+
+    /// Channel count of the open device config.
+    pub fn device_channels(&self) -> u16 {
+        self.input.lock().unwrap().config.channels()
+    }
+
+    /// 1-based channel recorded from the current device; 0 = all, mixed.
+    pub fn channel(&self) -> u16 {
+        self.input
+            .lock()
+            .unwrap()
+            .channel
+            .map_or(0, |c| c as u16 + 1)
+    }
+
+    /// Records only `channel` (1-based; 0 = average all) from the current
+    /// device, and from it alone: the choice is kept per device name.
+    pub fn set_channel(&self, channel: u16) -> Result<()> {
+        let name = self.device_name();
+        self.prefs.lock().unwrap().channels.insert(name, channel);
+        self.reopen(true).map(|_| ())
+    }
+
+    /// Switches to `preferred` (`None` = system default) without a
+    /// restart.
+    pub fn set_device(&self, preferred: Option<&str>) -> Result<()> {
+        *self.preferred.lock().unwrap() = preferred.filter(|s| !s.is_empty()).map(str::to_string);
+        self.reopen(false).map(|_| ())
+    }
+
+    /// Re-resolves the input: the saved choice while it's connected,
+    /// otherwise the system default. `force` reopens even under the same
+    /// name, since a replugged device leaves the old handle stale.
+    /// Returns whether anything was reopened.
+    pub fn reopen(&self, force: bool) -> Result<bool> {
+        let preferred = self.preferred.lock().unwrap().clone();
+        let prefs = self.prefs.lock().unwrap().clone();
+        let next = open_input(preferred.as_deref(), &prefs)?;
+        {
+            let mut input = self.input.lock().unwrap();
+            if !force && input.name == next.name {
+                return Ok(false);
+            }
+            tracing::info!("input device: '{}' → '{}'", input.name, next.name);
+            *input = next;
+        }
+        // The pre-roll stream follows the device.
+        let mut slot = self.preroll.lock().unwrap();
+        if let Some(ms) = slot.as_ref().map(|p| p.ms) {
+            *slot = None;
+            *slot = Some(self.start_preroll(ms)?);
+        }
+        Ok(true)
+    }
+
+    /// `reopen(true)` unless a recording is in progress (`None` then), or
+    /// `idle` says one is about to start. The recording lock is held
+    /// across the check and the reopen, so a recording that starts
+    /// meanwhile waits for the new stream instead of losing its own.
+    pub fn reopen_when_idle(&self, idle: impl Fn() -> bool) -> Result<Option<bool>> {
+        let Ok(_recording) = self.recording.try_lock() else {
+            return Ok(None);
+        };
+        if !idle() {
+            return Ok(None);
+        }
+        self.reopen(true).map(Some)
+    }
+
+    /// The saved device isn't the one in use: it was unplugged and the
+    /// default stands in until it's back.
+    pub fn fell_back(&self) -> bool {
+        let preferred = self.preferred.lock().unwrap();
+        preferred
+            .as_deref()
+            .is_some_and(|name| name != self.input.lock().unwrap().name)
+    }
+
+    /// Whether `name` is the saved device.
+    pub fn prefers(&self, name: &str) -> bool {
+        self.preferred.lock().unwrap().as_deref() == Some(name)
+    }
+
+    /// The pre-roll stream's device went away.
+    pub fn preroll_lost(&self) -> bool {
+        self.preroll
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|p| p.tap.lock().unwrap().lost)
+    }
+
+    /// A capturing stream on the current input, for one recording.
+    fn open_recording_stream(&self) -> Result<(cpal::Stream, Arc<Mutex<Tap>>)> {
+        let input = self.input.lock().unwrap().clone();
+        open_stream(&input, self.quality, 0, true)
+    }
+
+    /// Current pre-roll length; 0 when the microphone is only open while
+    /// recording.
+    pub fn preroll_ms(&self) -> u32 {
+        self.preroll.lock().unwrap().as_ref().map_or(0, |p| p.ms)
+    }
+
+    /// Keeps the input stream open between recordings and prepends its
+    /// last `ms` (clamped to MAX_PREROLL_MS) to each one. 0 closes it.
+    pub fn set_preroll(&self, ms: u32) -> Result<()> {
+        let ms = ms.min(MAX_PREROLL_MS);
+        let mut slot = self.preroll.lock().unwrap();
+        if slot.as_ref().map_or(0, |p| p.ms) == ms {
+            return Ok(());
+        }
+        // Close the old stream first: some drivers allow one at a time.
+        *slot = None;
+        if ms == 0 {
+            tracing::info!("pre-roll off, microphone closed between recordings");
+            return Ok(());
+        }
+        *slot = Some(self.start_preroll(ms)?);
+        tracing::info!(
+            "pre-roll on: microphone stays open, keeping the last {} ms",
+            ms
+        );
+        Ok(())
+    }
+
+    fn start_preroll(&self, ms: u32) -> Result<Preroll> {
+        let input = self.input.lock().unwrap().clone();
+        let quality = self.quality;
+        let stop = Arc::new(AtomicBool::new(false));
+        let (ready_tx, ready_rx) = mpsc::channel::<Result<Arc<Mutex<Tap>>>>();
+
+        let stop_for_thread = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("flov-preroll".into())
+            .spawn(move || {
+                let stream = match open_stream(&input, quality, ms, false) {
+                    Ok((stream, tap)) => {
+                        let _ = ready_tx.send(Ok(tap));
+                        stream
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                while !stop_for_thread.load(Ordering::SeqCst) {
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                }
+                drop(stream);
+            })
+            .context("spawn pre-roll thread")?;
+
+        let tap = ready_rx
+            .recv()
+            .context("pre-roll thread exited before opening the stream")?
+            .context("open pre-roll stream")?;
+        Ok(Preroll {
+            ms,
+            tap,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The pre-roll tap, if pre-roll is on. A pre-roll stream whose
+    /// device vanished is reopened on whatever is connected now.
+    fn live_preroll(&self) -> Result<Option<Arc<Mutex<Tap>>>> {
+        let current = || self.preroll.lock().unwrap().as_ref().map(|p| p.tap.clone());
+        match current() {
+            Some(tap) if tap.lock().unwrap().lost => {
+                tracing::warn!("pre-roll device lost, re-resolving");
+                self.reopen(true)?;
+                Ok(current())
+            }
+            other => Ok(other),
+        }
+    }
+
     /// Record while condition is true, with frequency spectrum callback (20 bands)
+    /// that also gets the levels of everything captured so far.
     pub fn record_while_with_spectrum<F, C>(
         &self,
         should_continue: F,
@@ -146,58 +725,32 @@ impl AudioRecorder {
     ) -> Result<Vec<f32>>
     where
         F: Fn() -> bool + Send + 'static,
-        C: Fn(Vec<f32>) + Send + 'static,
+        C: Fn(Vec<f32>, Levels) + Send + 'static,
     {
-        let channels = self.config.channels() as usize;
-        let native_rate = self.config.sample_rate().0;
-        let initial_capacity = (native_rate as usize)
-            .saturating_mul(10)
-            .min(48_000usize * 30);
-        let capture = Arc::new(Mutex::new(CaptureState::with_capacity(initial_capacity)));
-
-        let err_fn = |err| {
-            eprintln!("Audio stream error: {}", err);
-        };
-
-        let stream = match self.config.sample_format() {
-            cpal::SampleFormat::F32 => {
-                let config: cpal::StreamConfig = self.config.clone().into();
-                let capture_clone = capture.clone();
-                self.device.build_input_stream(
-                    &config,
-                    move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                        let mut capture = capture_clone.lock().unwrap();
-                        for chunk in data.chunks(channels) {
-                            let mono: f32 = chunk.iter().sum::<f32>() / channels as f32;
-                            capture.push(mono);
-                        }
-                    },
-                    err_fn,
-                    None,
-                )?
+        let _recording = self.recording.lock().unwrap();
+        // With pre-roll on, the stream is already warm: start capturing
+        // from it, seeded with the ring. Otherwise open one for this
+        // recording, as before.
+        let (tap, stream) = match self.live_preroll()? {
+            Some(tap) => {
+                tap.lock().unwrap().begin(INITIAL_CAPTURE_CAPACITY);
+                (tap, None)
             }
-            cpal::SampleFormat::I16 => {
-                let config: cpal::StreamConfig = self.config.clone().into();
-                let capture_clone = capture.clone();
-                self.device.build_input_stream(
-                    &config,
-                    move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                        let mut capture = capture_clone.lock().unwrap();
-                        for chunk in data.chunks(channels) {
-                            let mono: f32 = chunk.iter().map(|&s| s as f32 / 32768.0).sum::<f32>()
-                                / channels as f32;
-                            capture.push(mono);
-                        }
-                    },
-                    err_fn,
-                    None,
-                )?
+            None => {
+                let (stream, tap) = match self.open_recording_stream() {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        // Most often the device was unplugged since the
+                        // last recording.
+                        tracing::warn!("input stream failed ({:#}), re-resolving device", e);
+                        self.reopen(true)?;
+                        self.open_recording_stream()?
+                    }
+                };
+                (tap, Some(stream))
             }
-            _ => return Err(anyhow::anyhow!("Unsupported sample format")),
         };
 
-        stream.play()?;
-
         let mut planner = FftPlanner::new();
         let fft = planner.plan_fft_forward(SPECTRUM_FFT_SIZE);
         let hann_window: Vec<f32> = (0..SPECTRUM_FFT_SIZE)
@@ -209,11 +762,23 @@ impl AudioRecorder {
         let mut fft_samples = [0.0f32; SPECTRUM_FFT_SIZE];
         let mut complex_buf = vec![Complex::new(0.0, 0.0); SPECTRUM_FFT_SIZE];
 
+        let mut lost = false;
         while should_continue() {
-            let has_full_window = {
-                let capture = capture.lock().unwrap();
-                capture.spectrum.copy_ordered_into(&mut fft_samples)
+            let (has_full_window, levels) = {
+                let tap = tap.lock().unwrap();
+                lost = tap.lost;
+                match &tap.capture {
+                    Some(c) => (
+                        c.spectrum.copy_ordered_into(&mut fft_samples),
+                        c.meter.levels(),
+                    ),
+                    None => (false, Levels::default()),
+                }
             };
+            if lost {
+                tracing::warn!("input device lost mid-recording; keeping what was captured");
+                break;
+            }
 
             if has_full_window {
                 for (slot, (&sample, &window)) in complex_buf
@@ -240,7 +805,7 @@ impl AudioRecorder {
                     .map(|&b| (b / max_val * 3.0).min(1.0))
                     .collect();
 
-                on_spectrum(spectrum);
+                on_spectrum(spectrum, levels);
             }
             // ~16 Hz emit rate. The wave looks smooth at this cadence,
             // and over a multi-hour session it halves the IPC traffic
@@ -252,43 +817,21 @@ impl AudioRecorder {
 
         drop(stream);
 
-        let samples = Arc::try_unwrap(capture)
-            .map_err(|_| anyhow::anyhow!("Failed to unwrap samples"))?
-            .into_inner()
-            .unwrap()
-            .samples;
-
-        if native_rate != self.output_sample_rate {
-            Ok(resample(&samples, native_rate, self.output_sample_rate))
-        } else {
-            Ok(samples)
+        let samples = tap.lock().unwrap().finish();
+        if lost {
+            if let Err(e) = self.reopen(true) {
+                tracing::warn!("no input to fall back to: {:#}", e);
+            }
         }
-    }
-}
-
-// Simple linear resampling
-fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
-    let ratio = from_rate as f64 / to_rate as f64;
-    let new_len = (samples.len() as f64 / ratio) as usize;
-    let mut result = Vec::with_capacity(new_len);
-
-    for i in 0..new_len {
-        let src_idx = i as f64 * ratio;
-        let idx = src_idx as usize;
-        let frac = src_idx - idx as f64;
-
-        let sample = if idx + 1 < samples.len() {
-            samples[idx] * (1.0 - frac as f32) + samples[idx + 1] * frac as f32
-        } else if idx < samples.len() {
-            samples[idx]
-        } else {
-            0.0
-        };
 
-        result.push(sample);
+        Ok(samples)
     }
+}
 
-    result
+/// One-shot band-limited resampling (`resample::Quality::Balanced`) for
+/// decoded files; live capture resamples in the callback instead.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    crate::resample::resample(samples, from_rate, to_rate, Quality::Balanced)
 }
 
 #[cfg(test)]
@@ -303,17 +846,167 @@ mod tests {
     }
 
     #[test]
-    fn resample_downsamples_linearly() {
-        let samples = [0.0, 0.25, 0.5, 0.75];
+    fn resample_scales_length_by_rate_ratio() {
+        let samples = [0.5; 300];
 
-        assert_eq!(resample(&samples, 4, 2), vec![0.0, 0.5]);
+        assert_eq!(resample(&samples, 48_000, 16_000).len(), 100);
+        assert_eq!(resample(&samples, 8_000, 16_000).len(), 600);
     }
 
     #[test]
-    fn resample_upsamples_with_last_sample_padding() {
-        let samples = [0.0, 1.0];
+    fn preroll_ring_returns_partial_fill_in_order() {
+        let mut ring = PrerollRing::new(4);
 
-        assert_eq!(resample(&samples, 2, 4), vec![0.0, 0.5, 1.0, 1.0]);
+        ring.push(1.0);
+        ring.push(2.0);
+
+        assert_eq!(ring.take_ordered(), vec![1.0, 2.0]);
+        assert!(ring.take_ordered().is_empty());
+    }
+
+    #[test]
+    fn preroll_ring_keeps_only_the_latest_samples() {
+        let mut ring = PrerollRing::new(3);
+
+        for i in 0..7 {
+            ring.push(i as f32);
+        }
+
+        assert_eq!(ring.take_ordered(), vec![4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn zero_length_preroll_ring_drops_everything() {
+        let mut ring = PrerollRing::new(0);
+
+        ring.push(1.0);
+
+        assert!(ring.take_ordered().is_empty());
+    }
+
+    fn passthrough() -> Resampler {
+        Resampler::new(16_000, 16_000, Quality::Fast)
+    }
+
+    #[test]
+    fn tap_prepends_preroll_to_capture() {
+        let mut tap = Tap::idle(2, None, passthrough());
+        tap.push_block(&[0.1, 0.2, 0.3]);
+
+        tap.begin(16);
+        tap.push_block(&[0.4]);
+
+        assert_eq!(tap.finish(), vec![0.2, 0.3, 0.4]);
+        // The ring starts over for the next recording.
+        tap.push_block(&[0.5]);
+        assert_eq!(tap.preroll.take_ordered(), vec![0.5]);
+    }
+
+    #[test]
+    fn capture_meter_covers_preroll_and_clipping() {
+        let mut tap = Tap::idle(2, None, passthrough());
+        tap.push_block(&[0.9, 0.5]);
+
+        tap.begin(16);
+        tap.push_block(&[-1.0, 0.0]);
+
+        let levels = tap.capture.as_ref().unwrap().meter.levels();
+        assert_eq!((levels.peak, levels.clipped), (1.0, 1));
+        assert!((levels.rms - (2.06f32 / 4.0).sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tap_downmixes_and_resamples_to_output_rate() {
+        let mut tap = Tap::idle(0, None, Resampler::new(48_000, 16_000, Quality::Fast));
+        tap.begin(16);
+        // Stereo, one channel silent: the downmix halves it.
+        let frames: Vec<i16> = (0..4800).flat_map(|_| [16_384i16, 0]).collect();
+
+        tap.push_frames(&frames, 2, |s| s as f32 / 32768.0);
+        let samples = tap.finish();
+
+        assert_eq!(samples.len(), 1600);
+        // Away from the edges a DC input comes through at its level.
+        assert!((samples[800] - 0.25).abs() < 1e-3, "{}", samples[800]);
+    }
+
+    #[test]
+    fn tap_records_only_the_chosen_channel() {
+        let mut tap = Tap::idle(0, Some(1), passthrough());
+        tap.begin(4);
+
+        tap.push_frames(&[0.9f32, 0.1, 0.0, 0.9, 0.2, 0.3], 3, |s| s);
+
+        assert_eq!(tap.finish(), vec![0.1, 0.2]);
+    }
+
+    #[test]
+    fn sample_conversions_cover_integer_formats() {
+        let mut tap = Tap::idle(0, None, passthrough());
+        tap.begin(8);
+
+        tap.push_frames(&[u16::MAX, 32_768], 1, f32::from_sample_);
+        tap.push_frames(&[i32::MIN], 1, f32::from_sample_);
+        tap.push_frames(&[0u8], 1, f32::from_sample_);
+
+        let samples = tap.finish();
+        assert!((samples[0] - 1.0).abs() < 1e-3, "{:?}", samples);
+        assert_eq!(&samples[1..], &[0.0, -1.0, -1.0]);
+    }
+
+    fn range(
+        channels: u16,
+        min: u32,
+        max: u32,
+        format: cpal::SampleFormat,
+    ) -> cpal::SupportedStreamConfigRange {
+        cpal::SupportedStreamConfigRange::new(
+            channels,
+            cpal::SampleRate(min),
+            cpal::SampleRate(max),
+            cpal::SupportedBufferSize::Unknown,
+            format,
+        )
+    }
+
+    #[test]
+    fn pick_config_honours_rate_and_format() {
+        use cpal::SampleFormat as F;
+        let ranges = [
+            range(2, 44_100, 48_000, F::I16),
+            range(2, 44_100, 96_000, F::I32),
+            range(8, 44_100, 96_000, F::I32),
+            range(2, 44_100, 96_000, F::F32),
+        ];
+        let default = ranges[0].with_sample_rate(cpal::SampleRate(48_000));
+
+        let i32_96k = pick_config(&ranges, &default, Some(96_000), Some(F::I32)).unwrap();
+        assert_eq!(i32_96k.sample_format(), F::I32);
+        assert_eq!(i32_96k.sample_rate().0, 96_000);
+        // Same channel count as the default wins over more channels.
+        assert_eq!(i32_96k.channels(), 2);
+
+        let f32_default_rate = pick_config(&ranges, &default, None, Some(F::F32)).unwrap();
+        assert_eq!(f32_default_rate.sample_rate().0, 48_000);
+
+        // Only the rate given: the default's format is kept while it fits.
+        let i16_44k = pick_config(&ranges, &default, Some(44_100), None).unwrap();
+        assert_eq!(i16_44k.sample_format(), F::I16);
+        let at_88k = pick_config(&ranges, &default, Some(88_200), None).unwrap();
+        assert_ne!(at_88k.sample_format(), F::I16);
+
+        assert!(pick_config(&ranges, &default, Some(192_000), None).is_none());
+        assert!(pick_config(&ranges, &default, None, Some(F::U8)).is_none());
+    }
+
+    #[test]
+    fn sample_format_names_match_cpal() {
+        for name in [
+            "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64",
+        ] {
+            assert_eq!(parse_sample_format(name).unwrap().to_string(), name);
+        }
+        assert!(parse_sample_format("i24").is_none());
     }
 
     #[test]
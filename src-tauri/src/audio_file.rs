@@ -0,0 +1,247 @@
+// Audio file decoding for everything that transcribes files instead of the
+// microphone: the CLI, file import and the benchmark clips.
+//
+// Symphonia probes the container from the bytes (the extension is only a
+// hint), decodes the first audio track to f32, and the result is downmixed
+// and resampled to TRANSCRIBE_SAMPLE_RATE — the same buffer shape the
+// recorder hands to the sidecar.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::audio::{self, TRANSCRIBE_SAMPLE_RATE};
+
+/// File extensions the decoder is built for; used for file pickers and
+/// to reject obviously wrong drops early.
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["wav", "flac", "mp3"];
+
+/// Decodes `path` to mono f32 at TRANSCRIBE_SAMPLE_RATE.
+pub fn decode_file(path: &Path) -> Result<Vec<f32>> {
+    let file = std::fs::File::open(path).with_context(|| format!("open {:?}", path))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .with_context(|| format!("unsupported audio format: {:?}", path))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .with_context(|| format!("no audio track in {:?}", path))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .context("unsupported codec")?;
+
+    let mut interleaved = Vec::new();
+    let mut rate = track.codec_params.sample_rate.unwrap_or(0);
+    let mut channels = track.codec_params.channels.map_or(1, |c| c.count());
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).with_context(|| format!("read {:?}", path)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt frame (common at the end of truncated MP3s) costs
+            // a few ms of audio, not the whole file.
+            Err(DecodeError::DecodeError(e)) => {
+                tracing::warn!("skipping undecodable packet in {:?}: {}", path, e);
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("decode {:?}", path)),
+        };
+        let spec = *decoded.spec();
+        rate = spec.rate;
+        channels = spec.channels.count();
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        interleaved.extend_from_slice(buf.samples());
+    }
+    if rate == 0 {
+        anyhow::bail!("{:?} has no sample rate", path);
+    }
+
+    let mono = downmix(&interleaved, channels);
+    if rate == TRANSCRIBE_SAMPLE_RATE {
+        Ok(mono)
+    } else {
+        Ok(audio::resample(&mono, rate, TRANSCRIBE_SAMPLE_RATE))
+    }
+}
+
+/// Length of one transcription window for long files. Whisper decodes in
+/// 30 s windows anyway; feeding it more only makes a single sidecar run
+/// (and its timeout) longer without improving accuracy.
+pub const CHUNK_SECS: u32 = 30;
+
+/// Splits `len` samples at TRANSCRIBE_SAMPLE_RATE into consecutive
+/// CHUNK_SECS windows. The last one holds the remainder.
+pub fn chunk_ranges(len: usize) -> Vec<std::ops::Range<usize>> {
+    let size = (CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+    (0..len)
+        .step_by(size)
+        .map(|start| start..(start + size).min(len))
+        .collect()
+}
+
+/// How far back from each CHUNK_SECS boundary `pause_chunk_ranges`
+/// looks for a pause to cut at.
+const PAUSE_SEARCH_SECS: u32 = 10;
+/// Cut candidates are 20 ms apart, each scored by the 100 ms around it.
+const PAUSE_WINDOW: usize = 1600;
+const PAUSE_STEP: usize = 320;
+
+/// Like `chunk_ranges`, but each cut moves back to the quietest 100 ms
+/// in the last PAUSE_SEARCH_SECS before the boundary, so a word isn't
+/// split between two sidecar runs. Chunks stay at most CHUNK_SECS long;
+/// among equally quiet spots the latest wins, so silence is cut where
+/// `chunk_ranges` would cut it.
+pub fn pause_chunk_ranges(samples: &[f32]) -> Vec<std::ops::Range<usize>> {
+    let size = (CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+    let search = (PAUSE_SEARCH_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+    // Mean square of the window centred on `cut`.
+    let energy = |cut: usize| {
+        let window = &samples[cut - PAUSE_WINDOW / 2..(cut + PAUSE_WINDOW / 2).min(samples.len())];
+        window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32
+    };
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > size {
+        let boundary = start + size;
+        let mut cut = boundary;
+        let mut quietest = f32::INFINITY;
+        for candidate in (boundary - search..=boundary).step_by(PAUSE_STEP) {
+            let e = energy(candidate);
+            if e <= quietest {
+                quietest = e;
+                cut = candidate;
+            }
+        }
+        ranges.push(start..cut);
+        start = cut;
+    }
+    if start < samples.len() {
+        ranges.push(start..samples.len());
+    }
+    ranges
+}
+
+/// Averages interleaved frames down to one channel.
+pub fn downmix(interleaved: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downmix_averages_frames() {
+        assert_eq!(downmix(&[1.0, 0.0, 0.5, 0.5], 2), vec![0.5, 0.5]);
+        assert_eq!(downmix(&[0.25, -0.25], 1), vec![0.25, -0.25]);
+    }
+
+    #[test]
+    fn chunks_cover_everything_once() {
+        let size = (CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+
+        assert!(chunk_ranges(0).is_empty());
+        assert_eq!(chunk_ranges(10), vec![0..10]);
+        assert_eq!(
+            chunk_ranges(2 * size + 5),
+            vec![0..size, size..2 * size, 2 * size..2 * size + 5]
+        );
+    }
+
+    #[test]
+    fn long_audio_is_cut_at_pauses() {
+        let rate = TRANSCRIBE_SAMPLE_RATE as usize;
+        let size = CHUNK_SECS as usize * rate;
+        // Constant "speech" with a 200 ms pause at 25 s and another at 50 s.
+        let mut samples = vec![0.3f32; 70 * rate];
+        for pause in [25 * rate, 50 * rate] {
+            samples[pause..pause + rate / 5].fill(0.0);
+        }
+
+        let ranges = pause_chunk_ranges(&samples);
+
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges.last().unwrap().end, samples.len());
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        for (range, pause) in ranges.iter().zip([25 * rate, 50 * rate]) {
+            assert!(range.len() <= size);
+            assert!(
+                (pause..pause + rate / 5).contains(&range.end),
+                "cut at {} misses the pause at {}",
+                range.end,
+                pause
+            );
+        }
+    }
+
+    #[test]
+    fn silence_and_short_audio_chunk_like_fixed_windows() {
+        let size = (CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+
+        assert!(pause_chunk_ranges(&[]).is_empty());
+        assert_eq!(pause_chunk_ranges(&[0.5; 10]), vec![0..10]);
+        assert_eq!(pause_chunk_ranges(&vec![0.5; size]), vec![0..size]);
+        let silence = vec![0.0; 2 * size + 5];
+        assert_eq!(pause_chunk_ranges(&silence), chunk_ranges(silence.len()));
+    }
+
+    #[test]
+    fn decodes_stereo_wav_to_16k_mono() {
+        let path = std::env::temp_dir().join(format!("flov-decode-{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..8_000 {
+            writer.write_sample(i16::MAX / 2).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let samples = decode_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(samples.len(), 16_000);
+        assert!((samples[100] - 0.25).abs() < 0.01, "{}", samples[100]);
+    }
+}
@@ -0,0 +1,342 @@
+// Backend × model benchmark.
+//
+// Runs every usable backend against every downloaded model on a set of
+// reference clips and reports model load time, real-time factor and word
+// error rate, so picking "CUDA + turbo" vs "Vulkan + small" is a matter of
+// reading a table instead of guessing.
+//
+// A clip is `<name>.wav` (any rate / channel count, decoded by audio_file)
+// next to `<name>.txt` holding its reference transcript, read from the
+// `bench/` resource dir and `<data_dir>/bench/`. No recordings are bundled
+// yet; without any the run times `timing_clip` instead and leaves WER
+// blank. Results of the last run live in `bench.json`.
+//
+// Each run goes through `Transcriber::transcribe_on`, i.e. the same sidecar
+// spawn + stdin path as a real dictation, so the numbers include process
+// start-up and model load exactly as the user pays for them.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::audio::TRANSCRIBE_SAMPLE_RATE;
+use crate::paths::bench_results_path;
+use crate::transcribe::Transcriber;
+
+pub struct BenchClip {
+    pub name: String,
+    /// Mono f32 at TRANSCRIBE_SAMPLE_RATE, ready for the sidecar.
+    pub samples: Vec<f32>,
+    /// What was said; `None` for `timing_clip`, which gets no WER.
+    pub reference: Option<String>,
+}
+
+impl BenchClip {
+    pub fn audio_secs(&self) -> f64 {
+        self.samples.len() as f64 / TRANSCRIBE_SAMPLE_RATE as f64
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub backend: String,
+    pub model_id: String,
+    pub clip: String,
+    pub audio_secs: f64,
+    /// Model load as reported by the sidecar; `None` for old sidecars.
+    pub load_ms: Option<u64>,
+    /// Wall clock from spawn to exit, as seen by the app.
+    pub total_ms: u64,
+    /// Decode time / audio duration (falls back to wall clock when the
+    /// sidecar doesn't report timings). Below 1.0 is faster than realtime.
+    pub rtf: Option<f64>,
+    /// Word error rate against the clip's reference, 0.0 = perfect;
+    /// `None` without a reference.
+    pub wer: Option<f64>,
+    pub text: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BenchReport {
+    /// Unix seconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub results: Vec<BenchResult>,
+}
+
+/// Reads every `<name>.wav` + `<name>.txt` pair from `dirs`. Missing dirs
+/// are fine (the user dir usually doesn't exist); a clip that fails to
+/// decode is logged and skipped so one bad file doesn't sink the run.
+pub fn load_clips(dirs: &[PathBuf]) -> Vec<BenchClip> {
+    let mut clips = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        let mut wavs: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+            })
+            .collect();
+        wavs.sort();
+        for wav in wavs {
+            match load_clip(&wav) {
+                Ok(clip) => clips.push(clip),
+                Err(e) => tracing::warn!("skipping bench clip {:?}: {:#}", wav, e),
+            }
+        }
+    }
+    clips
+}
+
+fn load_clip(wav: &Path) -> Result<BenchClip> {
+    let txt = wav.with_extension("txt");
+    let reference = std::fs::read_to_string(&txt)
+        .with_context(|| format!("read reference {:?}", txt))?
+        .trim()
+        .to_string();
+    let name = wav
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(BenchClip {
+        name,
+        samples: crate::audio_file::decode_file(wav)?,
+        reference: Some(reference),
+    })
+}
+
+/// Stand-in when no clips are installed: 10 s of quiet noise, enough to
+/// time model load and decoding, with nothing to score a transcript
+/// against. Its RTF runs low, since there are few words to decode.
+pub fn timing_clip() -> BenchClip {
+    // Fixed LCG so every run decodes the same audio.
+    let mut seed: u32 = 0x2545_f491;
+    let samples = (0..TRANSCRIBE_SAMPLE_RATE as usize * 10)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32 * 0.02 - 0.01
+        })
+        .collect();
+    BenchClip {
+        name: "timing".into(),
+        samples,
+        reference: None,
+    }
+}
+
+/// Runs every (backend, model, clip) combination in that nesting order and
+/// calls `on_result` after each one so the UI can fill the table as it goes.
+/// `models` pairs a catalog id with its file on disk.
+pub fn run(
+    transcriber: &Transcriber,
+    backends: &[String],
+    models: &[(String, PathBuf)],
+    clips: &[BenchClip],
+    mut on_result: impl FnMut(&BenchResult, usize, usize),
+) -> BenchReport {
+    let total = backends.len() * models.len() * clips.len();
+    let mut report = BenchReport {
+        started_at: unix_now(),
+        ..Default::default()
+    };
+    for backend in backends {
+        for (model_id, model_path) in models {
+            for clip in clips {
+                let result = run_one(transcriber, backend, model_id, model_path, clip);
+                tracing::info!(
+                    "bench {} / {} / {}: load={:?}ms total={}ms rtf={:?} wer={:?} err={:?}",
+                    backend,
+                    model_id,
+                    clip.name,
+                    result.load_ms,
+                    result.total_ms,
+                    result.rtf,
+                    result.wer,
+                    result.error
+                );
+                report.results.push(result);
+                on_result(report.results.last().unwrap(), report.results.len(), total);
+            }
+        }
+    }
+    report.finished_at = unix_now();
+    report
+}
+
+fn run_one(
+    transcriber: &Transcriber,
+    backend: &str,
+    model_id: &str,
+    model_path: &Path,
+    clip: &BenchClip,
+) -> BenchResult {
+    let audio_secs = clip.audio_secs();
+    let mut result = BenchResult {
+        backend: backend.to_string(),
+        model_id: model_id.to_string(),
+        clip: clip.name.clone(),
+        audio_secs,
+        load_ms: None,
+        total_ms: 0,
+        rtf: None,
+        wer: None,
+        text: String::new(),
+        error: None,
+    };
+    match transcriber.transcribe_on(backend, model_path, &clip.samples) {
+        Ok(out) => {
+            result.total_ms = out.elapsed.as_millis() as u64;
+            result.load_ms = out.load_ms;
+            let decode_ms = out.decode_ms.unwrap_or(result.total_ms);
+            if audio_secs > 0.0 {
+                result.rtf = Some(decode_ms as f64 / 1000.0 / audio_secs);
+            }
+            result.wer = clip
+                .reference
+                .as_deref()
+                .map(|reference| word_error_rate(reference, &out.text));
+            result.text = out.text;
+        }
+        Err(e) => result.error = Some(format!("{:#}", e)),
+    }
+    result
+}
+
+/// Word-level Levenshtein distance over the reference length. Case and
+/// punctuation are ignored — Whisper's punctuation isn't what the user is
+/// comparing backends on. Can exceed 1.0 when the hypothesis hallucinates
+/// extra words.
+pub fn word_error_rate(reference: &str, hypothesis: &str) -> f64 {
+    let r = normalize_words(reference);
+    let h = normalize_words(hypothesis);
+    if r.is_empty() {
+        return if h.is_empty() { 0.0 } else { 1.0 };
+    }
+
+    // Single-row DP: prev[j] = distance between r[..i] and h[..j].
+    let mut prev: Vec<usize> = (0..=h.len()).collect();
+    let mut cur = vec![0; h.len() + 1];
+    for (i, rw) in r.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, hw) in h.iter().enumerate() {
+            let substitution = prev[j] + usize::from(rw != hw);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[h.len()] as f64 / r.len() as f64
+}
+
+fn normalize_words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|w| {
+            w.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Last saved report, if any. A corrupt file reads as "no results" —
+/// it's a cache, re-running the benchmark rebuilds it.
+pub fn load_report() -> Option<BenchReport> {
+    let path = bench_results_path().ok()?;
+    let s = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&s).ok()
+}
+
+pub fn save_report(report: &BenchReport) -> Result<()> {
+    let path = bench_results_path()?;
+    let json = serde_json::to_string_pretty(report).context("serialize bench report")?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json).with_context(|| format!("write {:?}", tmp))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("rename {:?} -> {:?}", tmp, path))?;
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wer_is_zero_for_identical_text_ignoring_case_and_punctuation() {
+        assert_eq!(word_error_rate("Hello, world!", "hello world"), 0.0);
+        assert_eq!(word_error_rate("Привет, мир.", "привет мир"), 0.0);
+    }
+
+    #[test]
+    fn wer_counts_substitutions_insertions_and_deletions() {
+        // one substitution out of four words
+        assert_eq!(
+            word_error_rate("the quick brown fox", "the quick red fox"),
+            0.25
+        );
+        // one deletion
+        assert_eq!(
+            word_error_rate("the quick brown fox", "the brown fox"),
+            0.25
+        );
+        // two insertions
+        assert_eq!(word_error_rate("a b", "a x b y"), 1.0);
+    }
+
+    #[test]
+    fn clips_pair_wavs_with_their_references() {
+        let dir = std::env::temp_dir().join(format!("flov-bench-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: TRANSCRIBE_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        for name in ["hello", "orphan"] {
+            let mut writer =
+                hound::WavWriter::create(dir.join(format!("{name}.wav")), spec).unwrap();
+            for _ in 0..TRANSCRIBE_SAMPLE_RATE * 2 {
+                writer.write_sample(0i16).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+        std::fs::write(dir.join("hello.txt"), "Hello there\n").unwrap();
+
+        let clips = load_clips(&[dir.clone(), dir.join("missing")]);
+
+        assert_eq!(clips.len(), 1, "the wav without a .txt is skipped");
+        assert_eq!(clips[0].name, "hello");
+        assert_eq!(clips[0].reference.as_deref(), Some("Hello there"));
+        assert_eq!(clips[0].audio_secs(), 2.0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn timing_clip_has_no_reference() {
+        let clip = timing_clip();
+
+        assert_eq!(clip.reference, None);
+        assert_eq!(clip.audio_secs(), 10.0);
+        assert!(clip.samples.iter().all(|s| s.abs() <= 0.01));
+    }
+
+    #[test]
+    fn wer_handles_empty_sides() {
+        assert_eq!(word_error_rate("", ""), 0.0);
+        assert_eq!(word_error_rate("", "noise"), 1.0);
+        assert_eq!(word_error_rate("some words", ""), 1.0);
+    }
+}
@@ -0,0 +1,134 @@
+// Tauri commands for the Benchmark card in Settings.
+//
+// A run spawns one sidecar per (backend, model, clip) and can take minutes
+// on CPU, so it lives on a worker thread; the frontend listens for
+// `bench-progress` after every row and `bench-finished` at the end.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::bench::{self, BenchReport, BenchResult};
+use crate::models;
+use crate::transcribe::{self, BackendProbe, Transcriber};
+
+pub struct BenchState {
+    pub transcriber: Arc<Transcriber>,
+    pub backend_probes: Arc<Mutex<Option<Vec<BackendProbe>>>>,
+    pub running: Arc<AtomicBool>,
+}
+
+#[derive(Clone, Serialize)]
+struct ProgressEvent {
+    done: usize,
+    total: usize,
+    result: BenchResult,
+}
+
+#[derive(Clone, Serialize)]
+struct FinishedEvent {
+    report: Option<BenchReport>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BenchView {
+    pub running: bool,
+    pub report: Option<BenchReport>,
+}
+
+#[tauri::command]
+pub fn get_benchmark(state: State<BenchState>) -> BenchView {
+    BenchView {
+        running: state.running.load(Ordering::SeqCst),
+        report: bench::load_report(),
+    }
+}
+
+#[tauri::command]
+pub fn run_benchmark(app: AppHandle, state: State<BenchState>) -> Result<(), String> {
+    if state.running.swap(true, Ordering::SeqCst) {
+        return Err("benchmark already running".into());
+    }
+
+    let clip_dirs = clip_dirs(&app);
+    let mut clips = bench::load_clips(&clip_dirs);
+    if clips.is_empty() {
+        tracing::info!("no benchmark clips in {:?}; timing only, no WER", clip_dirs);
+        clips.push(bench::timing_clip());
+    }
+
+    // Only backends that passed the startup probe — benchmarking a backend
+    // with no usable device would just record a column of errors.
+    let backends: Vec<String> = match state.backend_probes.lock().unwrap().as_deref() {
+        Some(probes) => transcribe::rank_backends(probes),
+        None => transcribe::available_backends(),
+    };
+    let downloaded: Vec<(String, PathBuf)> = models::list(None)
+        .into_iter()
+        .filter(|m| m.downloaded)
+        .map(|m| (m.id, m.local_path))
+        .collect();
+    if backends.is_empty() || downloaded.is_empty() {
+        state.running.store(false, Ordering::SeqCst);
+        return Err("benchmark needs at least one backend and one downloaded model".into());
+    }
+
+    let transcriber = state.transcriber.clone();
+    let running = state.running.clone();
+    std::thread::Builder::new()
+        .name("flov-bench".into())
+        .spawn(move || {
+            let report = bench::run(
+                &transcriber,
+                &backends,
+                &downloaded,
+                &clips,
+                |result, done, total| {
+                    let _ = app.emit(
+                        "bench-progress",
+                        ProgressEvent {
+                            done,
+                            total,
+                            result: result.clone(),
+                        },
+                    );
+                },
+            );
+            let error = bench::save_report(&report)
+                .err()
+                .map(|e| format!("save results: {:#}", e));
+            if let Some(e) = &error {
+                tracing::error!("{}", e);
+            }
+            running.store(false, Ordering::SeqCst);
+            let _ = app.emit(
+                "bench-finished",
+                FinishedEvent {
+                    report: Some(report),
+                    error,
+                },
+            );
+        })
+        .map_err(|e| {
+            state.running.store(false, Ordering::SeqCst);
+            e.to_string()
+        })?;
+    Ok(())
+}
+
+/// Bundled clips first (stable baseline across machines), then the user's.
+fn clip_dirs(app: &AppHandle) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    match app.path().resource_dir() {
+        Ok(dir) => dirs.push(dir.join("bench")),
+        Err(e) => tracing::warn!("resource_dir unavailable: {}", e),
+    }
+    if let Ok(dir) = crate::paths::bench_dir() {
+        dirs.push(dir);
+    }
+    dirs
+}
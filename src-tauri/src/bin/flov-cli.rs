@@ -0,0 +1,370 @@
+// flov-cli — headless front-end to the same config, models and whisper
+// sidecars the tray app uses. For scripts, servers and anyone who'd rather
+// not have a window.
+//
+//   flov-cli transcribe <file>... [options]   WAV / FLAC / MP3 → text
+//   flov-cli record [options]                 microphone until Enter
+//   flov-cli models list
+//   flov-cli models download <id>
+//   flov-cli config show | path
+//   flov-cli config get <section.key>
+//   flov-cli config set <section.key> <value>
+//
+// Transcription options:
+//   --format text|json|srt|vtt   output format (default: text)
+//   --language <code>            overrides [whisper].language
+//   --backend <name>             auto / cuda / vulkan / metal / cpu
+//   --model <id|path>            catalog id or model file path
+//   --sidecar-dir <dir>          where flov-whisper-* live (default: next
+//                                to this binary)
+//
+// Results go to stdout, progress and errors to stderr, so
+// `flov-cli transcribe talk.mp3 --format srt > talk.srt` does what it says.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context, Result};
+use flov_lib::audio::{self, AudioRecorder, TRANSCRIBE_SAMPLE_RATE};
+use flov_lib::config::{Config, SETTABLE_KEYS};
+use flov_lib::denoise::DenoiseStage;
+use flov_lib::import::{self, FileTranscript};
+use flov_lib::levels::Levels;
+use flov_lib::subtitles::SubtitleFormat;
+use flov_lib::transcribe::Transcriber;
+use flov_lib::{audio_file, models};
+
+const USAGE: &str = "usage:
+  flov-cli transcribe <file>... [--format text|json|srt|vtt] [--language <code>]
+                                [--backend <name>] [--model <id|path>] [--sidecar-dir <dir>]
+  flov-cli record [same options as transcribe]
+  flov-cli models list
+  flov-cli models download <id>
+  flov-cli config show | path
+  flov-cli config get <section.key>
+  flov-cli config set <section.key> <value>";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    Subtitles(SubtitleFormat),
+}
+
+#[derive(Default)]
+struct Options {
+    format: Option<Format>,
+    language: Option<String>,
+    backend: Option<String>,
+    model: Option<String>,
+    sidecar_dir: Option<PathBuf>,
+    /// Non-flag arguments (input files for `transcribe`).
+    positional: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    init_logging();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("flov-cli: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Warnings and errors to stderr; RUST_LOG raises the level. The app's
+/// log file is left alone so a CLI run doesn't interleave with the tray.
+fn init_logging() {
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .try_init();
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let Some((command, rest)) = args.split_first() else {
+        bail!("{}", USAGE);
+    };
+    match (command.as_str(), rest) {
+        ("transcribe", rest) => transcribe_files(parse_options(rest)?),
+        ("record", rest) => record(parse_options(rest)?),
+        ("models", [sub]) if sub == "list" => list_models(),
+        ("models", [sub, id]) if sub == "download" => download_model(id),
+        ("config", [sub]) if sub == "show" => show_config(),
+        ("config", [sub]) if sub == "path" => {
+            println!("{}", Config::path()?.display());
+            Ok(())
+        }
+        ("config", [sub, key]) if sub == "get" => {
+            let value = Config::load()?
+                .get(key)
+                .with_context(|| format!("unknown config key '{}'", key))?;
+            println!("{}", value);
+            Ok(())
+        }
+        ("config", [sub, key, value]) if sub == "set" => Config::set(key, value),
+        ("help" | "--help" | "-h", _) => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => bail!("{}", USAGE),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options> {
+    let mut opts = Options::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .with_context(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--format" => {
+                let name = value()?;
+                opts.format = Some(match name.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    other => match SubtitleFormat::from_name(other) {
+                        Some(f) => Format::Subtitles(f),
+                        None => bail!("unknown format '{}' (text, json, srt or vtt)", other),
+                    },
+                })
+            }
+            "--language" => opts.language = Some(value()?),
+            "--backend" => opts.backend = Some(value()?),
+            "--model" => opts.model = Some(value()?),
+            "--sidecar-dir" => opts.sidecar_dir = Some(PathBuf::from(value()?)),
+            flag if flag.starts_with("--") => bail!("unknown option {}\n{}", flag, USAGE),
+            _ => opts.positional.push(arg.clone()),
+        }
+    }
+    Ok(opts)
+}
+
+/// Builds a Transcriber from flov.toml with the command-line overrides.
+/// No backend probe: "auto" falls back to the fixed priority order, which
+/// is what the app does until its own probe finishes.
+fn transcriber(opts: &Options, cfg: &Config) -> Result<Transcriber> {
+    let model_path = match &opts.model {
+        Some(m) if models::find(m).is_ok() => models::entry_local_path(m)?,
+        Some(m) => PathBuf::from(m),
+        None => cfg.whisper.model_path.clone(),
+    };
+    if !model_path.exists() {
+        bail!(
+            "model file not found: {:?} — run `flov-cli models download <id>`",
+            model_path
+        );
+    }
+    let language = opts
+        .language
+        .clone()
+        .unwrap_or_else(|| cfg.whisper.language.clone());
+    let backend = opts
+        .backend
+        .clone()
+        .unwrap_or_else(|| cfg.backend.choice.clone());
+
+    let transcriber = Transcriber::new(
+        Arc::new(Mutex::new(model_path)),
+        language,
+        Arc::new(Mutex::new(backend)),
+        Arc::new(Mutex::new(None)),
+    )?;
+    Ok(match &opts.sidecar_dir {
+        Some(dir) => transcriber.with_sidecar_dir(dir.clone()),
+        None => transcriber,
+    })
+}
+
+/// Chunked transcription with per-chunk progress on stderr.
+fn transcribe_samples(
+    transcriber: &Transcriber,
+    samples: &[f32],
+    label: &str,
+) -> Result<FileTranscript> {
+    import::transcribe_samples(transcriber, samples, |i, n| {
+        if n > 1 {
+            eprintln!("{}: chunk {}/{}", label, i + 1, n);
+        }
+    })
+    .with_context(|| label.to_string())
+}
+
+fn transcribe_files(opts: Options) -> Result<()> {
+    if opts.positional.is_empty() {
+        bail!("transcribe needs at least one file\n{}", USAGE);
+    }
+    let cfg = Config::load()?;
+    let transcriber = transcriber(&opts, &cfg)?;
+    let format = opts.format.unwrap_or(Format::Text);
+
+    for file in &opts.positional {
+        let samples = audio_file::decode_file(std::path::Path::new(file))?;
+        let transcript = transcribe_samples(&transcriber, &samples, file)?;
+        print_result(format, Some(file), &transcript)?;
+    }
+    Ok(())
+}
+
+fn record(opts: Options) -> Result<()> {
+    let cfg = Config::load()?;
+    let transcriber = transcriber(&opts, &cfg)?;
+    let recorder = AudioRecorder::new(&cfg.audio)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_on_enter = stop.clone();
+    std::thread::Builder::new()
+        .name("flov-cli-stdin".into())
+        .spawn(move || {
+            let _ = std::io::stdin().lock().read_line(&mut String::new());
+            stop_on_enter.store(true, Ordering::SeqCst);
+        })?;
+
+    eprintln!("Recording… press Enter to stop.");
+    let limit = (cfg.audio.max_recording_secs > 0)
+        .then(|| std::time::Duration::from_secs(cfg.audio.max_recording_secs as u64));
+    let started = std::time::Instant::now();
+    let mut samples = recorder.record_while_with_spectrum(
+        move || !stop.load(Ordering::SeqCst) && limit.is_none_or(|l| started.elapsed() < l),
+        |_, _| {},
+    )?;
+    if limit.is_some_and(|l| started.elapsed() >= l) {
+        eprintln!(
+            "Stopped at the {} s limit ([audio] max_recording_secs).",
+            cfg.audio.max_recording_secs
+        );
+    }
+    if recorder.output_sample_rate() != TRANSCRIBE_SAMPLE_RATE {
+        samples = audio::resample(
+            &samples,
+            recorder.output_sample_rate(),
+            TRANSCRIBE_SAMPLE_RATE,
+        );
+    }
+    if samples.is_empty() {
+        bail!("nothing was recorded");
+    }
+    let levels = Levels::measure(&samples);
+    eprintln!(
+        "Levels: rms {:.1} dBFS, peak {:.1} dBFS{}",
+        levels.rms_dbfs(),
+        levels.peak_dbfs(),
+        if levels.clipped > 0 {
+            format!(
+                ", {} clipped samples — turn the input gain down",
+                levels.clipped
+            )
+        } else {
+            String::new()
+        }
+    );
+    DenoiseStage::from_config(&cfg.audio).apply(&mut samples);
+    cfg.audio.gain.apply(&mut samples);
+
+    let transcript = transcribe_samples(&transcriber, &samples, "recording")?;
+    print_result(opts.format.unwrap_or(Format::Text), None, &transcript)
+}
+
+fn print_result(format: Format, file: Option<&str>, transcript: &FileTranscript) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    match format {
+        Format::Text => writeln!(out, "{}", transcript.text)?,
+        Format::Subtitles(f) => write!(out, "{}", f.render(&transcript.cues))?,
+        Format::Json => {
+            let value = serde_json::json!({
+                "file": file,
+                "duration": transcript.duration,
+                "backend": transcript.backend,
+                "text": transcript.text,
+                "segments": transcript.cues,
+            });
+            writeln!(out, "{}", serde_json::to_string_pretty(&value)?)?;
+        }
+    }
+    Ok(())
+}
+
+// Listings write through a locked stdout with `?` rather than println!,
+// so `flov-cli models list | head` ends quietly instead of panicking on
+// the closed pipe.
+fn list_models() -> Result<()> {
+    let cfg = Config::load()?;
+    let mut out = std::io::stdout().lock();
+    for m in models::list(Some(&cfg.whisper.model_path)) {
+        let marker = if m.active {
+            "*"
+        } else if m.downloaded {
+            "+"
+        } else {
+            " "
+        };
+        writeln!(
+            out,
+            "{} {:<24} {:>6} MB  {}",
+            marker,
+            m.id,
+            m.size_bytes / 1_000_000,
+            m.label
+        )?;
+    }
+    eprintln!("* active   + downloaded");
+    Ok(())
+}
+
+fn download_model(id: &str) -> Result<()> {
+    let dest = models::entry_local_path(id)?;
+    if dest.exists() {
+        eprintln!("{} is already downloaded: {}", id, dest.display());
+        return Ok(());
+    }
+    models::cleanup_partials(&[]);
+    models::ensure_free_space(id)?;
+
+    let path = models::download(id, |downloaded, total| {
+        let pct = (downloaded * 100).checked_div(total).unwrap_or(0);
+        eprint!(
+            "\r{}: {} / {} MB ({}%)",
+            id,
+            downloaded / 1_000_000,
+            total / 1_000_000,
+            pct
+        );
+    })?;
+    eprintln!();
+
+    // Same rule as the Models window: a first model becomes the active one.
+    let cfg = Config::load()?;
+    if !cfg.whisper.model_path.exists() {
+        Config::write_model_path(&path)?;
+        eprintln!("{} is now the active model", id);
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn show_config() -> Result<()> {
+    let cfg = Config::load()?;
+    let mut out = std::io::stdout().lock();
+    for key in SETTABLE_KEYS {
+        let mut value = cfg.get(key).unwrap_or_default();
+        if *key == "openrouter.api_key" && !value.is_empty() {
+            value = "(set)".to_string();
+        } else if value.contains('\n') {
+            value = format!("{}…", value.lines().next().unwrap_or_default());
+        }
+        writeln!(out, "{} = {}", key, value)?;
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Deserialize)]
@@ -14,6 +15,117 @@ pub struct Config {
     pub backend: BackendConfig,
     #[serde(default)]
     pub hotkey: HotkeyConfig,
+    #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+}
+
+/// Recording archive (archive.rs). Off by default: it keeps the user's
+/// voice on disk.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ArchiveConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Oldest recordings go once the archive is bigger than this; 0 = no limit.
+    #[serde(default = "default_archive_max_mb")]
+    pub max_mb: u64,
+    /// Recordings older than this go; 0 = kept forever.
+    #[serde(default = "default_archive_max_days")]
+    pub max_days: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_mb: default_archive_max_mb(),
+            max_days: default_archive_max_days(),
+        }
+    }
+}
+
+fn default_archive_max_mb() -> u64 {
+    500
+}
+
+fn default_archive_max_days() -> u64 {
+    30
+}
+
+/// Where transcripts go (sinks.rs).
+#[derive(Debug, Deserialize, Clone)]
+pub struct OutputConfig {
+    /// Paste into the focused window. Turn off to only feed the sinks.
+    #[serde(default = "default_true")]
+    pub paste: bool,
+    /// `[[output.sinks]]` tables, each run alongside the paste.
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            paste: true,
+            sinks: Vec::new(),
+        }
+    }
+}
+
+/// One `[[output.sinks]]` entry, picked by `kind`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SinkConfig {
+    /// `<dir>/YYYY-MM-DD.md`; a relative dir is under the data dir.
+    DailyNotes {
+        dir: PathBuf,
+    },
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    /// Program and arguments, text on stdin.
+    Command {
+        command: Vec<String>,
+    },
+    Stdout,
+}
+
+/// Control surfaces (control.rs, dbus.rs).
+#[derive(Debug, Deserialize, Clone)]
+pub struct ControlConfig {
+    /// Local HTTP API. Off by default: it lets any local process holding
+    /// the token start the microphone.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_control_port")]
+    pub port: u16,
+    /// `com.flov.App` on the Linux session bus, which is already
+    /// per-user, so on by default.
+    #[serde(default = "default_true")]
+    pub dbus: bool,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_control_port(),
+            dbus: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_control_port() -> u16 {
+    47120
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -93,6 +205,40 @@ pub struct AudioConfig {
     /// back to default with a warning.
     #[serde(default)]
     pub device: Option<String>,
+    /// Keep the microphone open between recordings and prepend this much
+    /// audio from before the hotkey press. 0 (default) = off.
+    #[serde(default)]
+    pub preroll_ms: u32,
+    /// Device rate → 16 kHz filter: "fast", "balanced" (default), "best".
+    #[serde(default)]
+    pub resample_quality: crate::resample::Quality,
+    /// Level normalization before transcription: "off" (default),
+    /// "peak" or "agc".
+    #[serde(default)]
+    pub gain: crate::levels::Gain,
+    /// Noise suppression before transcription: "off" (default), "light"
+    /// or "strong".
+    #[serde(default)]
+    pub denoise: crate::denoise::Denoise,
+    /// Also write each recording before and after denoising to
+    /// `<data dir>/denoise/`.
+    #[serde(default)]
+    pub denoise_dump: bool,
+    /// Recording stops by itself after this many seconds, as if the
+    /// hotkey were released. 0 = no limit.
+    #[serde(default = "default_max_recording_secs")]
+    pub max_recording_secs: u32,
+    /// Open the device at this rate instead of its default config's.
+    #[serde(default)]
+    pub stream_sample_rate: Option<u32>,
+    /// Open the device with this cpal sample format ("i16", "u16",
+    /// "i32", "f32", …) instead of its default config's.
+    #[serde(default)]
+    pub stream_format: Option<String>,
+    /// `[audio.channels]`: device name → 1-based channel to record.
+    /// Unlisted devices (or 0) average all channels.
+    #[serde(default)]
+    pub channels: BTreeMap<String, u16>,
 }
 
 // Manual Default — the previous `derive(Default)` returned
@@ -106,6 +252,15 @@ impl Default for AudioConfig {
         Self {
             sample_rate: default_sample_rate(),
             device: None,
+            preroll_ms: 0,
+            resample_quality: Default::default(),
+            gain: Default::default(),
+            denoise: Default::default(),
+            denoise_dump: false,
+            max_recording_secs: default_max_recording_secs(),
+            stream_sample_rate: None,
+            stream_format: None,
+            channels: BTreeMap::new(),
         }
     }
 }
@@ -114,10 +269,30 @@ impl Default for AudioConfig {
 pub struct OpenRouterConfig {
     #[serde(default)]
     pub api_key: String,
-    #[serde(default = "default_openrouter_model")]
-    pub model: String,
+    /// Models to try in order: `model = "a"` or `model = ["a", "b"]`.
+    /// The next one is used when a model is unknown, overloaded or down.
+    #[serde(
+        default = "default_openrouter_model",
+        deserialize_with = "deser_models"
+    )]
+    pub model: Vec<String>,
     #[serde(default = "default_system_prompt")]
     pub system_prompt: String,
+    /// Paste the answer a sentence at a time as it streams in, instead of
+    /// all at once when it's complete.
+    #[serde(default)]
+    pub progressive: bool,
+    /// Waiting for OpenRouter to start answering, and for each next chunk
+    /// of the streamed answer. The whole answer may take longer.
+    #[serde(default = "default_openrouter_timeout_secs")]
+    pub timeout_secs: u64,
+    /// All attempts of one post-processing call, retry waits included, up
+    /// to the one that starts answering.
+    #[serde(default = "default_openrouter_total_timeout_secs")]
+    pub total_timeout_secs: u64,
+    /// Attempts after the first on network errors, timeouts, 429 and 5xx.
+    #[serde(default = "default_openrouter_retries")]
+    pub retries: u32,
 }
 
 impl Default for OpenRouterConfig {
@@ -126,12 +301,62 @@ impl Default for OpenRouterConfig {
             api_key: String::new(),
             model: default_openrouter_model(),
             system_prompt: default_system_prompt(),
+            progressive: false,
+            timeout_secs: default_openrouter_timeout_secs(),
+            total_timeout_secs: default_openrouter_total_timeout_secs(),
+            retries: default_openrouter_retries(),
         }
     }
 }
 
-fn default_openrouter_model() -> String {
-    "openai/gpt-4o-mini".to_string()
+fn default_openrouter_timeout_secs() -> u64 {
+    60
+}
+
+fn default_openrouter_total_timeout_secs() -> u64 {
+    120
+}
+
+fn default_openrouter_retries() -> u32 {
+    2
+}
+
+fn default_openrouter_model() -> Vec<String> {
+    vec!["openai/gpt-4o-mini".to_string()]
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Accepts a single model, a comma-separated string or an array. An
+/// empty list falls back to the default model.
+fn deser_models<'de, D>(d: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let models = match OneOrMany::deserialize(d)? {
+        OneOrMany::One(s) => parse_models(&s),
+        OneOrMany::Many(list) => parse_models(&list.join(",")),
+    };
+    Ok(if models.is_empty() {
+        default_openrouter_model()
+    } else {
+        models
+    })
+}
+
+/// Splits a comma-separated model list, as typed in Settings or given to
+/// `flov config set openrouter.model`.
+pub fn parse_models(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 fn default_system_prompt() -> String {
@@ -151,6 +376,10 @@ fn default_sample_rate() -> u32 {
     16000
 }
 
+fn default_max_recording_secs() -> u32 {
+    600
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         let config_path = crate::paths::config_path().context("Failed to compute config path")?;
@@ -163,6 +392,9 @@ impl Config {
                 openrouter: OpenRouterConfig::default(),
                 backend: BackendConfig::default(),
                 hotkey: HotkeyConfig::default(),
+                control: ControlConfig::default(),
+                output: OutputConfig::default(),
+                archive: ArchiveConfig::default(),
             });
         }
 
@@ -176,6 +408,13 @@ impl Config {
         if config.whisper.model_path.is_relative() {
             config.whisper.model_path = data_dir.join(&config.whisper.model_path);
         }
+        for sink in &mut config.output.sinks {
+            if let SinkConfig::DailyNotes { dir } = sink {
+                if dir.is_relative() {
+                    *dir = data_dir.join(&*dir);
+                }
+            }
+        }
 
         Ok(config)
     }
@@ -207,6 +446,18 @@ impl Config {
         write_field(&["openrouter", field], value)
     }
 
+    /// Updates `[openrouter].model` in flov.toml: a plain string for one
+    /// model, an array for a fallback list.
+    pub fn write_openrouter_models(models: &[String]) -> Result<()> {
+        match models {
+            [one] => write_field(&["openrouter", "model"], one.as_str()),
+            many => write_field(
+                &["openrouter", "model"],
+                many.iter().collect::<toml_edit::Array>(),
+            ),
+        }
+    }
+
     /// Updates `[hotkey].combo` in flov.toml.
     pub fn write_hotkey_combo(combo: &str) -> Result<()> {
         write_field(&["hotkey", "combo"], combo)
@@ -217,11 +468,179 @@ impl Config {
     pub fn write_audio_device(device: &str) -> Result<()> {
         write_field(&["audio", "device"], device)
     }
+
+    /// Updates `[audio].preroll_ms` in flov.toml.
+    pub fn write_preroll_ms(ms: u32) -> Result<()> {
+        write_field(&["audio", "preroll_ms"], ms as i64)
+    }
+
+    /// Updates `[audio.channels].<device>` in flov.toml. 0 means "mix
+    /// all channels".
+    pub fn write_audio_channel(device: &str, channel: u16) -> Result<()> {
+        write_field(&["audio", "channels", device], channel as i64)
+    }
+
+    /// Updates `[audio].denoise` in flov.toml.
+    pub fn write_denoise(mode: &str) -> Result<()> {
+        write_field(&["audio", "denoise"], mode)
+    }
+
+    /// Updates `[archive].enabled` in flov.toml.
+    pub fn write_archive_enabled(enabled: bool) -> Result<()> {
+        write_field(&["archive", "enabled"], enabled)
+    }
+
+    /// Current value of a `section.key` from SETTABLE_KEYS, as the CLI
+    /// prints it. `None` for unknown keys.
+    pub fn get(&self, key: &str) -> Option<String> {
+        Some(match key {
+            "whisper.model_path" => self.whisper.model_path.to_string_lossy().into_owned(),
+            "whisper.language" => self.whisper.language.clone(),
+            "audio.sample_rate" => self.audio.sample_rate.to_string(),
+            "audio.device" => self.audio.device.clone().unwrap_or_default(),
+            "audio.preroll_ms" => self.audio.preroll_ms.to_string(),
+            "audio.resample_quality" => self.audio.resample_quality.name().to_string(),
+            "audio.gain" => self.audio.gain.name().to_string(),
+            "audio.denoise" => self.audio.denoise.name().to_string(),
+            "audio.denoise_dump" => self.audio.denoise_dump.to_string(),
+            "audio.max_recording_secs" => self.audio.max_recording_secs.to_string(),
+            "openrouter.api_key" => self.openrouter.api_key.clone(),
+            "openrouter.model" => self.openrouter.model.join(", "),
+            "openrouter.system_prompt" => self.openrouter.system_prompt.clone(),
+            "openrouter.progressive" => self.openrouter.progressive.to_string(),
+            "openrouter.timeout_secs" => self.openrouter.timeout_secs.to_string(),
+            "openrouter.total_timeout_secs" => self.openrouter.total_timeout_secs.to_string(),
+            "openrouter.retries" => self.openrouter.retries.to_string(),
+            "backend.choice" => self.backend.choice.clone(),
+            "hotkey.combo" => self.hotkey.combo.clone(),
+            "control.enabled" => self.control.enabled.to_string(),
+            "control.port" => self.control.port.to_string(),
+            "control.dbus" => self.control.dbus.to_string(),
+            "output.paste" => self.output.paste.to_string(),
+            "archive.enabled" => self.archive.enabled.to_string(),
+            "archive.max_mb" => self.archive.max_mb.to_string(),
+            "archive.max_days" => self.archive.max_days.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Writes a `section.key` from SETTABLE_KEYS, parsing numeric and
+    /// boolean keys so the file still deserializes. Unknown keys are
+    /// rejected rather than written, since serde would silently ignore a
+    /// typo.
+    pub fn set(key: &str, value: &str) -> Result<()> {
+        if !SETTABLE_KEYS.contains(&key) {
+            anyhow::bail!(
+                "unknown config key '{}' (expected one of: {})",
+                key,
+                SETTABLE_KEYS.join(", ")
+            );
+        }
+        let path_keys: Vec<&str> = key.split('.').collect();
+        if matches!(
+            key,
+            "audio.sample_rate"
+                | "audio.preroll_ms"
+                | "audio.max_recording_secs"
+                | "openrouter.timeout_secs"
+                | "openrouter.total_timeout_secs"
+                | "openrouter.retries"
+                | "archive.max_mb"
+                | "archive.max_days"
+        ) {
+            let n: u32 = value
+                .parse()
+                .with_context(|| format!("{} must be a whole number, got '{}'", key, value))?;
+            return write_field(&path_keys, n as i64);
+        }
+        if key == "audio.resample_quality" && crate::resample::Quality::parse(value).is_none() {
+            anyhow::bail!(
+                "{} must be one of {}, got '{}'",
+                key,
+                crate::resample::Quality::NAMES.join(", "),
+                value
+            );
+        }
+        if key == "audio.gain" && crate::levels::Gain::parse(value).is_none() {
+            anyhow::bail!(
+                "{} must be one of {}, got '{}'",
+                key,
+                crate::levels::Gain::NAMES.join(", "),
+                value
+            );
+        }
+        if key == "audio.denoise" && crate::denoise::Denoise::parse(value).is_none() {
+            anyhow::bail!(
+                "{} must be one of {}, got '{}'",
+                key,
+                crate::denoise::Denoise::NAMES.join(", "),
+                value
+            );
+        }
+        if key == "openrouter.model" {
+            let models = parse_models(value);
+            if models.is_empty() {
+                anyhow::bail!("{} needs at least one model", key);
+            }
+            return Config::write_openrouter_models(&models);
+        }
+        if key == "control.port" {
+            let port: u16 = value
+                .parse()
+                .with_context(|| format!("{} must be a port number, got '{}'", key, value))?;
+            return write_field(&path_keys, port as i64);
+        }
+        if matches!(
+            key,
+            "control.enabled"
+                | "control.dbus"
+                | "output.paste"
+                | "audio.denoise_dump"
+                | "openrouter.progressive"
+                | "archive.enabled"
+        ) {
+            let enabled: bool = value
+                .parse()
+                .with_context(|| format!("{} must be true or false, got '{}'", key, value))?;
+            return write_field(&path_keys, enabled);
+        }
+        write_field(&path_keys, value)
+    }
 }
 
-/// Walk `[section][key]` in flov.toml, set the leaf string value, and write
-/// back. Creates the file and any missing sections.
-fn write_field(path_keys: &[&str], value: &str) -> Result<()> {
+/// Keys `Config::get` / `Config::set` understand, in flov.toml order.
+pub const SETTABLE_KEYS: &[&str] = &[
+    "whisper.model_path",
+    "whisper.language",
+    "audio.sample_rate",
+    "audio.device",
+    "audio.preroll_ms",
+    "audio.resample_quality",
+    "audio.gain",
+    "audio.denoise",
+    "audio.denoise_dump",
+    "audio.max_recording_secs",
+    "openrouter.api_key",
+    "openrouter.model",
+    "openrouter.system_prompt",
+    "openrouter.progressive",
+    "openrouter.timeout_secs",
+    "openrouter.total_timeout_secs",
+    "openrouter.retries",
+    "backend.choice",
+    "hotkey.combo",
+    "control.enabled",
+    "control.port",
+    "control.dbus",
+    "output.paste",
+    "archive.enabled",
+    "archive.max_mb",
+    "archive.max_days",
+];
+
+/// Walk `[section][key]` in flov.toml, set the leaf value, and write back.
+/// Creates the file and any missing sections.
+fn write_field(path_keys: &[&str], value: impl Into<toml_edit::Value>) -> Result<()> {
     let path = Config::path()?;
     let existing = if path.exists() {
         std::fs::read_to_string(&path).with_context(|| format!("read {:?}", path))?
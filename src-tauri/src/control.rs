@@ -0,0 +1,984 @@
+// Opt-in local control API, for Stream Deck, AutoHotkey, Raycast and
+// editor plugins that want to drive dictation without the global keyboard
+// hook.
+//
+// A small HTTP server on 127.0.0.1:<control.port>, off unless flov.toml has
+// `[control] enabled = true`. Every request must send
+// `Authorization: Bearer <token>`; the token is generated on first start
+// into `<data dir>/control-token` (see `paths::control_token_path`).
+// Start and stop just flip `active_mode`, exactly like pressing and
+// releasing the hotkey, so the recording loop can't tell who asked.
+//
+//   GET  /status            {"state": "idle" | "recording" | "transcribing", "cycle": n}
+//   POST /start             start recording
+//   POST /stop[?wait=false] stop; by default waits for and returns the transcript
+//   POST /toggle            /start when idle, /stop otherwise
+//   POST /cancel            stop and discard: nothing is transcribed or inserted
+//   GET  /history[?limit=n] recent transcripts, newest first
+//   GET  /events            text/event-stream of state changes
+//
+// `ControlHub` is the Tauri-free half: recording.rs feeds it every
+// pipeline event and the server reads status, history and results from
+// it, so the tests below run the real server without a window.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::hotkey::{MODE_IDLE, MODE_TRANSCRIBE};
+use crate::levels::Levels;
+use crate::paths;
+use crate::pipeline::PipelineEvent;
+
+/// Transcripts kept for `/history`. In memory only: stats.json already
+/// keeps the counts, and dictated text shouldn't pile up on disk.
+pub const HISTORY_LEN: usize = 50;
+
+/// Cycle results kept for `/stop` callers that wait on an older cycle
+/// while newer ones finish.
+const RESULTS_LEN: usize = 16;
+
+/// How long `/start` waits for the recording loop to pick the press up.
+/// Longer only when the previous recording is still being handed over.
+const START_WAIT: Duration = Duration::from_secs(1);
+/// Upper bound for `/stop` waiting on a transcript (large model on CPU,
+/// plus post-processing).
+const STOP_WAIT: Duration = Duration::from_secs(180);
+/// Comment line sent on an idle event stream so a closed client is
+/// noticed and its subscription dropped.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+/// Request threads. Each waiting `/stop` holds one, so this caps
+/// concurrent clients.
+const WORKERS: usize = 8;
+/// Open `/events` streams. Each gets its own thread rather than a worker,
+/// so idle subscribers can't starve the other endpoints.
+const MAX_STREAMS: usize = 16;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DictationState {
+    #[default]
+    Idle,
+    Recording,
+    Transcribing,
+}
+
+impl DictationState {
+    pub fn name(self) -> &'static str {
+        match self {
+            DictationState::Idle => "idle",
+            DictationState::Recording => "recording",
+            DictationState::Transcribing => "transcribing",
+        }
+    }
+}
+
+/// How a dictation cycle ended, as `/stop` reports it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum CycleResult {
+    /// Text was inserted (after post-processing).
+    Done {
+        text: String,
+    },
+    /// Nothing to insert: a short tap or silence.
+    Empty,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub cycle: u64,
+    /// Unix seconds.
+    pub at: u64,
+    pub text: String,
+    /// Input levels of the recording; `None` if it wasn't measured.
+    pub levels: Option<Levels>,
+    /// Archive id of the recording (archive.rs), when it was kept.
+    pub recording: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Status {
+    pub state: DictationState,
+    /// Number of the latest cycle; 0 before the first one.
+    pub cycle: u64,
+}
+
+#[derive(Default)]
+pub struct ControlHub {
+    inner: Mutex<Hub>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Hub {
+    started: u64,
+    /// The cycle whose microphone is open.
+    recording: Option<u64>,
+    /// The cycle `Controller::start` asked for, until the stop.
+    requested: Option<u64>,
+    /// Cycles that started and haven't ended. Recordings are queued for
+    /// processing, so one can be recording while earlier ones transcribe.
+    open: BTreeMap<u64, Progress>,
+    /// How the latest cycles ended, oldest first.
+    results: VecDeque<(u64, CycleResult)>,
+    history: VecDeque<HistoryEntry>,
+    subscribers: Vec<Sender<Value>>,
+}
+
+/// What an open cycle has produced so far.
+#[derive(Default)]
+struct Progress {
+    processing: bool,
+    /// Text inserted, until the cycle ends.
+    inserted: Option<String>,
+    /// Input levels of the recording.
+    levels: Option<Levels>,
+    recording: Option<String>,
+}
+
+impl Hub {
+    fn state(&self) -> DictationState {
+        if self.recording.is_some() {
+            DictationState::Recording
+        } else if self.open.values().any(|p| p.processing) {
+            DictationState::Transcribing
+        } else {
+            DictationState::Idle
+        }
+    }
+
+    fn progress(&mut self, cycle: u64) -> &mut Progress {
+        self.open.entry(cycle).or_default()
+    }
+
+    fn finish(&mut self, cycle: u64, result: CycleResult) {
+        self.open.remove(&cycle);
+        if self.recording == Some(cycle) {
+            self.recording = None;
+        }
+        if self.results.len() == RESULTS_LEN {
+            self.results.pop_front();
+        }
+        self.results.push_back((cycle, result));
+    }
+
+    fn result(&self, cycle: u64) -> Option<&CycleResult> {
+        self.results
+            .iter()
+            .find(|(c, _)| *c == cycle)
+            .map(|(_, result)| result)
+    }
+
+    /// Sends `event` to every `/events` client, dropping the ones that
+    /// went away.
+    fn broadcast(&mut self, event: Value) {
+        self.subscribers.retain(|s| s.send(event.clone()).is_ok());
+    }
+}
+
+impl ControlHub {
+    pub fn on_event(&self, cycle: u64, event: &PipelineEvent) {
+        let mut hub = self.inner.lock().unwrap();
+        let message = match event {
+            PipelineEvent::CycleStarted => {
+                hub.started = hub.started.max(cycle);
+                hub.open.insert(cycle, Progress::default());
+                None
+            }
+            PipelineEvent::Recording => {
+                hub.recording = Some(cycle);
+                Some(json!({ "event": "recording", "cycle": cycle }))
+            }
+            PipelineEvent::Transcribing => {
+                if hub.recording == Some(cycle) {
+                    hub.recording = None;
+                }
+                hub.progress(cycle).processing = true;
+                Some(json!({ "event": "transcribing", "cycle": cycle }))
+            }
+            PipelineEvent::Postprocessing => {
+                Some(json!({ "event": "postprocessing", "cycle": cycle }))
+            }
+            PipelineEvent::Pending(count) => {
+                Some(json!({ "event": "pending", "cycle": cycle, "count": count }))
+            }
+            PipelineEvent::Inserted(text) => {
+                let progress = hub.progress(cycle);
+                progress.inserted = Some(text.clone());
+                let levels = progress.levels;
+                let recording = progress.recording.take();
+                if hub.history.len() == HISTORY_LEN {
+                    hub.history.pop_front();
+                }
+                hub.history.push_back(HistoryEntry {
+                    cycle,
+                    at: unix_now(),
+                    text: text.clone(),
+                    levels,
+                    recording,
+                });
+                Some(json!({ "event": "done", "cycle": cycle, "text": text }))
+            }
+            PipelineEvent::Idle | PipelineEvent::Notice(_) => {
+                let result = match hub.open.get_mut(&cycle).and_then(|p| p.inserted.take()) {
+                    Some(text) => CycleResult::Done { text },
+                    None => CycleResult::Empty,
+                };
+                hub.finish(cycle, result);
+                let notice = match event {
+                    PipelineEvent::Notice(n) => Some(n.clone()),
+                    _ => None,
+                };
+                Some(json!({ "event": "idle", "cycle": cycle, "notice": notice }))
+            }
+            PipelineEvent::Error(message) => {
+                hub.finish(
+                    cycle,
+                    CycleResult::Error {
+                        message: message.clone(),
+                    },
+                );
+                Some(json!({ "event": "error", "cycle": cycle, "message": message }))
+            }
+            PipelineEvent::Transcribed {
+                levels, recording, ..
+            } => {
+                let progress = hub.progress(cycle);
+                progress.levels = Some(*levels);
+                progress.recording = recording.clone();
+                None
+            }
+            PipelineEvent::Spectrum { .. } | PipelineEvent::LimitApproaching(_) => None,
+        };
+        if let Some(message) = message {
+            hub.broadcast(message);
+        }
+        drop(hub);
+        self.changed.notify_all();
+    }
+
+    pub fn status(&self) -> Status {
+        let hub = self.inner.lock().unwrap();
+        Status {
+            state: hub.state(),
+            cycle: hub.started,
+        }
+    }
+
+    /// Newest first.
+    pub fn history(&self, limit: usize) -> Vec<HistoryEntry> {
+        let hub = self.inner.lock().unwrap();
+        hub.history.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub fn subscribe(&self) -> Receiver<Value> {
+        let (tx, rx) = mpsc::channel();
+        self.inner.lock().unwrap().subscribers.push(tx);
+        rx
+    }
+
+    /// The newest cycle that has started but not ended, if any.
+    pub fn running_cycle(&self) -> Option<u64> {
+        let hub = self.inner.lock().unwrap();
+        hub.open.keys().next_back().copied()
+    }
+
+    /// The cycle a stop ends: the one `Controller::start` asked for, or
+    /// the one recording when the hotkey started it. `None` when the loop
+    /// never picked the start up, so nothing was recorded.
+    fn take_stopped(&self) -> Option<u64> {
+        let mut hub = self.inner.lock().unwrap();
+        match hub.requested.take() {
+            Some(cycle) if cycle > hub.started => None,
+            // Still open, or already ended on its own (an audio error).
+            Some(cycle) if hub.open.contains_key(&cycle) || hub.recording.is_none() => Some(cycle),
+            _ => hub.recording,
+        }
+    }
+
+    /// Waits for a cycle after `after` to start; returns its number.
+    fn wait_started(&self, after: u64, timeout: Duration) -> Option<u64> {
+        let hub = self.inner.lock().unwrap();
+        let (hub, _) = self
+            .changed
+            .wait_timeout_while(hub, timeout, |h| h.started <= after)
+            .unwrap();
+        (hub.started > after).then_some(hub.started)
+    }
+
+    /// Waits for `cycle` to end and returns how it ended. `None` on
+    /// timeout, or when the result is too old to be kept.
+    pub fn wait_finished(&self, cycle: u64, timeout: Duration) -> Option<CycleResult> {
+        let hub = self.inner.lock().unwrap();
+        let (hub, _) = self
+            .changed
+            .wait_timeout_while(hub, timeout, |h| {
+                h.result(cycle).is_none() && (cycle > h.started || h.open.contains_key(&cycle))
+            })
+            .unwrap();
+        hub.result(cycle).cloned()
+    }
+}
+
+/// Start / stop / cancel as every control surface sees them (HTTP here,
+/// D-Bus on Linux). Never blocks; a caller that wants the transcript
+/// waits on `ControlHub::wait_finished`.
+#[derive(Clone)]
+pub struct Controller {
+    pub hub: Arc<ControlHub>,
+    pub active_mode: Arc<AtomicU8>,
+    /// Read by the pipeline: drop the running cycle instead of inserting.
+    pub cancel_requested: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlError {
+    AlreadyRecording,
+    NotRecording,
+}
+
+impl std::fmt::Display for ControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ControlError::AlreadyRecording => "already recording",
+            ControlError::NotRecording => "not recording",
+        })
+    }
+}
+
+impl std::error::Error for ControlError {}
+
+impl Controller {
+    /// True from start until stop, like a held hotkey.
+    pub fn is_active(&self) -> bool {
+        self.active_mode.load(Ordering::SeqCst) != MODE_IDLE
+    }
+
+    /// Returns the number the new cycle gets. Earlier recordings still
+    /// being transcribed don't hold it up.
+    pub fn start(&self) -> Result<u64, ControlError> {
+        let cycle = self.hub.status().cycle + 1;
+        self.active_mode
+            .compare_exchange(
+                MODE_IDLE,
+                MODE_TRANSCRIBE,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .map_err(|_| ControlError::AlreadyRecording)?;
+        self.hub.inner.lock().unwrap().requested = Some(cycle);
+        tracing::info!("control: start");
+        Ok(cycle)
+    }
+
+    /// Stops recording; the cycle goes on to transcribe and insert.
+    /// Returns that cycle — the one `start` returned, not whichever queued
+    /// cycle is newest — or `None` when the loop hadn't picked the start
+    /// up yet and nothing was recorded.
+    pub fn stop(&self) -> Result<Option<u64>, ControlError> {
+        if self.active_mode.swap(MODE_IDLE, Ordering::SeqCst) == MODE_IDLE {
+            return Err(ControlError::NotRecording);
+        }
+        tracing::info!("control: stop");
+        Ok(self.hub.take_stopped())
+    }
+
+    /// Stops recording and throws the audio away. While nothing is
+    /// recording, the next transcript to finish is dropped instead of
+    /// inserted.
+    pub fn cancel(&self) -> Result<(), ControlError> {
+        if !self.is_active() && self.hub.running_cycle().is_none() {
+            return Err(ControlError::NotRecording);
+        }
+        tracing::info!("control: cancel");
+        self.cancel_requested.store(true, Ordering::SeqCst);
+        self.active_mode.store(MODE_IDLE, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Binds 127.0.0.1:`port` and serves the API on a background thread.
+/// The token is read from (or created in) the data dir.
+pub fn spawn(port: u16, controller: Controller) -> Result<SocketAddr> {
+    let token = load_or_create_token()?;
+    let api = Api {
+        controller,
+        token,
+        streams: AtomicUsize::new(0),
+    };
+    let addr = serve(&format!("127.0.0.1:{}", port), api)?;
+    tracing::info!(
+        "control API listening on http://{} (token in {:?})",
+        addr,
+        paths::control_token_path()?
+    );
+    Ok(addr)
+}
+
+struct Api {
+    controller: Controller,
+    token: String,
+    /// Open `/events` streams.
+    streams: AtomicUsize,
+}
+
+fn serve(addr: &str, api: Api) -> Result<SocketAddr> {
+    let server = Server::http(addr).map_err(|e| anyhow!("control API: bind {}: {}", addr, e))?;
+    let local = server
+        .server_addr()
+        .to_ip()
+        .context("control API: not an IP socket")?;
+    let server = Arc::new(server);
+    let api = Arc::new(api);
+    // A fixed pool: /stop holds a worker for as long as a dictation
+    // lasts, so there are a few, but a burst of requests can't spawn
+    // threads without bound. /events streams run outside it.
+    for i in 0..WORKERS {
+        let server = server.clone();
+        let api = api.clone();
+        std::thread::Builder::new()
+            .name(format!("flov-control-{}", i))
+            .spawn(move || loop {
+                match server.recv() {
+                    Ok(request) => api.handle(request),
+                    Err(e) => {
+                        tracing::error!("control API: accept failed: {}", e);
+                        return;
+                    }
+                }
+            })
+            .context("spawn control API worker")?;
+    }
+    Ok(local)
+}
+
+impl Api {
+    fn handle(self: &Arc<Self>, request: Request) {
+        if !self.authorized(&request) {
+            respond(request, 401, json!({ "error": "missing or wrong token" }));
+            return;
+        }
+        let hub = &self.controller.hub;
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let (status, body) = match (request.method(), path) {
+            (Method::Get, "/events") => return self.spawn_stream(request),
+            (Method::Get, "/status") => (200, json!(hub.status())),
+            (Method::Get, "/history") => {
+                let limit = query_param(query, "limit")
+                    .and_then(|l| l.parse().ok())
+                    .unwrap_or(HISTORY_LEN);
+                (200, json!(hub.history(limit)))
+            }
+            (Method::Post, "/start") => self.start(),
+            (Method::Post, "/stop") => self.stop(query_param(query, "wait") != Some("false")),
+            (Method::Post, "/toggle") => {
+                if self.controller.is_active() {
+                    self.stop(true)
+                } else {
+                    self.start()
+                }
+            }
+            (Method::Post, "/cancel") => match self.controller.cancel() {
+                Ok(()) => (200, json!({ "cancelled": true })),
+                Err(e) => conflict(e),
+            },
+            _ => (
+                404,
+                json!({ "error": format!("no such endpoint: {}", path) }),
+            ),
+        };
+        respond(request, status, body);
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let expected = format!("Bearer {}", self.token);
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .is_some_and(|h| constant_time_eq(h.value.as_str().as_bytes(), expected.as_bytes()))
+    }
+
+    fn start(&self) -> (u16, Value) {
+        let cycle = match self.controller.start() {
+            Ok(cycle) => cycle,
+            Err(e) => return conflict(e),
+        };
+        match self.controller.hub.wait_started(cycle - 1, START_WAIT) {
+            Some(cycle) => (200, json!({ "cycle": cycle })),
+            // The loop is still finishing the previous transcription.
+            None => (202, json!({ "cycle": cycle })),
+        }
+    }
+
+    fn stop(&self, wait: bool) -> (u16, Value) {
+        let cycle = match self.controller.stop() {
+            Ok(Some(cycle)) => cycle,
+            Ok(None) => return (200, json!(CycleResult::Empty)),
+            Err(e) => return conflict(e),
+        };
+        if !wait {
+            return (202, json!({ "cycle": cycle }));
+        }
+        match self.controller.hub.wait_finished(cycle, STOP_WAIT) {
+            Some(result) => {
+                let mut body = json!(result);
+                body["cycle"] = json!(cycle);
+                (200, body)
+            }
+            None => (
+                504,
+                json!({ "error": "no transcript yet; follow /events", "cycle": cycle }),
+            ),
+        }
+    }
+
+    /// Hands an `/events` client its own thread, up to `MAX_STREAMS`.
+    fn spawn_stream(self: &Arc<Self>, request: Request) {
+        if self.streams.fetch_add(1, Ordering::SeqCst) >= MAX_STREAMS {
+            self.streams.fetch_sub(1, Ordering::SeqCst);
+            respond(request, 503, json!({ "error": "too many event streams" }));
+            return;
+        }
+        let api = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name("flov-control-events".into())
+            .spawn(move || {
+                api.stream_events(request);
+                api.streams.fetch_sub(1, Ordering::SeqCst);
+            });
+        if let Err(e) = spawned {
+            self.streams.fetch_sub(1, Ordering::SeqCst);
+            tracing::error!("control API: spawn event stream: {}", e);
+        }
+    }
+
+    /// Server-sent events. Written straight to the socket: tiny_http's own
+    /// chunked responses buffer 8 KB before anything reaches the client.
+    fn stream_events(&self, request: Request) {
+        let hub = &self.controller.hub;
+        let events = hub.subscribe();
+        let mut out = request.into_writer();
+        let head = "HTTP/1.1 200 OK\r\n\
+                    Content-Type: text/event-stream\r\n\
+                    Cache-Control: no-cache\r\n\
+                    Connection: close\r\n\r\n";
+        // The current state first, so a client that connects mid-cycle
+        // isn't left guessing until the next change.
+        let status = json!({ "event": "status", "state": hub.status() });
+        let mut chunk = format!("{}data: {}\n\n", head, status);
+        loop {
+            if out
+                .write_all(chunk.as_bytes())
+                .and_then(|_| out.flush())
+                .is_err()
+            {
+                return;
+            }
+            chunk = match events.recv_timeout(KEEPALIVE) {
+                Ok(event) => format!("data: {}\n\n", event),
+                Err(RecvTimeoutError::Timeout) => ": keepalive\n\n".to_string(),
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+        }
+    }
+}
+
+fn conflict(e: ControlError) -> (u16, Value) {
+    (409, json!({ "error": e.to_string() }))
+}
+
+fn respond(request: Request, status: u16, body: Value) {
+    let header = Header::from_bytes("Content-Type", "application/json").expect("static header");
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header);
+    if let Err(e) = request.respond(response) {
+        tracing::warn!("control API: respond failed: {}", e);
+    }
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Reads the API token, generating one on first use. Delete the file to
+/// rotate it (takes effect on the next start).
+pub fn load_or_create_token() -> Result<String> {
+    let path = paths::control_token_path()?;
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return Ok(existing.to_string());
+        }
+    }
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("generate control token: {}", e))?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    write_private(&path, &token).with_context(|| format!("write {:?}", path))?;
+    Ok(token)
+}
+
+/// The data dir is per-user on every platform; on Unix the file is also
+/// made owner-only, since /tmp-style shared homes exist.
+fn write_private(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    const TOKEN: &str = "secret";
+
+    fn controller() -> Controller {
+        Controller {
+            hub: Arc::new(ControlHub::default()),
+            active_mode: Arc::new(AtomicU8::new(MODE_IDLE)),
+            cancel_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn server() -> (Controller, String) {
+        let controller = controller();
+        let addr = serve(
+            "127.0.0.1:0",
+            Api {
+                controller: controller.clone(),
+                token: TOKEN.into(),
+                streams: AtomicUsize::new(0),
+            },
+        )
+        .unwrap();
+        (controller, format!("http://{}", addr))
+    }
+
+    fn call(method: &str, url: &str, token: &str) -> (u16, Value) {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into();
+        let auth = format!("Bearer {}", token);
+        let mut response = match method {
+            "GET" => agent.get(url).header("Authorization", &auth).call(),
+            _ => agent.post(url).header("Authorization", &auth).send_empty(),
+        }
+        .unwrap();
+        let status = response.status().as_u16();
+        (status, response.body_mut().read_json().unwrap())
+    }
+
+    /// Plays one hotkey cycle through the hub, the way the recording loop
+    /// would once `active_mode` flips.
+    fn run_cycle(hub: &ControlHub, cycle: u64, text: Option<&str>) {
+        hub.on_event(cycle, &PipelineEvent::CycleStarted);
+        hub.on_event(cycle, &PipelineEvent::Recording);
+        hub.on_event(cycle, &PipelineEvent::Transcribing);
+        if let Some(text) = text {
+            hub.on_event(cycle, &PipelineEvent::Inserted(text.into()));
+        }
+        hub.on_event(cycle, &PipelineEvent::Idle);
+    }
+
+    #[test]
+    fn hub_tracks_state_results_and_history() {
+        let hub = ControlHub::default();
+        run_cycle(&hub, 1, Some("one"));
+        run_cycle(&hub, 2, None);
+        hub.on_event(3, &PipelineEvent::CycleStarted);
+        hub.on_event(3, &PipelineEvent::Recording);
+
+        assert_eq!(
+            hub.status(),
+            Status {
+                state: DictationState::Recording,
+                cycle: 3
+            }
+        );
+        assert_eq!(hub.running_cycle(), Some(3));
+        assert_eq!(
+            hub.wait_finished(2, Duration::ZERO),
+            Some(CycleResult::Empty)
+        );
+        let history = hub.history(10);
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].cycle, history[0].text.as_str()), (1, "one"));
+
+        hub.on_event(3, &PipelineEvent::Error("Audio error: gone".into()));
+        assert_eq!(hub.status().state, DictationState::Idle);
+        assert_eq!(
+            hub.wait_finished(3, Duration::ZERO),
+            Some(CycleResult::Error {
+                message: "Audio error: gone".into()
+            })
+        );
+    }
+
+    #[test]
+    fn history_records_input_levels() {
+        let hub = ControlHub::default();
+        let levels = Levels {
+            rms: 0.05,
+            peak: 1.0,
+            clipped: 12,
+        };
+        hub.on_event(1, &PipelineEvent::CycleStarted);
+        hub.on_event(
+            1,
+            &PipelineEvent::Transcribed {
+                chars: 2,
+                seconds: 1.0,
+                levels,
+                recording: Some("1700000000000".into()),
+            },
+        );
+        hub.on_event(1, &PipelineEvent::Inserted("hi".into()));
+        hub.on_event(1, &PipelineEvent::Idle);
+        run_cycle(&hub, 2, Some("unmeasured"));
+
+        let history = hub.history(2);
+        assert_eq!(history[0].levels, None);
+        assert_eq!(history[0].recording, None);
+        assert_eq!(history[1].levels, Some(levels));
+        assert_eq!(history[1].recording.as_deref(), Some("1700000000000"));
+    }
+
+    #[test]
+    fn history_keeps_the_newest_entries() {
+        let hub = ControlHub::default();
+        for i in 0..HISTORY_LEN + 5 {
+            run_cycle(&hub, i as u64 + 1, Some(&i.to_string()));
+        }
+
+        let history = hub.history(usize::MAX);
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history[0].text, (HISTORY_LEN + 4).to_string());
+        assert_eq!(hub.history(2).len(), 2);
+    }
+
+    #[test]
+    fn hub_keeps_queued_cycles_apart() {
+        let hub = ControlHub::default();
+        hub.on_event(1, &PipelineEvent::CycleStarted);
+        hub.on_event(1, &PipelineEvent::Recording);
+        hub.on_event(1, &PipelineEvent::Transcribing);
+        hub.on_event(2, &PipelineEvent::CycleStarted);
+        hub.on_event(2, &PipelineEvent::Recording);
+        assert_eq!(hub.status().state, DictationState::Recording);
+
+        hub.on_event(1, &PipelineEvent::Inserted("first".into()));
+        hub.on_event(1, &PipelineEvent::Idle);
+        assert_eq!(hub.status().state, DictationState::Recording);
+        assert_eq!(hub.running_cycle(), Some(2));
+
+        hub.on_event(2, &PipelineEvent::Transcribing);
+        assert_eq!(hub.status().state, DictationState::Transcribing);
+        hub.on_event(2, &PipelineEvent::Inserted("second".into()));
+        hub.on_event(2, &PipelineEvent::Idle);
+
+        assert_eq!(hub.status().state, DictationState::Idle);
+        assert_eq!(
+            hub.wait_finished(1, Duration::ZERO),
+            Some(CycleResult::Done {
+                text: "first".into()
+            })
+        );
+        assert_eq!(
+            hub.wait_finished(2, Duration::ZERO),
+            Some(CycleResult::Done {
+                text: "second".into()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_wrong_token() {
+        let (_, base) = server();
+
+        let (status, body) = call("GET", &format!("{}/status", base), "nope");
+
+        assert_eq!(status, 401);
+        assert_eq!(body["error"], "missing or wrong token");
+    }
+
+    #[test]
+    fn start_and_stop_return_the_transcript() {
+        let (controller, base) = server();
+        // Stand-in for the recording loop: runs a cycle while the mode is
+        // held, like recording.rs does.
+        let loop_hub = controller.hub.clone();
+        let loop_mode = controller.active_mode.clone();
+        std::thread::spawn(move || {
+            while loop_mode.load(Ordering::SeqCst) == MODE_IDLE {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            loop_hub.on_event(1, &PipelineEvent::CycleStarted);
+            loop_hub.on_event(1, &PipelineEvent::Recording);
+            while loop_mode.load(Ordering::SeqCst) != MODE_IDLE {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            loop_hub.on_event(1, &PipelineEvent::Transcribing);
+            loop_hub.on_event(1, &PipelineEvent::Inserted("hello there".into()));
+            loop_hub.on_event(1, &PipelineEvent::Idle);
+        });
+
+        assert_eq!(
+            call("POST", &format!("{}/start", base), TOKEN),
+            (200, json!({ "cycle": 1 }))
+        );
+        assert_eq!(
+            call("POST", &format!("{}/start", base), TOKEN).0,
+            409,
+            "second start while recording"
+        );
+        assert_eq!(
+            call("GET", &format!("{}/status", base), TOKEN).1,
+            json!({ "state": "recording", "cycle": 1 })
+        );
+        assert_eq!(
+            call("POST", &format!("{}/stop", base), TOKEN),
+            (
+                200,
+                json!({ "result": "done", "text": "hello there", "cycle": 1 })
+            )
+        );
+        assert_eq!(call("POST", &format!("{}/stop", base), TOKEN).0, 409);
+        assert_eq!(
+            call("GET", &format!("{}/history?limit=1", base), TOKEN).1[0]["text"],
+            "hello there"
+        );
+    }
+
+    /// Opens `/events` and returns its `data:` payloads.
+    fn events(base: &str) -> impl Iterator<Item = String> {
+        let addr = base.trim_start_matches("http://");
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET /events HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\n\r\n",
+            addr, TOKEN
+        )
+        .unwrap();
+        BufReader::new(stream)
+            .lines()
+            .map(|l| l.unwrap())
+            .filter_map(|l| l.strip_prefix("data: ").map(str::to_string))
+    }
+
+    #[test]
+    fn events_stream_state_changes() {
+        let (controller, base) = server();
+        let hub = &controller.hub;
+        let mut lines = events(&base);
+
+        let first: Value = serde_json::from_str(&lines.next().unwrap()).unwrap();
+        assert_eq!(first["event"], "status");
+        run_cycle(hub, 1, Some("hi"));
+        let events: Vec<Value> = lines
+            .take(4)
+            .map(|l| serde_json::from_str(&l).unwrap())
+            .collect();
+
+        let names: Vec<&str> = events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["recording", "transcribing", "done", "idle"]);
+        assert_eq!(events[2]["text"], "hi");
+    }
+
+    #[test]
+    fn idle_event_streams_leave_the_workers_free() {
+        let (_, base) = server();
+        let mut streams: Vec<_> = (0..WORKERS + 2).map(|_| events(&base)).collect();
+        for stream in &mut streams {
+            assert!(stream.next().unwrap().contains("status"));
+        }
+
+        assert_eq!(call("GET", &format!("{}/status", base), TOKEN).0, 200);
+    }
+
+    #[test]
+    fn stop_ends_the_cycle_start_asked_for() {
+        let c = controller();
+        c.hub.on_event(1, &PipelineEvent::CycleStarted);
+        c.hub.on_event(1, &PipelineEvent::Recording);
+        c.hub.on_event(1, &PipelineEvent::Transcribing);
+
+        // Stopped before the loop picked the start up: not the queued cycle 1.
+        assert_eq!(c.start(), Ok(2));
+        assert_eq!(c.stop(), Ok(None));
+
+        assert_eq!(c.start(), Ok(2));
+        c.hub.on_event(2, &PipelineEvent::CycleStarted);
+        c.hub.on_event(2, &PipelineEvent::Recording);
+        assert_eq!(c.stop(), Ok(Some(2)));
+
+        // Started at the hotkey: the cycle that is recording.
+        c.hub.on_event(3, &PipelineEvent::CycleStarted);
+        c.hub.on_event(3, &PipelineEvent::Recording);
+        c.active_mode.store(MODE_TRANSCRIBE, Ordering::SeqCst);
+        assert_eq!(c.stop(), Ok(Some(3)));
+    }
+
+    #[test]
+    fn cancel_flags_the_running_cycle() {
+        let c = controller();
+        assert_eq!(c.cancel(), Err(ControlError::NotRecording));
+        assert_eq!(c.stop(), Err(ControlError::NotRecording));
+
+        assert_eq!(c.start(), Ok(1));
+        assert_eq!(c.start(), Err(ControlError::AlreadyRecording));
+        c.hub.on_event(1, &PipelineEvent::CycleStarted);
+        c.cancel().unwrap();
+
+        assert!(!c.is_active());
+        assert!(c.cancel_requested.load(Ordering::SeqCst));
+        // Still cancellable while the cycle winds down.
+        assert_eq!(c.cancel(), Ok(()));
+    }
+
+    #[test]
+    fn query_params() {
+        assert_eq!(query_param("limit=5&wait=false", "wait"), Some("false"));
+        assert_eq!(query_param("limit=5", "wait"), None);
+        assert_eq!(query_param("", "limit"), None);
+    }
+}
@@ -0,0 +1,311 @@
+// Linux desktop integration: the control actions as a session-bus service,
+// so compositor shortcuts can drive dictation without the evdev hook
+// (which needs root or the `input` group, and which Wayland compositors
+// would rather not see anyway).
+//
+//   bus name   com.flov.App
+//   object     /com/flov/App
+//   interface  com.flov.App
+//     Start() -> t cycle   Stop()   Toggle()   Cancel()   State() -> s
+//     signal StateChanged(s state)   idle | recording | transcribing
+//     signal Transcript(s text)      the text that was inserted
+//
+// e.g. in sway: bindsym $mod+d exec gdbus call --session --dest com.flov.App \
+//   --object-path /com/flov/App --method com.flov.App.Toggle
+//
+// Methods go through the same `control::Controller` as the HTTP API, and
+// never block: the transcript arrives as a signal. On by default; turn it
+// off with `[control] dbus = false`.
+
+use std::sync::mpsc::Receiver;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use zbus::blocking::connection;
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+use crate::control::{ControlError, ControlHub, Controller, DictationState};
+
+pub const BUS_NAME: &str = "com.flov.App";
+pub const OBJECT_PATH: &str = "/com/flov/App";
+pub const INTERFACE: &str = "com.flov.App";
+
+struct Service {
+    controller: Controller,
+}
+
+impl From<ControlError> for fdo::Error {
+    fn from(e: ControlError) -> Self {
+        fdo::Error::Failed(e.to_string())
+    }
+}
+
+#[zbus::interface(name = "com.flov.App")]
+impl Service {
+    fn start(&self) -> fdo::Result<u64> {
+        Ok(self.controller.start()?)
+    }
+
+    fn stop(&self) -> fdo::Result<()> {
+        self.controller.stop()?;
+        Ok(())
+    }
+
+    fn toggle(&self) -> fdo::Result<()> {
+        if self.controller.is_active() {
+            self.controller.stop()?;
+        } else {
+            self.controller.start()?;
+        }
+        Ok(())
+    }
+
+    fn cancel(&self) -> fdo::Result<()> {
+        Ok(self.controller.cancel()?)
+    }
+
+    fn state(&self) -> &'static str {
+        self.controller.hub.status().state.name()
+    }
+
+    // Declared for introspection; emitted from `forward_signals`.
+    #[zbus(signal)]
+    async fn state_changed(emitter: &SignalEmitter<'_>, state: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn transcript(emitter: &SignalEmitter<'_>, text: &str) -> zbus::Result<()>;
+}
+
+/// Claims `com.flov.App` on the session bus and forwards pipeline events
+/// as signals for as long as the app runs.
+pub fn spawn(controller: Controller) -> Result<()> {
+    serve(connection::Builder::session()?, controller)?;
+    tracing::info!("D-Bus: serving {} at {}", BUS_NAME, OBJECT_PATH);
+    Ok(())
+}
+
+/// Connects with `builder` (the session bus in the app, a private bus in
+/// tests) and starts the signal thread, which owns the connection and so
+/// keeps the service up.
+fn serve(builder: connection::Builder<'_>, controller: Controller) -> Result<()> {
+    let hub = controller.hub.clone();
+    let events = hub.subscribe();
+    let conn = builder
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Service { controller })?
+        .build()
+        .with_context(|| format!("D-Bus: claim {}", BUS_NAME))?;
+    std::thread::Builder::new()
+        .name("flov-dbus-signals".into())
+        .spawn(move || forward_signals(&conn, &hub, events))
+        .context("spawn D-Bus signal thread")?;
+    Ok(())
+}
+
+/// `StateChanged` follows the hub's state rather than the event: with
+/// recordings queued, a job finishing while the next one records is no
+/// reason to report idle.
+fn forward_signals(conn: &zbus::blocking::Connection, hub: &ControlHub, events: Receiver<Value>) {
+    let mut last = DictationState::Idle;
+    for event in events {
+        let mut emitted = match event["event"].as_str() {
+            Some("done") => emit(
+                conn,
+                "Transcript",
+                event["text"].as_str().unwrap_or_default(),
+            ),
+            _ => Ok(()),
+        };
+        let state = hub.status().state;
+        if state != last {
+            last = state;
+            emitted = emitted.and(emit(conn, "StateChanged", state.name()));
+        }
+        if let Err(e) = emitted {
+            tracing::warn!("D-Bus: emit signal failed: {}", e);
+        }
+    }
+}
+
+fn emit(conn: &zbus::blocking::Connection, signal: &str, arg: &str) -> zbus::Result<()> {
+    conn.emit_signal(None::<()>, OBJECT_PATH, INTERFACE, signal, &(arg,))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotkey::MODE_IDLE;
+    use crate::pipeline::PipelineEvent;
+    use std::process::{Child, Command, Stdio};
+    use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+    use std::sync::Arc;
+
+    /// A private bus, so the tests neither need nor touch a desktop
+    /// session. Killed on drop.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Panics where dbus-daemon isn't installed, so the tests that need
+    /// it are `#[ignore]`d and run with `cargo test -- --ignored`.
+    fn private_bus() -> Bus {
+        use std::io::BufRead;
+
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("dbus-daemon must be installed to run the D-Bus tests");
+        let mut line = String::new();
+        std::io::BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        Bus {
+            daemon,
+            address: line.trim().to_string(),
+        }
+    }
+
+    fn controller() -> Controller {
+        Controller {
+            hub: Arc::new(ControlHub::default()),
+            active_mode: Arc::new(AtomicU8::new(MODE_IDLE)),
+            cancel_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn call<R>(conn: &zbus::blocking::Connection, method: &str) -> zbus::Result<R>
+    where
+        R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+    {
+        conn.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE), method, &())?
+            .body()
+            .deserialize()
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn methods_drive_the_controller() {
+        let bus = private_bus();
+        let c = controller();
+        serve(
+            connection::Builder::address(&*bus.address).unwrap(),
+            c.clone(),
+        )
+        .unwrap();
+        let client = connection::Builder::address(&*bus.address)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(call::<u64>(&client, "Start").unwrap(), 1);
+        assert!(c.is_active());
+        let err = call::<()>(&client, "Start").unwrap_err();
+        assert!(err.to_string().contains("already recording"), "{}", err);
+
+        call::<()>(&client, "Toggle").unwrap();
+        assert!(!c.is_active());
+
+        call::<()>(&client, "Toggle").unwrap();
+        c.hub.on_event(1, &PipelineEvent::CycleStarted);
+        call::<()>(&client, "Cancel").unwrap();
+        assert!(c.cancel_requested.load(Ordering::SeqCst));
+        assert_eq!(call::<String>(&client, "State").unwrap(), "idle");
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn pipeline_events_become_signals() {
+        let bus = private_bus();
+        let c = controller();
+        serve(
+            connection::Builder::address(&*bus.address).unwrap(),
+            c.clone(),
+        )
+        .unwrap();
+        let client = connection::Builder::address(&*bus.address)
+            .unwrap()
+            .build()
+            .unwrap();
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(INTERFACE)
+            .unwrap()
+            .build();
+        let mut signals =
+            zbus::blocking::MessageIterator::for_match_rule(rule, &client, None).unwrap();
+
+        // The state is read when the signal goes out, so each change is
+        // awaited before the next event.
+        let mut next = || {
+            let m = signals.next().unwrap().unwrap();
+            let name = m.header().member().unwrap().to_string();
+            (name, m.body().deserialize::<String>().unwrap())
+        };
+        let signal = |name: &str, arg: &str| (name.to_string(), arg.to_string());
+
+        c.hub.on_event(1, &PipelineEvent::CycleStarted);
+        c.hub.on_event(1, &PipelineEvent::Recording);
+        assert_eq!(next(), signal("StateChanged", "recording"));
+        c.hub.on_event(1, &PipelineEvent::Transcribing);
+        assert_eq!(next(), signal("StateChanged", "transcribing"));
+        c.hub.on_event(1, &PipelineEvent::Inserted("привет".into()));
+        assert_eq!(next(), signal("Transcript", "привет"));
+        c.hub.on_event(1, &PipelineEvent::Idle);
+        assert_eq!(next(), signal("StateChanged", "idle"));
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn a_queued_job_ending_mid_recording_is_not_idle() {
+        let bus = private_bus();
+        let c = controller();
+        serve(
+            connection::Builder::address(&*bus.address).unwrap(),
+            c.clone(),
+        )
+        .unwrap();
+        let client = connection::Builder::address(&*bus.address)
+            .unwrap()
+            .build()
+            .unwrap();
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(INTERFACE)
+            .unwrap()
+            .build();
+        let mut signals =
+            zbus::blocking::MessageIterator::for_match_rule(rule, &client, None).unwrap();
+        let mut next = || {
+            let m = signals.next().unwrap().unwrap();
+            let name = m.header().member().unwrap().to_string();
+            (name, m.body().deserialize::<String>().unwrap())
+        };
+        let signal = |name: &str, arg: &str| (name.to_string(), arg.to_string());
+
+        c.hub.on_event(1, &PipelineEvent::CycleStarted);
+        c.hub.on_event(1, &PipelineEvent::Recording);
+        assert_eq!(next(), signal("StateChanged", "recording"));
+        c.hub.on_event(1, &PipelineEvent::Transcribing);
+        assert_eq!(next(), signal("StateChanged", "transcribing"));
+        c.hub.on_event(2, &PipelineEvent::CycleStarted);
+        c.hub.on_event(2, &PipelineEvent::Recording);
+        assert_eq!(next(), signal("StateChanged", "recording"));
+
+        // Cycle 1 ends while 2 records: its transcript, but no idle.
+        c.hub.on_event(1, &PipelineEvent::Inserted("первый".into()));
+        c.hub.on_event(1, &PipelineEvent::Idle);
+        assert_eq!(next(), signal("Transcript", "первый"));
+        c.hub.on_event(2, &PipelineEvent::Transcribing);
+        assert_eq!(next(), signal("StateChanged", "transcribing"));
+    }
+}
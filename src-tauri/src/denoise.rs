@@ -0,0 +1,579 @@
+// Noise suppression by spectral gating, on the 16 kHz recording before
+// it's transcribed.
+//
+// Fans, air conditioning and open-office murmur sit under the speech in
+// every frequency bin, and whisper hallucinates words into them. The
+// gate learns the noise floor of each bin from the recording's quietest
+// frames, then turns every time-frequency cell that doesn't rise clearly
+// above it down by a fixed amount. Cells are compared after averaging
+// with their neighbours, and the mask is smoothed the same way, so random
+// noise peaks don't slip through as chirping "musical noise". Stationary
+// noise only: a door slam still gets through.
+//
+// The STFT is recomputed for each pass (energies, noise floor, gating)
+// rather than kept: the full grids come to ~30 MB per minute of audio,
+// and a 512-point FFT is cheap.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use serde::Deserialize;
+
+use crate::audio::TRANSCRIBE_SAMPLE_RATE;
+
+/// 32 ms frames, 50% overlap.
+const FRAME: usize = 512;
+const HOP: usize = FRAME / 2;
+/// Fewer frames than this (~0.25 s) give no usable noise estimate.
+const MIN_FRAMES: usize = 16;
+/// The noise floor comes from this fraction of the quietest frames.
+const NOISE_FRACTION: usize = 10;
+/// Mask smoothing radius, in frames and in bins.
+const SMOOTH_FRAMES: usize = 2;
+const SMOOTH_BINS: usize = 2;
+
+/// `[audio] denoise`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Denoise {
+    #[default]
+    Off,
+    /// Noise down ~12 dB; speech stays untouched.
+    Light,
+    /// Noise down ~24 dB; can thin out quiet syllables.
+    Strong,
+}
+
+impl Denoise {
+    pub const NAMES: &'static [&'static str] = &["off", "light", "strong"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Denoise::Off => "off",
+            Denoise::Light => "light",
+            Denoise::Strong => "strong",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(Denoise::Off),
+            "light" => Some(Denoise::Light),
+            "strong" => Some(Denoise::Strong),
+            _ => None,
+        }
+    }
+
+    /// (gain of gated cells, gate height in noise standard deviations
+    /// above the noise mean). `None` when off.
+    fn params(self) -> Option<(f32, f32)> {
+        match self {
+            Denoise::Off => None,
+            Denoise::Light => Some((0.25, 3.0)),
+            Denoise::Strong => Some((0.063, 3.0)),
+        }
+    }
+}
+
+/// Returns `samples` with stationary noise suppressed, same length.
+/// Recordings too short to estimate the noise from come back unchanged.
+pub fn denoise(samples: &[f32], mode: Denoise) -> Vec<f32> {
+    match mode.params() {
+        Some((floor, n_std)) => gate(samples, floor, n_std),
+        None => samples.to_vec(),
+    }
+}
+
+fn gate(samples: &[f32], floor: f32, n_std: f32) -> Vec<f32> {
+    let stft = Stft::new(samples);
+    if stft.frames < MIN_FRAMES {
+        return samples.to_vec();
+    }
+    let threshold = noise_threshold(&stft, n_std);
+
+    // Each mask frame is ready SMOOTH_FRAMES after its magnitudes are, so
+    // only the two smoothing windows are held, never the whole grid.
+    let mut out = vec![0.0f32; samples.len()];
+    let mut masks = Smoother::new(stft.frames);
+    stft.smoothed_magnitudes(|_, mags| {
+        let raw_mask = mags
+            .iter()
+            .zip(&threshold)
+            .map(|(&m, &t)| if m > t { 1.0 } else { floor })
+            .collect();
+        masks.push(raw_mask, |f, mask| stft.synthesize(f, mask, &mut out));
+    });
+    out
+}
+
+/// Per bin: mean + `n_std` standard deviations of the smoothed magnitude
+/// over the quietest tenth of the frames. Two passes: one for each
+/// frame's energy, one summing up the quiet frames.
+fn noise_threshold(stft: &Stft, n_std: f32) -> Vec<f32> {
+    let mut energies = Vec::with_capacity(stft.frames);
+    stft.smoothed_magnitudes(|_, mags| energies.push(mags.iter().map(|m| m * m).sum::<f32>()));
+    let mut by_energy: Vec<usize> = (0..stft.frames).collect();
+    by_energy.sort_by(|&a, &b| energies[a].total_cmp(&energies[b]));
+    let mut quiet = vec![false; stft.frames];
+    let count = (stft.frames / NOISE_FRACTION).max(1);
+    for &f in &by_energy[..count] {
+        quiet[f] = true;
+    }
+
+    let mut sum = vec![0.0f64; BINS];
+    let mut sum_sq = vec![0.0f64; BINS];
+    stft.smoothed_magnitudes(|f, mags| {
+        if quiet[f] {
+            for (k, &m) in mags.iter().enumerate() {
+                sum[k] += m as f64;
+                sum_sq[k] += (m as f64).powi(2);
+            }
+        }
+    });
+    let n = count as f64;
+    sum.iter()
+        .zip(&sum_sq)
+        .map(|(&sum, &sum_sq)| {
+            let mean = sum / n;
+            let var = (sum_sq / n - mean * mean).max(0.0);
+            (mean + n_std as f64 * var.sqrt()) as f32
+        })
+        .collect()
+}
+
+const BINS: usize = FRAME / 2 + 1;
+
+/// Short-time Fourier transform of a recording, a frame at a time.
+struct Stft<'a> {
+    samples: &'a [f32],
+    /// HOP zeros on both ends, so every real sample is covered by two
+    /// frames and the windows add back up to exactly 1.
+    frames: usize,
+    /// sqrt-Hann for analysis and synthesis: their product is a periodic
+    /// Hann, which sums to 1 at 50% overlap.
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+}
+
+impl<'a> Stft<'a> {
+    fn new(samples: &'a [f32]) -> Self {
+        let window = (0..FRAME)
+            .map(|i| {
+                (0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME as f32).cos()).sqrt()
+            })
+            .collect();
+        let mut planner = FftPlanner::new();
+        Self {
+            samples,
+            frames: samples.len().div_ceil(HOP) + 1,
+            window,
+            fft: planner.plan_fft_forward(FRAME),
+            ifft: planner.plan_fft_inverse(FRAME),
+        }
+    }
+
+    /// Index into `samples` of position `i` in frame `f`, if it isn't
+    /// padding.
+    fn sample_index(&self, f: usize, i: usize) -> Option<usize> {
+        (f * HOP + i)
+            .checked_sub(HOP)
+            .filter(|&n| n < self.samples.len())
+    }
+
+    fn spectrum(&self, f: usize) -> Vec<Complex<f32>> {
+        let mut buf: Vec<Complex<f32>> = self
+            .window
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| {
+                let s = self.sample_index(f, i).map_or(0.0, |n| self.samples[n]);
+                Complex::new(s * w, 0.0)
+            })
+            .collect();
+        self.fft.process(&mut buf);
+        buf
+    }
+
+    /// Calls `each` with every frame's magnitudes, box-smoothed, in order.
+    fn smoothed_magnitudes(&self, mut each: impl FnMut(usize, &[f32])) {
+        let mut smoother = Smoother::new(self.frames);
+        for f in 0..self.frames {
+            let mags = self.spectrum(f)[..BINS].iter().map(|c| c.norm()).collect();
+            smoother.push(mags, &mut each);
+        }
+    }
+
+    /// Frame `f` with `mask` applied, added back into `out`.
+    fn synthesize(&self, f: usize, mask: &[f32], out: &mut [f32]) {
+        let mut spectrum = self.spectrum(f);
+        for k in 0..BINS {
+            spectrum[k] *= mask[k];
+            // Keep the spectrum conjugate-symmetric so the output is real.
+            if k > 0 && k < FRAME / 2 {
+                spectrum[FRAME - k] = spectrum[k].conj();
+            }
+        }
+        self.ifft.process(&mut spectrum);
+        for (i, (c, &w)) in spectrum.iter().zip(&self.window).enumerate() {
+            if let Some(n) = self.sample_index(f, i) {
+                out[n] += c.re * w / FRAME as f32;
+            }
+        }
+    }
+}
+
+/// Box average over ±SMOOTH_FRAMES × ±SMOOTH_BINS, fed one frame at a
+/// time and holding only the frames a window needs.
+struct Smoother {
+    frames: usize,
+    /// Bin-averaged rows, starting at frame `first`.
+    window: VecDeque<Vec<f32>>,
+    first: usize,
+    pushed: usize,
+    next: usize,
+}
+
+impl Smoother {
+    fn new(frames: usize) -> Self {
+        Self {
+            frames,
+            window: VecDeque::with_capacity(2 * SMOOTH_FRAMES + 1),
+            first: 0,
+            pushed: 0,
+            next: 0,
+        }
+    }
+
+    /// Takes the next frame's row and hands `emit` every smoothed frame
+    /// whose window it completes: the one SMOOTH_FRAMES back, and after
+    /// the last frame, all the rest.
+    fn push(&mut self, row: Vec<f32>, mut emit: impl FnMut(usize, &[f32])) {
+        // The box is separable: averaging over bins first, then frames,
+        // gives the same result.
+        let bins = row.len();
+        let row = (0..bins)
+            .map(|k| {
+                let ks = k.saturating_sub(SMOOTH_BINS)..(k + SMOOTH_BINS + 1).min(bins);
+                row[ks.clone()].iter().sum::<f32>() / ks.len() as f32
+            })
+            .collect();
+        self.window.push_back(row);
+        self.pushed += 1;
+
+        let ready = if self.pushed == self.frames {
+            self.frames
+        } else {
+            self.pushed.saturating_sub(SMOOTH_FRAMES)
+        };
+        while self.next < ready {
+            let f = self.next;
+            let fs = f.saturating_sub(SMOOTH_FRAMES)..(f + SMOOTH_FRAMES + 1).min(self.frames);
+            while self.first < fs.start {
+                self.window.pop_front();
+                self.first += 1;
+            }
+            let mut smoothed = vec![0.0f32; bins];
+            for row in self.window.range(..fs.end - self.first) {
+                for (s, &v) in smoothed.iter_mut().zip(row) {
+                    *s += v;
+                }
+            }
+            for s in &mut smoothed {
+                *s /= fs.len() as f32;
+            }
+            emit(f, &smoothed);
+            self.next += 1;
+        }
+    }
+}
+
+/// The denoise step as the recording pipeline runs it: the mode is
+/// switched from Settings between recordings, and with `dump_dir` set each
+/// recording is also written out before and after, for tuning.
+pub struct DenoiseStage {
+    mode: Mutex<Denoise>,
+    dump_dir: Option<PathBuf>,
+}
+
+impl DenoiseStage {
+    pub fn new(mode: Denoise, dump_dir: Option<PathBuf>) -> Self {
+        Self {
+            mode: Mutex::new(mode),
+            dump_dir,
+        }
+    }
+
+    /// `[audio] denoise` and, with `denoise_dump = true`, dumps under
+    /// `<data dir>/denoise/`.
+    pub fn from_config(cfg: &crate::config::AudioConfig) -> Arc<Self> {
+        let dump_dir = if cfg.denoise_dump {
+            crate::paths::denoise_dump_dir()
+                .map_err(|e| tracing::warn!("denoise dumps disabled: {:#}", e))
+                .ok()
+        } else {
+            None
+        };
+        Arc::new(Self::new(cfg.denoise, dump_dir))
+    }
+
+    pub fn mode(&self) -> Denoise {
+        *self.mode.lock().unwrap()
+    }
+
+    pub fn set_mode(&self, mode: Denoise) {
+        *self.mode.lock().unwrap() = mode;
+    }
+
+    pub fn apply(&self, samples: &mut Vec<f32>) {
+        let mode = self.mode();
+        if mode == Denoise::Off {
+            return;
+        }
+        let started = std::time::Instant::now();
+        let cleaned = denoise(samples, mode);
+        tracing::info!(
+            "denoise ({}) took {:?} for {} samples",
+            mode.name(),
+            started.elapsed(),
+            samples.len()
+        );
+        if let Some(dir) = &self.dump_dir {
+            if let Err(e) = dump(dir, samples, &cleaned) {
+                tracing::warn!("denoise dump failed: {:#}", e);
+            }
+        }
+        *samples = cleaned;
+    }
+}
+
+/// Writes `<unix ms>-before.wav` and `<unix ms>-after.wav` into `dir`.
+fn dump(dir: &Path, before: &[f32], after: &[f32]) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("create {:?}", dir))?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    for (suffix, samples) in [("before", before), ("after", after)] {
+        let path = dir.join(format!("{}-{}.wav", stamp, suffix));
+        write_wav(&path, samples)?;
+    }
+    tracing::info!("denoise dump: {:?}/{}-{{before,after}}.wav", dir, stamp);
+    Ok(())
+}
+
+/// 16 kHz mono 32-bit float WAV.
+pub fn write_wav(path: &Path, samples: &[f32]) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: TRANSCRIBE_SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer =
+        hound::WavWriter::create(path, spec).with_context(|| format!("create {:?}", path))?;
+    for &s in samples {
+        writer.write_sample(s)?;
+    }
+    writer
+        .finalize()
+        .with_context(|| format!("write {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::Levels;
+
+    /// Deterministic white noise in [-amplitude, amplitude].
+    fn noise(len: usize, amplitude: f32, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                ((state >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    /// A voiced-speech stand-in: 150 Hz fundamental with falling
+    /// harmonics, on for 0.5 s and off for 0.5 s.
+    fn speech(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                if (i / 8_000) % 2 == 1 {
+                    return 0.0;
+                }
+                let t = i as f32 / 16_000.0;
+                (1..=8)
+                    .map(|h| {
+                        0.2 / h as f32 * (2.0 * std::f32::consts::PI * 150.0 * h as f32 * t).sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn mix(a: &[f32], b: &[f32]) -> Vec<f32> {
+        a.iter().zip(b).map(|(x, y)| x + y).collect()
+    }
+
+    #[test]
+    fn off_and_short_recordings_pass_through() {
+        let samples = noise(32_000, 0.1, 1);
+        assert_eq!(denoise(&samples, Denoise::Off), samples);
+
+        let short = noise(HOP * 4, 0.1, 2);
+        assert_eq!(denoise(&short, Denoise::Strong), short);
+    }
+
+    #[test]
+    fn reconstruction_is_transparent_without_gating() {
+        // A floor of 1.0 leaves every cell as it was, so the STFT round
+        // trip must hand back the input, edges included.
+        let samples = mix(&speech(16_003), &noise(16_003, 0.05, 4));
+        let out = gate(&samples, 1.0, 2.0);
+
+        assert_eq!(out.len(), samples.len());
+        let err = out
+            .iter()
+            .zip(&samples)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        assert!(err < 1e-5, "max error {}", err);
+    }
+
+    #[test]
+    fn streaming_smoother_matches_the_whole_grid_box_average() {
+        let (frames, bins) = (11, 9);
+        let values = noise(frames * bins, 1.0, 5);
+        let grid: Vec<&[f32]> = values.chunks(bins).collect();
+
+        let mut smoothed = Vec::new();
+        let mut smoother = Smoother::new(frames);
+        for row in &grid {
+            smoother.push(row.to_vec(), |f, row| smoothed.push((f, row.to_vec())));
+        }
+
+        assert_eq!(smoothed.len(), frames);
+        for (f, row) in smoothed.iter().enumerate() {
+            assert_eq!(row.0, f);
+            let fs = f.saturating_sub(SMOOTH_FRAMES)..(f + SMOOTH_FRAMES + 1).min(frames);
+            for (k, &v) in row.1.iter().enumerate() {
+                let ks = k.saturating_sub(SMOOTH_BINS)..(k + SMOOTH_BINS + 1).min(bins);
+                let sum: f32 = grid[fs.clone()]
+                    .iter()
+                    .map(|row| row[ks.clone()].iter().sum::<f32>())
+                    .sum();
+                let expected = sum / (fs.len() * ks.len()) as f32;
+                assert!(
+                    (v - expected).abs() < 1e-5,
+                    "({}, {}): {} vs {}",
+                    f,
+                    k,
+                    v,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn white_noise_is_suppressed_between_words() {
+        let len = 64_000;
+        let clean = speech(len);
+        let noisy = mix(&clean, &noise(len, 0.03, 7));
+
+        for (mode, min_db) in [(Denoise::Light, 10.0), (Denoise::Strong, 20.0)] {
+            let out = denoise(&noisy, mode);
+            // Second pause (1.5–2 s), clear of the words' smoothed edges.
+            let gap = 24_000 + 4 * FRAME..32_000 - 4 * FRAME;
+            let before = Levels::measure(&noisy[gap.clone()]).rms_dbfs();
+            let after = Levels::measure(&out[gap]).rms_dbfs();
+            assert!(
+                before - after > min_db,
+                "{:?}: pause {:.1} -> {:.1} dBFS",
+                mode,
+                before,
+                after
+            );
+
+            // Speech keeps its level (second word, 1–1.5 s).
+            let word = 16_000 + FRAME..24_000 - FRAME;
+            let clean_db = Levels::measure(&clean[word.clone()]).rms_dbfs();
+            let out_db = Levels::measure(&out[word]).rms_dbfs();
+            assert!(
+                (clean_db - out_db).abs() < 1.5,
+                "{:?}: word {:.1} vs clean {:.1} dBFS",
+                mode,
+                out_db,
+                clean_db
+            );
+        }
+    }
+
+    #[test]
+    fn fan_hum_is_suppressed() {
+        // Low rumble plus a 120 Hz hum, the kind a desk fan makes.
+        let len = 48_000;
+        let fan: Vec<f32> = noise(len, 0.02, 3)
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                n + 0.02 * (i as f32 * 2.0 * std::f32::consts::PI * 120.0 / 16_000.0).sin()
+            })
+            .collect();
+        let out = denoise(&fan, Denoise::Light);
+
+        let middle = FRAME..len - FRAME;
+        let before = Levels::measure(&fan[middle.clone()]).rms_dbfs();
+        let after = Levels::measure(&out[middle]).rms_dbfs();
+        assert!(before - after > 9.0, "{:.1} -> {:.1} dBFS", before, after);
+    }
+
+    #[test]
+    fn stage_dumps_before_and_after() {
+        let dir = std::env::temp_dir().join(format!("flov-denoise-{}", std::process::id()));
+        let stage = DenoiseStage::new(Denoise::Light, Some(dir.clone()));
+        let noisy = mix(&speech(32_000), &noise(32_000, 0.03, 9));
+        let mut samples = noisy.clone();
+
+        stage.apply(&mut samples);
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 2);
+        assert!(names[0].ends_with("-after.wav") && names[1].ends_with("-before.wav"));
+        let before: Vec<f32> = hound::WavReader::open(dir.join(&names[1]))
+            .unwrap()
+            .samples::<f32>()
+            .map(|s| s.unwrap())
+            .collect();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(before, noisy);
+        assert_ne!(samples, noisy);
+
+        stage.set_mode(Denoise::Off);
+        let mut untouched = noisy.clone();
+        stage.apply(&mut untouched);
+        assert_eq!(untouched, noisy);
+    }
+
+    #[test]
+    fn mode_names_round_trip() {
+        for name in Denoise::NAMES {
+            assert_eq!(Denoise::parse(name).unwrap().name(), *name);
+        }
+        assert!(Denoise::parse("max").is_none());
+    }
+}
@@ -0,0 +1,189 @@
+// Audio file transcription: the part shared by the Settings drop view and
+// flov-cli. Decoding lives in audio_file; this cuts the samples into
+// CHUNK_SECS windows, runs each through the speech-to-text stage and
+// stitches the pieces back together as text plus timed cues.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::audio::TRANSCRIBE_SAMPLE_RATE;
+use crate::audio_file;
+use crate::pipeline::SpeechToText;
+use crate::subtitles::Cue;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileTranscript {
+    /// Non-empty chunk texts joined with a space.
+    pub text: String,
+    /// Whisper's segments on the file's timeline (one per chunk when the
+    /// speech-to-text stage can't report segments).
+    pub cues: Vec<Cue>,
+    pub duration: f64,
+    /// Backend that produced the last chunk.
+    pub backend: String,
+}
+
+/// Transcribes 16 kHz mono `samples` chunk by chunk. `on_chunk(i, n)` is
+/// called before chunk `i` of `n` starts, for progress. The first failing
+/// chunk fails the whole file: a transcript with a silent 30 s hole in the
+/// middle is worse than an error the user can retry.
+pub fn transcribe_samples(
+    stt: &dyn SpeechToText,
+    samples: &[f32],
+    mut on_chunk: impl FnMut(usize, usize),
+) -> Result<FileTranscript> {
+    let ranges = audio_file::chunk_ranges(samples.len());
+    let rate = TRANSCRIBE_SAMPLE_RATE as f64;
+    let mut texts = Vec::with_capacity(ranges.len());
+    let mut cues = Vec::new();
+    let mut backend = String::new();
+    for (i, range) in ranges.iter().enumerate() {
+        on_chunk(i, ranges.len());
+        let transcript = stt
+            .transcribe_timed(&samples[range.clone()], range.start as f64 / rate)
+            .with_context(|| format!("chunk {} of {} failed", i + 1, ranges.len()))?;
+        backend = transcript.backend;
+        texts.push(transcript.text.trim().to_string());
+        cues.extend(transcript.segments);
+    }
+    texts.retain(|t| !t.is_empty());
+    let text = texts.join(" ");
+    Ok(FileTranscript {
+        text,
+        cues,
+        duration: samples.len() as f64 / rate,
+        backend,
+    })
+}
+
+/// Decodes and transcribes one file. Same progress contract as
+/// `transcribe_samples`.
+pub fn transcribe_file(
+    stt: &dyn SpeechToText,
+    path: &Path,
+    on_chunk: impl FnMut(usize, usize),
+) -> Result<FileTranscript> {
+    let samples = audio_file::decode_file(path)?;
+    if samples.is_empty() {
+        anyhow::bail!("{:?} contains no audio", path);
+    }
+    transcribe_samples(stt, &samples, on_chunk)
+}
+
+/// Where to save the transcript of `source`: next to it, same stem, with
+/// `ext`. An existing file is never overwritten — `talk-1.txt`,
+/// `talk-2.txt`… are tried instead.
+pub fn output_path(source: &Path, ext: &str) -> PathBuf {
+    let candidate = source.with_extension(ext);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "transcript".to_string());
+    (1..)
+        .map(|n| source.with_file_name(format!("{}-{}.{}", stem, n, ext)))
+        .find(|p| !p.exists())
+        .expect("unbounded range")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcribe::Transcript;
+    use std::sync::Mutex;
+
+    /// Answers with the chunk length; fails on the chunk index in `fail_at`.
+    struct ChunkStt {
+        calls: Mutex<usize>,
+        fail_at: Option<usize>,
+    }
+
+    impl SpeechToText for ChunkStt {
+        fn has_model(&self) -> bool {
+            true
+        }
+
+        fn transcribe(&self, samples: &[f32]) -> Result<Transcript> {
+            let mut calls = self.calls.lock().unwrap();
+            let i = *calls;
+            *calls += 1;
+            if self.fail_at == Some(i) {
+                anyhow::bail!("sidecar crashed");
+            }
+            Ok(Transcript {
+                text: if samples.len() < 100 {
+                    "  ".into()
+                } else {
+                    format!(" {} ", samples.len())
+                },
+                backend: "cpu".into(),
+                failed_backends: Vec::new(),
+                segments: Vec::new(),
+            })
+        }
+    }
+
+    fn stt(fail_at: Option<usize>) -> ChunkStt {
+        ChunkStt {
+            calls: Mutex::new(0),
+            fail_at,
+        }
+    }
+
+    #[test]
+    fn stitches_chunks_with_offsets() {
+        let chunk = (audio_file::CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+        let samples = vec![0.0; 2 * chunk + 8_000];
+        let mut progress = Vec::new();
+
+        let t = transcribe_samples(&stt(None), &samples, |i, n| progress.push((i, n))).unwrap();
+
+        assert_eq!(progress, vec![(0, 3), (1, 3), (2, 3)]);
+        assert_eq!(t.text, "480000 480000 8000");
+        assert_eq!(t.cues.len(), 3);
+        assert_eq!((t.cues[1].start, t.cues[1].end), (30.0, 60.0));
+        assert_eq!(t.cues[2].end, 60.5);
+        assert_eq!(t.duration, 60.5);
+        assert_eq!(t.backend, "cpu");
+    }
+
+    #[test]
+    fn blank_chunks_leave_no_double_spaces() {
+        let chunk = (audio_file::CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+        let samples = vec![0.0; chunk + 50];
+
+        let t = transcribe_samples(&stt(None), &samples, |_, _| {}).unwrap();
+
+        assert_eq!(t.text, "480000");
+        assert_eq!(t.cues[1].text, "");
+    }
+
+    #[test]
+    fn failing_chunk_fails_the_file() {
+        let chunk = (audio_file::CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+        let samples = vec![0.0; 3 * chunk];
+
+        let err = transcribe_samples(&stt(Some(1)), &samples, |_, _| {}).unwrap_err();
+
+        assert_eq!(format!("{:#}", err), "chunk 2 of 3 failed: sidecar crashed");
+    }
+
+    #[test]
+    fn output_path_never_overwrites() {
+        let dir = std::env::temp_dir().join(format!("flov-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("voice.mp3");
+
+        assert_eq!(output_path(&source, "txt"), dir.join("voice.txt"));
+        std::fs::write(dir.join("voice.txt"), "").unwrap();
+        assert_eq!(output_path(&source, "txt"), dir.join("voice-1.txt"));
+        std::fs::write(dir.join("voice-1.txt"), "").unwrap();
+        assert_eq!(output_path(&source, "txt"), dir.join("voice-2.txt"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
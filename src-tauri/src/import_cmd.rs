@@ -0,0 +1,267 @@
+// Tauri commands for the file import view in Settings.
+//
+// Files dropped on the window are queued as jobs and transcribed one at a
+// time on a worker thread (a second sidecar next to a dictation is enough
+// GPU pressure already). Every status change is pushed as an
+// `import-progress` event carrying the whole job, so the view just
+// replaces its row.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::audio_file;
+use crate::import;
+use crate::subtitles::{Cue, SubtitleFormat};
+use crate::transcribe::Transcriber;
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ImportStatus {
+    Queued,
+    Decoding,
+    Transcribing { chunk: usize, chunks: usize },
+    Done,
+    Failed { error: String },
+}
+
+#[derive(Clone, Serialize)]
+pub struct ImportJob {
+    pub id: u64,
+    pub file: PathBuf,
+    pub name: String,
+    pub status: ImportStatus,
+    pub duration: Option<f64>,
+    pub text: String,
+    pub cues: Vec<Cue>,
+    pub backend: Option<String>,
+    /// Where the transcript was last saved, if it was.
+    pub saved_to: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub struct ImportState {
+    pub transcriber: Arc<Transcriber>,
+    pub jobs: Arc<Mutex<Vec<ImportJob>>>,
+    pub next_id: Arc<AtomicU64>,
+    /// True while the worker thread is draining the queue.
+    pub running: Arc<AtomicBool>,
+    /// Set by the tray item so a freshly created Settings window opens
+    /// straight into the import view (it may miss the event).
+    pub open_requested: Arc<AtomicBool>,
+}
+
+impl ImportState {
+    pub fn new(transcriber: Arc<Transcriber>) -> Self {
+        Self {
+            transcriber,
+            jobs: Arc::new(Mutex::new(Vec::new())),
+            next_id: Arc::new(AtomicU64::new(1)),
+            running: Arc::new(AtomicBool::new(false)),
+            open_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ImportView {
+    pub jobs: Vec<ImportJob>,
+    pub open: bool,
+}
+
+#[tauri::command]
+pub fn get_imports(state: State<ImportState>) -> ImportView {
+    ImportView {
+        jobs: state.jobs.lock().unwrap().clone(),
+        open: state.open_requested.swap(false, Ordering::SeqCst),
+    }
+}
+
+/// Queues `paths` for transcription. Files with an extension we can't
+/// decode are rejected up front so a stray drop doesn't sit in the list
+/// only to fail later. Returns the queued jobs.
+#[tauri::command]
+pub fn import_files(
+    paths: Vec<PathBuf>,
+    app: AppHandle,
+    state: State<ImportState>,
+) -> Result<Vec<ImportJob>, String> {
+    let (accepted, rejected): (Vec<_>, Vec<_>) = paths.into_iter().partition(|p| is_supported(p));
+    if accepted.is_empty() {
+        return Err(format!(
+            "unsupported file type: {} (supported: {})",
+            rejected
+                .iter()
+                .map(|p| display_name(p))
+                .collect::<Vec<_>>()
+                .join(", "),
+            audio_file::SUPPORTED_EXTENSIONS.join(", ")
+        ));
+    }
+    for p in &rejected {
+        tracing::warn!("import: skipping unsupported file {:?}", p);
+    }
+
+    let queued: Vec<ImportJob> = accepted
+        .into_iter()
+        .map(|file| ImportJob {
+            id: state.next_id.fetch_add(1, Ordering::SeqCst),
+            name: display_name(&file),
+            file,
+            status: ImportStatus::Queued,
+            duration: None,
+            text: String::new(),
+            cues: Vec::new(),
+            backend: None,
+            saved_to: None,
+        })
+        .collect();
+
+    // The worker clears `running` under the same lock once the queue is
+    // empty, so a job pushed here is either seen by it or starts a new one.
+    let mut jobs = state.jobs.lock().unwrap();
+    jobs.extend(queued.iter().cloned());
+    if !state.running.swap(true, Ordering::SeqCst) {
+        let worker_state = state.inner().clone();
+        let spawned = std::thread::Builder::new()
+            .name("flov-import".into())
+            .spawn(move || run_queue(&worker_state, &app));
+        if let Err(e) = spawned {
+            state.running.store(false, Ordering::SeqCst);
+            return Err(format!("failed to start import worker: {}", e));
+        }
+    }
+    Ok(queued)
+}
+
+/// Writes the transcript of job `id` next to its source file and returns
+/// the path written. `format` is `txt` (plain text) or a subtitle format
+/// (`srt`, `vtt`) built from the job's segments.
+#[tauri::command]
+pub fn save_import(id: u64, format: String, state: State<ImportState>) -> Result<PathBuf, String> {
+    let mut jobs = state.jobs.lock().unwrap();
+    let job = jobs
+        .iter_mut()
+        .find(|j| j.id == id)
+        .ok_or_else(|| format!("no import job {}", id))?;
+    if !matches!(job.status, ImportStatus::Done) {
+        return Err("transcription is not finished yet".into());
+    }
+    let (ext, contents) = if format == "txt" {
+        ("txt", format!("{}\n", job.text))
+    } else {
+        let subtitle = SubtitleFormat::from_name(&format)
+            .ok_or_else(|| format!("unknown export format '{}'", format))?;
+        (subtitle.extension(), subtitle.render(&job.cues))
+    };
+    let path = import::output_path(&job.file, ext);
+    std::fs::write(&path, contents).map_err(|e| format!("write {:?}: {}", path, e))?;
+    tracing::info!("import {}: saved {} to {:?}", id, ext, path);
+    job.saved_to = Some(path.clone());
+    Ok(path)
+}
+
+/// Drops finished and failed jobs from the list.
+#[tauri::command]
+pub fn clear_imports(state: State<ImportState>) {
+    state
+        .jobs
+        .lock()
+        .unwrap()
+        .retain(|j| !matches!(j.status, ImportStatus::Done | ImportStatus::Failed { .. }))
+}
+
+fn run_queue(state: &ImportState, app: &AppHandle) {
+    loop {
+        let next = {
+            let jobs = state.jobs.lock().unwrap();
+            let next = jobs
+                .iter()
+                .find(|j| matches!(j.status, ImportStatus::Queued))
+                .map(|j| (j.id, j.file.clone()));
+            if next.is_none() {
+                state.running.store(false, Ordering::SeqCst);
+            }
+            next
+        };
+        let Some((id, file)) = next else {
+            return;
+        };
+
+        update(state, app, id, |j| j.status = ImportStatus::Decoding);
+        tracing::info!("import {}: {:?}", id, file);
+        let result = import::transcribe_file(&*state.transcriber, &file, |chunk, chunks| {
+            update(state, app, id, |j| {
+                j.status = ImportStatus::Transcribing { chunk, chunks }
+            });
+        });
+        match result {
+            Ok(t) => {
+                tracing::info!(
+                    "import {}: {} chars from {:.1}s of audio on {}",
+                    id,
+                    t.text.chars().count(),
+                    t.duration,
+                    t.backend
+                );
+                update(state, app, id, |j| {
+                    j.status = ImportStatus::Done;
+                    j.duration = Some(t.duration);
+                    j.text = t.text;
+                    j.cues = t.cues;
+                    j.backend = Some(t.backend);
+                });
+            }
+            Err(e) => {
+                tracing::error!("import {} failed: {:#}", id, e);
+                update(state, app, id, |j| {
+                    j.status = ImportStatus::Failed {
+                        error: format!("{:#}", e),
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Applies `change` to job `id` (if it wasn't cleared meanwhile) and
+/// pushes the new snapshot to the view.
+fn update(state: &ImportState, app: &AppHandle, id: u64, change: impl FnOnce(&mut ImportJob)) {
+    let snapshot = {
+        let mut jobs = state.jobs.lock().unwrap();
+        let Some(job) = jobs.iter_mut().find(|j| j.id == id) else {
+            return;
+        };
+        change(job);
+        job.clone()
+    };
+    let _ = app.emit("import-progress", snapshot);
+}
+
+fn is_supported(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        audio_file::SUPPORTED_EXTENSIONS
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(e))
+    })
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Tray entry point: tray icons can't take file drops on any platform we
+/// ship, so the tray opens Settings on the import view instead.
+pub fn open_from_tray(app: &AppHandle, state: &ImportState) {
+    state.open_requested.store(true, Ordering::SeqCst);
+    if let Err(e) = crate::ui::open_settings_window(app) {
+        tracing::error!("open settings for import failed: {:#}", e);
+        return;
+    }
+    let _ = app.emit("import-open", ());
+}
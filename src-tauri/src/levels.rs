@@ -0,0 +1,293 @@
+// Input level metering and optional gain normalization.
+//
+// A quiet laptop mic hands whisper speech peaking at -35 dBFS, and it
+// transcribes noticeably worse than the same words at -10; a hot mic
+// clips and garbles consonants. `Meter` measures RMS / peak / clipped
+// samples (live in the capture tap for the pill, and over the finished
+// recording for history and stats), and `Gain` is the `[audio] gain`
+// stage that lifts quiet recordings before they reach the sidecar.
+// Clipping can't be undone, only reported.
+
+use serde::{Deserialize, Serialize};
+
+/// |sample| at or above this counts as clipped: integer formats top out
+/// one step below 1.0, and the resampler can ring slightly past it.
+pub const CLIP_LEVEL: f32 = 0.999;
+
+/// A recording whose peak stays under this (-20 dBFS) is "quiet".
+pub const QUIET_PEAK: f32 = 0.1;
+
+/// Gain stages work on 20 ms blocks of 16 kHz audio.
+const BLOCK: usize = 320;
+/// Peak normalization target, -1 dBFS.
+const TARGET_PEAK: f32 = 0.89;
+/// AGC speech level, -20 dBFS RMS.
+const TARGET_RMS: f32 = 0.1;
+/// At most +20 dB, so a silent room isn't turned into loud hiss.
+const MAX_GAIN: f32 = 10.0;
+/// AGC turns hot input down by at most 12 dB.
+const MIN_GAIN: f32 = 0.25;
+/// Blocks quieter than -50 dBFS RMS are pauses; the AGC holds its gain
+/// through them instead of pumping up the background.
+const GATE_RMS: f32 = 0.003;
+/// Envelope smoothing per block: rises within a few blocks, falls over
+/// ~0.4 s.
+const ATTACK: f32 = 0.5;
+const RELEASE: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Levels {
+    /// Linear, full scale = 1.0.
+    pub rms: f32,
+    pub peak: f32,
+    /// Samples at or above `CLIP_LEVEL`.
+    pub clipped: u64,
+}
+
+impl Levels {
+    pub fn measure(samples: &[f32]) -> Self {
+        let mut meter = Meter::default();
+        meter.push(samples);
+        meter.levels()
+    }
+
+    pub fn rms_dbfs(&self) -> f32 {
+        dbfs(self.rms)
+    }
+
+    pub fn peak_dbfs(&self) -> f32 {
+        dbfs(self.peak)
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.peak < QUIET_PEAK
+    }
+}
+
+/// 20·log10, floored at -120 dBFS for digital silence.
+pub fn dbfs(linear: f32) -> f32 {
+    20.0 * linear.max(1e-6).log10()
+}
+
+/// Running totals behind `Levels`, fed block by block.
+#[derive(Debug, Clone, Default)]
+pub struct Meter {
+    sum_sq: f64,
+    count: u64,
+    peak: f32,
+    clipped: u64,
+}
+
+impl Meter {
+    pub fn push(&mut self, samples: &[f32]) {
+        for &s in samples {
+            let a = s.abs();
+            self.sum_sq += (s as f64) * (s as f64);
+            self.peak = self.peak.max(a);
+            if a >= CLIP_LEVEL {
+                self.clipped += 1;
+            }
+        }
+        self.count += samples.len() as u64;
+    }
+
+    pub fn levels(&self) -> Levels {
+        let rms = if self.count == 0 {
+            0.0
+        } else {
+            (self.sum_sq / self.count as f64).sqrt() as f32
+        };
+        Levels {
+            rms,
+            peak: self.peak,
+            clipped: self.clipped,
+        }
+    }
+}
+
+/// `[audio] gain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Gain {
+    /// Samples go to the sidecar as captured.
+    #[default]
+    Off,
+    /// One gain for the whole recording, bringing its peak to -1 dBFS.
+    /// Never turns anything down.
+    Peak,
+    /// Follows the speech level block by block towards -20 dBFS RMS,
+    /// limited so peaks stay under -1 dBFS.
+    Agc,
+}
+
+impl Gain {
+    pub const NAMES: &'static [&'static str] = &["off", "peak", "agc"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Gain::Off => "off",
+            Gain::Peak => "peak",
+            Gain::Agc => "agc",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(Gain::Off),
+            "peak" => Some(Gain::Peak),
+            "agc" => Some(Gain::Agc),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, samples: &mut [f32]) {
+        match self {
+            Gain::Off => {}
+            Gain::Peak => normalize_peak(samples),
+            Gain::Agc => agc(samples),
+        }
+    }
+}
+
+fn normalize_peak(samples: &mut [f32]) {
+    let peak = samples.iter().fold(0.0f32, |p, s| p.max(s.abs()));
+    if peak == 0.0 {
+        return;
+    }
+    let gain = (TARGET_PEAK / peak).min(MAX_GAIN);
+    if gain > 1.0 {
+        samples.iter_mut().for_each(|s| *s *= gain);
+    }
+}
+
+fn agc(samples: &mut [f32]) {
+    let block_levels = |block: &[f32]| {
+        let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+        let peak = block.iter().fold(0.0f32, |p, s| p.max(s.abs()));
+        (rms, peak)
+    };
+    // Start from the first speech block's level so the opening words
+    // don't wait for the envelope to catch up. All silence: leave it.
+    let Some(mut env) = samples
+        .chunks(BLOCK)
+        .map(|b| block_levels(b).0)
+        .find(|&rms| rms > GATE_RMS)
+    else {
+        return;
+    };
+    let mut gain = (TARGET_RMS / env).clamp(MIN_GAIN, MAX_GAIN);
+
+    for block in samples.chunks_mut(BLOCK) {
+        let (rms, peak) = block_levels(block);
+        let mut target = gain;
+        if rms > GATE_RMS {
+            let coef = if rms > env { ATTACK } else { RELEASE };
+            env += coef * (rms - env);
+            target = (TARGET_RMS / env).clamp(MIN_GAIN, MAX_GAIN);
+        }
+        if peak > 0.0 {
+            target = target.min(TARGET_PEAK / peak);
+        }
+        // Ramp across the block so gain changes don't click.
+        let n = block.len() as f32;
+        for (i, s) in block.iter_mut().enumerate() {
+            let g = gain + (target - gain) * (i + 1) as f32 / n;
+            *s = (*s * g).clamp(-1.0, 1.0);
+        }
+        gain = target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (i as f32 * 2.0 * std::f32::consts::PI * 440.0 / 16_000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn meter_reports_rms_peak_and_clipped_samples() {
+        let mut meter = Meter::default();
+        meter.push(&[0.5, -0.5]);
+        meter.push(&[1.0, -1.0, 0.0]);
+
+        let levels = meter.levels();
+        assert!((levels.rms - (2.5f32 / 5.0).sqrt()).abs() < 1e-6);
+        assert_eq!(levels.peak, 1.0);
+        assert_eq!(levels.clipped, 2);
+        assert_eq!(Meter::default().levels(), Levels::default());
+    }
+
+    #[test]
+    fn dbfs_of_full_scale_sine() {
+        let levels = Levels::measure(&sine(1.0, 16_000));
+
+        assert!(
+            (levels.rms_dbfs() + 3.01).abs() < 0.05,
+            "{}",
+            levels.rms_dbfs()
+        );
+        assert!(levels.peak_dbfs().abs() < 0.01);
+        assert_eq!(dbfs(0.0), -120.0);
+    }
+
+    #[test]
+    fn peak_normalization_only_boosts() {
+        let mut quiet = sine(0.2, 16_000);
+        Gain::Peak.apply(&mut quiet);
+        assert!((Levels::measure(&quiet).peak - TARGET_PEAK).abs() < 1e-3);
+
+        let loud = sine(0.95, 16_000);
+        let mut same = loud.clone();
+        Gain::Peak.apply(&mut same);
+        assert_eq!(same, loud);
+
+        let mut faint = sine(0.001, 16_000);
+        let before = Levels::measure(&faint).peak;
+        Gain::Peak.apply(&mut faint);
+        assert!((Levels::measure(&faint).peak - before * MAX_GAIN).abs() < 1e-6);
+    }
+
+    #[test]
+    fn agc_brings_quiet_and_hot_speech_towards_target() {
+        for amplitude in [0.02, 0.6] {
+            let mut samples = sine(amplitude, 32_000);
+            Gain::Agc.apply(&mut samples);
+
+            let levels = Levels::measure(&samples[16_000..]);
+            assert!(
+                (levels.rms_dbfs() + 20.0).abs() < 1.0,
+                "{} -> {} dBFS",
+                amplitude,
+                levels.rms_dbfs()
+            );
+            assert!(levels.peak <= TARGET_PEAK + 1e-3);
+        }
+    }
+
+    #[test]
+    fn agc_leaves_silence_alone_and_holds_gain_through_pauses() {
+        let mut silence = vec![0.0005f32; 16_000];
+        Gain::Agc.apply(&mut silence);
+        assert!(silence.iter().all(|&s| s == 0.0005));
+
+        // Speech, a pause, speech: the pause isn't boosted to speech level.
+        let mut samples = sine(0.02, 8_000);
+        samples.extend(std::iter::repeat_n(0.0005, 8_000));
+        samples.extend(sine(0.02, 8_000));
+        Gain::Agc.apply(&mut samples);
+        let pause = Levels::measure(&samples[8_000..16_000]);
+        assert!(pause.peak < 0.0005 * MAX_GAIN + 1e-4, "{:?}", pause);
+    }
+
+    #[test]
+    fn gain_names_round_trip() {
+        for name in Gain::NAMES {
+            assert_eq!(Gain::parse(name).unwrap().name(), *name);
+        }
+        assert!(Gain::parse("loud").is_none());
+    }
+}
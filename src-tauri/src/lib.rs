@@ -1,14 +1,30 @@
 // Domain modules from existing flov.
+pub mod archive;
+pub mod archive_cmd;
 pub mod audio;
+pub mod audio_file;
+pub mod bench;
+pub mod bench_cmd;
 pub mod config;
+pub mod control;
+#[cfg(target_os = "linux")]
+mod dbus;
+pub mod denoise;
 pub mod hotkey;
+pub mod import;
+pub mod import_cmd;
 pub mod input;
+pub mod levels;
 pub mod models;
 pub mod models_cmd;
 pub mod paths;
+pub mod pipeline;
 pub mod postprocess;
+pub mod resample;
+pub mod sinks;
 pub mod state_cmd;
 pub mod stats;
+pub mod subtitles;
 pub mod transcribe;
 
 mod recording;
@@ -18,7 +34,7 @@ mod ui;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 #[cfg(target_os = "windows")]
 fn configure_webview2_environment() {
@@ -82,10 +98,10 @@ pub fn run() {
     tracing::info!("flov starting (Tauri)");
 
     let cfg = config::Config::load().expect("config load failed");
-    let recorder = Arc::new(
-        audio::AudioRecorder::new(cfg.audio.sample_rate, cfg.audio.device.as_deref())
-            .expect("audio init failed"),
-    );
+    let recorder = Arc::new(audio::AudioRecorder::new(&cfg.audio).expect("audio init failed"));
+    if let Err(e) = recorder.set_preroll(cfg.audio.preroll_ms) {
+        tracing::warn!("pre-roll unavailable: {:#}", e);
+    }
 
     // Shared mutable backend + model — written by the tray/Models window,
     // read by the Transcriber on every transcribe() call so a switch takes
@@ -99,20 +115,33 @@ pub fn run() {
         cfg.backend.choice
     );
 
+    // Filled in by the probe thread spawned in setup(); until then "auto"
+    // falls back to the plain binary-exists check.
+    let backend_probes = Arc::new(Mutex::new(None));
+
     let transcriber = Arc::new(
         transcribe::Transcriber::new(
             model_path.clone(),
             cfg.whisper.language.clone(),
             backend_choice.clone(),
+            backend_probes.clone(),
         )
         .expect("whisper init failed"),
     );
 
+    // Nothing is downloading yet, so every `.part` on disk is left over
+    // from a download that was killed mid-way.
+    let reclaimed = models::cleanup_partials(&[]);
+    if reclaimed > 0 {
+        tracing::info!("reclaimed {} bytes of stale partial downloads", reclaimed);
+    }
+
     let model_state = models_cmd::ModelState {
         model_path: model_path.clone(),
         in_flight: Arc::new(Mutex::new(Vec::new())),
     };
 
+    let pp_limits = postprocess::Limits::from_config(&cfg.openrouter);
     let initial_pp = if cfg.openrouter.api_key.is_empty() {
         None
     } else {
@@ -120,12 +149,13 @@ pub fn run() {
             cfg.openrouter.api_key.clone(),
             cfg.openrouter.model.clone(),
             cfg.openrouter.system_prompt.clone(),
+            pp_limits,
         )))
     };
     let post_processor = Arc::new(Mutex::new(initial_pp));
     let pp_settings = Arc::new(Mutex::new(state_cmd::PostprocessSettings {
         api_key: cfg.openrouter.api_key.clone(),
-        model: cfg.openrouter.model.clone(),
+        model: cfg.openrouter.model.join(", "),
         system_prompt: cfg.openrouter.system_prompt.clone(),
     }));
     let postprocess_enabled = Arc::new(AtomicBool::new(false));
@@ -157,18 +187,56 @@ pub fn run() {
     hotkey::set_hotkey_def(initial_def);
     let hotkey_combo = Arc::new(Mutex::new(cfg.hotkey.combo.clone()));
     let audio_device = Arc::new(Mutex::new(cfg.audio.device.clone()));
+    let denoise = denoise::DenoiseStage::from_config(&cfg.audio);
+    let archive = Arc::new(archive::Archive::from_config(&cfg.archive));
 
     let app_state = state_cmd::AppState {
         backend_choice: backend_choice.clone(),
         available_backends: available_backends.clone(),
+        backend_probes: backend_probes.clone(),
         postprocess_enabled: postprocess_enabled.clone(),
         post_processor: post_processor.clone(),
         pp_settings: pp_settings.clone(),
+        pp_limits,
         hotkey_combo: hotkey_combo.clone(),
         audio_device: audio_device.clone(),
+        recorder: recorder.clone(),
+        denoise: denoise.clone(),
         stats: stats.clone(),
     };
 
+    let bench_state = bench_cmd::BenchState {
+        transcriber: transcriber.clone(),
+        backend_probes: backend_probes.clone(),
+        running: Arc::new(AtomicBool::new(false)),
+    };
+    let import_state = import_cmd::ImportState::new(transcriber.clone());
+    let archive_state = archive_cmd::ArchiveState {
+        archive: archive.clone(),
+        transcriber: transcriber.clone(),
+        backend_probes: backend_probes.clone(),
+        running: Arc::new(AtomicBool::new(false)),
+    };
+
+    // Shared by the HTTP API and D-Bus. The hub is fed by the recording
+    // loop whether or not either is enabled; it's only a few counters.
+    let controller = control::Controller {
+        hub: Arc::new(control::ControlHub::default()),
+        active_mode: active_mode.clone(),
+        cancel_requested: Arc::new(AtomicBool::new(false)),
+    };
+    let control_port = cfg.control.enabled.then_some(cfg.control.port);
+    if !cfg.output.paste && cfg.output.sinks.is_empty() {
+        tracing::warn!("[output] paste = false and no sinks: transcripts go nowhere");
+    }
+    let output = cfg.output.clone();
+    let gain = cfg.audio.gain;
+    let max_recording = (cfg.audio.max_recording_secs > 0)
+        .then(|| std::time::Duration::from_secs(cfg.audio.max_recording_secs as u64));
+    let progressive_insert = cfg.openrouter.progressive;
+    #[cfg(target_os = "linux")]
+    let control_dbus = cfg.control.dbus;
+
     let stats_for_loop = stats.clone();
     let sample_rate_for_loop = recorder.output_sample_rate();
 
@@ -176,6 +244,9 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .manage(model_state)
         .manage(app_state)
+        .manage(bench_state)
+        .manage(import_state)
+        .manage(archive_state)
         .setup(move |app| {
             // LSUIElement=true в Info.plist скрывает Dock-иконку, но
             // когда Tauri показывает webview window (pill или Settings),
@@ -198,6 +269,22 @@ pub fn run() {
             }
 
             tray::setup(&app_handle)?;
+            tray::set_mic_open(&app_handle, recorder.preroll_ms() > 0);
+
+            // Probe every installed sidecar for a usable device. Each probe
+            // spawns a process and may wait on a GPU driver, so keep it off
+            // the UI thread; the Backend card refreshes on the event.
+            let probes_slot = backend_probes.clone();
+            let app_for_probe = app_handle.clone();
+            std::thread::Builder::new()
+                .name("flov-backend-probe".into())
+                .spawn(move || {
+                    let probes = transcribe::probe_backends();
+                    tracing::info!("backend ranking: {:?}", transcribe::rank_backends(&probes));
+                    *probes_slot.lock().unwrap() = Some(probes);
+                    let _ = app_for_probe.emit("backends-probed", ());
+                })
+                .expect("spawn backend probe thread");
 
             // Spawn the recording orchestration thread; it owns the recorder
             // loop and emits state/amplitude events to the webview.
@@ -211,7 +298,24 @@ pub fn run() {
             let app_for_thread = app_handle.clone();
             let sample_rate = sample_rate_for_loop;
 
+            if let Some(port) = control_port {
+                if let Err(e) = control::spawn(port, controller.clone()) {
+                    tracing::error!("control API disabled: {:#}", e);
+                }
+            }
+            #[cfg(target_os = "linux")]
+            if control_dbus {
+                if let Err(e) = dbus::spawn(controller.clone()) {
+                    tracing::warn!("D-Bus service unavailable: {:#}", e);
+                }
+            }
+
             recording::spawn_state_watchdog(is_recording.clone(), active_mode.clone());
+            recording::spawn_device_watcher(
+                app_handle.clone(),
+                recorder.clone(),
+                is_recording.clone(),
+            );
             recording::spawn_webview_reloader(
                 app_handle.clone(),
                 is_recording.clone(),
@@ -226,7 +330,15 @@ pub fn run() {
                 post_processor,
                 postprocess_enabled,
                 stats,
+                control: controller.hub.clone(),
+                cancel_requested: controller.cancel_requested.clone(),
+                output,
                 sample_rate,
+                denoise,
+                gain,
+                max_recording,
+                progressive_insert,
+                archive,
             });
 
             Ok(())
@@ -250,7 +362,22 @@ pub fn run() {
             state_cmd::set_hotkey,
             state_cmd::list_audio_inputs,
             state_cmd::set_audio_input,
+            state_cmd::set_audio_channel,
+            state_cmd::get_preroll,
+            state_cmd::set_preroll,
+            state_cmd::get_denoise,
+            state_cmd::set_denoise,
             state_cmd::get_stats,
+            bench_cmd::get_benchmark,
+            bench_cmd::run_benchmark,
+            import_cmd::get_imports,
+            import_cmd::import_files,
+            import_cmd::save_import,
+            import_cmd::clear_imports,
+            archive_cmd::list_recordings,
+            archive_cmd::set_archive_enabled,
+            archive_cmd::delete_recording,
+            archive_cmd::retranscribe,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -8,7 +8,17 @@
 
 use anyhow::{bail, Context, Result};
 use serde::Serialize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Extension of the in-progress download file. `download` streams into
+/// `<name>.part` and renames on success, so anything still carrying this
+/// extension after a crash / kill is garbage.
+pub const PARTIAL_EXT: &str = "part";
+
+/// Extra room we insist on beyond the model's own size. The OS, the log
+/// file and stats.json all live on the same volume — filling it to the
+/// last byte makes the rest of the app fail in confusing ways.
+const FREE_SPACE_HEADROOM: u64 = 200 * 1024 * 1024;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ModelInfo {
@@ -31,13 +41,18 @@ pub struct CatalogEntry {
     pub label: &'static str,
     pub filename: &'static str,
     pub size_bytes: u64,
+    /// Rough peak memory (weights + whisper.cpp compute buffers) needed to
+    /// run the model, from the whisper.cpp README table. Drives the
+    /// "largest model that fits" recommendation in the Backend card.
+    pub mem_bytes: u64,
     pub languages: &'static str,
     pub notes: &'static str,
 }
 
 // Sizes are taken from HuggingFace's `ggerganov/whisper.cpp` release manifest
-// (rounded to bytes). They're used only for UI/UX (progress bars, "do you
-// have free disk?"); the actual download writes whatever bytes arrive.
+// (rounded to bytes). They're used only for UI/UX (progress bars, and the
+// free-space preflight in `ensure_free_space`); the actual download writes
+// whatever bytes arrive.
 //
 // Curated to the 5 models that actually carry their weight: smaller variants
 // for fast/CPU runs, large-v3-turbo as the daily driver. Quantized + older
@@ -50,6 +65,7 @@ const CATALOG: &[CatalogEntry] = &[
         label: "Tiny",
         filename: "ggml-tiny.bin",
         size_bytes: 75_000_000,
+        mem_bytes: 273_000_000,
         languages: "multi",
         notes: "Smallest, fastest. Quality only OK for clean English.",
     },
@@ -59,6 +75,7 @@ const CATALOG: &[CatalogEntry] = &[
         label: "Base",
         filename: "ggml-base.bin",
         size_bytes: 142_000_000,
+        mem_bytes: 388_000_000,
         languages: "multi",
         notes: "Better than tiny, still very fast.",
     },
@@ -68,6 +85,7 @@ const CATALOG: &[CatalogEntry] = &[
         label: "Small",
         filename: "ggml-small.bin",
         size_bytes: 466_000_000,
+        mem_bytes: 852_000_000,
         languages: "multi",
         notes: "Reasonable quality / speed tradeoff.",
     },
@@ -77,6 +95,7 @@ const CATALOG: &[CatalogEntry] = &[
         label: "Medium",
         filename: "ggml-medium.bin",
         size_bytes: 1_530_000_000,
+        mem_bytes: 2_100_000_000,
         languages: "multi",
         notes: "Good quality, modest GPU.",
     },
@@ -86,6 +105,9 @@ const CATALOG: &[CatalogEntry] = &[
         label: "Large v3 Turbo",
         filename: "ggml-large-v3-turbo.bin",
         size_bytes: 1_620_000_000,
+        // Not in the README table: 1.6 GB of weights, but the 4-layer
+        // decoder keeps compute buffers well under medium's.
+        mem_bytes: 2_000_000_000,
         languages: "multi",
         notes: "Quality close to v3, ~6× faster decoder. Default.",
     },
@@ -156,6 +178,12 @@ pub fn entry_url(id: &str) -> Result<String> {
     Ok(url_for(e.filename))
 }
 
+/// Largest (= last, the catalog is ordered small → large) model whose
+/// `mem_bytes` fits in `budget_bytes` of device memory.
+pub fn recommend_for_memory(budget_bytes: u64) -> Option<&'static CatalogEntry> {
+    CATALOG.iter().rev().find(|e| e.mem_bytes <= budget_bytes)
+}
+
 pub fn entry_size(id: &str) -> Result<u64> {
     Ok(find(id)?.size_bytes)
 }
@@ -168,3 +196,254 @@ pub fn delete_file(id: &str) -> Result<()> {
     std::fs::remove_file(&path).with_context(|| format!("remove {:?}", path))?;
     Ok(())
 }
+
+/// Disk usage of the models directory, shown under the Models list.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageInfo {
+    /// Sum of every downloaded catalog model, by on-disk length.
+    pub used_bytes: u64,
+    /// Free bytes on the volume that holds the models directory. `None`
+    /// when the OS query failed — the UI just hides the figure.
+    pub free_bytes: Option<u64>,
+}
+
+pub fn storage_info() -> StorageInfo {
+    let used_bytes = CATALOG
+        .iter()
+        .filter_map(|e| local_path(e).ok())
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    let free_bytes = models_dir("whisper")
+        .and_then(|d| available_space(&d))
+        .map_err(|e| tracing::warn!("free space query failed: {:#}", e))
+        .ok()
+        .flatten();
+    StorageInfo {
+        used_bytes,
+        free_bytes,
+    }
+}
+
+/// Streams model `id` from HuggingFace into `<file>.part`, fsyncs, and
+/// renames it into place. `on_progress(downloaded, total)` fires at most
+/// ~10 times a second. Callers run `ensure_free_space` first; the
+/// Models window and the CLI both go through here.
+pub fn download(id: &str, mut on_progress: impl FnMut(u64, u64)) -> Result<PathBuf> {
+    use std::io::{Read, Write};
+
+    let url = entry_url(id)?;
+    let dest = entry_local_path(id)?;
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("mkdir {:?}", parent))?;
+    }
+    let tmp = dest.with_extension(PARTIAL_EXT);
+
+    tracing::info!("download {} -> {:?}", url, tmp);
+    let resp = ureq::get(&url)
+        .call()
+        .with_context(|| format!("GET {}", url))?;
+    let total = resp
+        .headers()
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(find(id)?.size_bytes);
+
+    let mut reader = resp.into_body().into_reader();
+    let mut file = std::fs::File::create(&tmp).with_context(|| format!("create {:?}", tmp))?;
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut downloaded: u64 = 0;
+    let mut last_emit = std::time::Instant::now();
+
+    loop {
+        let n = reader.read(&mut buf).context("read response")?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n]).context("write file")?;
+        downloaded += n as u64;
+        // Throttle progress to ~10/s; neither the UI nor a terminal
+        // needs finer.
+        if last_emit.elapsed() > std::time::Duration::from_millis(100) {
+            on_progress(downloaded, total);
+            last_emit = std::time::Instant::now();
+        }
+    }
+
+    file.sync_all().context("fsync")?;
+    drop(file);
+    std::fs::rename(&tmp, &dest).with_context(|| format!("rename {:?} -> {:?}", tmp, dest))?;
+    Ok(dest)
+}
+
+/// Refuses to start a download that can't fit. Compares the catalog size
+/// (plus `FREE_SPACE_HEADROOM`) against the free space on the models
+/// volume. A failed OS query is logged and, like a target without one,
+/// treated as "enough" — better to try and fail on write than to block
+/// downloads on an odd filesystem.
+pub fn ensure_free_space(id: &str) -> Result<()> {
+    let e = find(id)?;
+    let dir = models_dir(e.family)?;
+    let free = match available_space(&dir) {
+        Ok(Some(f)) => f,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            tracing::warn!("free space query for {:?} failed: {:#}", dir, err);
+            return Ok(());
+        }
+    };
+    if !has_room_for(e.size_bytes, free) {
+        bail!(
+            "not enough disk space for {}: needs {} MB, {} MB free",
+            e.label,
+            (e.size_bytes + FREE_SPACE_HEADROOM) / 1_000_000,
+            free / 1_000_000
+        );
+    }
+    Ok(())
+}
+
+fn has_room_for(size_bytes: u64, free_bytes: u64) -> bool {
+    free_bytes >= size_bytes.saturating_add(FREE_SPACE_HEADROOM)
+}
+
+/// Removes leftover `*.part` files from interrupted downloads. `keep`
+/// lists model ids whose download is running right now — their partial
+/// file is live and must not be yanked from under the writer. Returns the
+/// number of bytes reclaimed.
+pub fn cleanup_partials(keep: &[String]) -> u64 {
+    let live: Vec<PathBuf> = keep
+        .iter()
+        .filter_map(|id| entry_local_path(id).ok())
+        .map(|p| p.with_extension(PARTIAL_EXT))
+        .collect();
+    let mut families: Vec<&str> = CATALOG.iter().map(|e| e.family).collect();
+    families.sort();
+    families.dedup();
+
+    let mut reclaimed = 0;
+    for family in families {
+        let Ok(dir) = models_dir(family) else {
+            continue;
+        };
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(PARTIAL_EXT) {
+                continue;
+            }
+            if live.contains(&path) {
+                continue;
+            }
+            let len = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    tracing::info!("removed stale partial download {:?} ({} bytes)", path, len);
+                    reclaimed += len;
+                }
+                Err(e) => tracing::warn!("remove stale partial {:?} failed: {}", path, e),
+            }
+        }
+    }
+    reclaimed
+}
+
+/// The model to switch to when `deleted_id` (currently active) is about
+/// to be removed: the largest other downloaded model, since the catalog is
+/// ordered small → large and bigger is the better default.
+pub fn fallback_for(deleted_id: &str) -> Option<&'static CatalogEntry> {
+    pick_fallback(deleted_id, |e| {
+        local_path(e).map(|p| p.exists()).unwrap_or(false)
+    })
+}
+
+fn pick_fallback(
+    deleted_id: &str,
+    is_downloaded: impl Fn(&CatalogEntry) -> bool,
+) -> Option<&'static CatalogEntry> {
+    CATALOG
+        .iter()
+        .rev()
+        .filter(|e| e.id != deleted_id)
+        .find(|e| is_downloaded(e))
+}
+
+/// Free bytes on the volume holding `dir`. `None` on targets without a
+/// query wired up.
+#[cfg(target_os = "windows")]
+fn available_space(dir: &Path) -> Result<Option<u64>> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = dir
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut free: u64 = 0;
+    unsafe { GetDiskFreeSpaceExW(PCWSTR(wide.as_ptr()), Some(&mut free), None, None) }
+        .with_context(|| format!("GetDiskFreeSpaceExW {:?}", dir))?;
+    Ok(Some(free))
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn available_space(dir: &Path) -> Result<Option<u64>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+        .with_context(|| format!("path has interior NUL: {:?}", dir))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    let rc = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| format!("statvfs {:?}", dir));
+    }
+    // `f_bavail` = blocks available to unprivileged users (what a download
+    // can actually use), `f_frsize` = fundamental block size.
+    #[allow(clippy::unnecessary_cast)]
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn available_space(_dir: &Path) -> Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_room_requires_headroom_beyond_model_size() {
+        assert!(has_room_for(1_000, 1_000 + FREE_SPACE_HEADROOM));
+        assert!(!has_room_for(1_000, 1_000 + FREE_SPACE_HEADROOM - 1));
+    }
+
+    #[test]
+    fn fallback_prefers_largest_other_downloaded_model() {
+        let picked = pick_fallback("large-v3-turbo", |e| e.id == "tiny" || e.id == "small");
+
+        assert_eq!(picked.map(|e| e.id), Some("small"));
+    }
+
+    #[test]
+    fn recommends_largest_model_that_fits() {
+        assert_eq!(
+            recommend_for_memory(8_000_000_000).map(|e| e.id),
+            Some("large-v3-turbo")
+        );
+        assert_eq!(
+            recommend_for_memory(1_000_000_000).map(|e| e.id),
+            Some("small")
+        );
+        assert!(recommend_for_memory(100_000_000).is_none());
+    }
+
+    #[test]
+    fn fallback_skips_the_deleted_model() {
+        assert!(pick_fallback("tiny", |e| e.id == "tiny").is_none());
+    }
+}
@@ -3,14 +3,13 @@
 // Download runs on a worker thread so the command returns immediately; the
 // frontend listens for `model-progress` events to update the bar.
 
-use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use serde::Serialize;
 use tauri::{AppHandle, Emitter, State};
 
-use crate::models::{self, ModelInfo};
+use crate::models::{self, ModelInfo, StorageInfo};
 
 #[derive(Clone)]
 pub struct ModelState {
@@ -29,15 +28,48 @@ struct ProgressEvent {
     error: Option<String>,
 }
 
+#[derive(Serialize)]
+pub struct ModelsView {
+    pub models: Vec<ModelInfo>,
+    pub storage: StorageInfo,
+}
+
 #[tauri::command]
-pub fn list_models(state: State<ModelState>) -> Vec<ModelInfo> {
+pub fn list_models(state: State<ModelState>) -> ModelsView {
     let active = state.model_path.lock().unwrap().clone();
-    models::list(Some(&active))
+    ModelsView {
+        models: models::list(Some(&active)),
+        storage: models::storage_info(),
+    }
 }
 
+/// Deletes a downloaded model. If it is the active one, the largest other
+/// downloaded model takes over (and is persisted) so the next hotkey press
+/// still transcribes. Returns the id that became active, if any — the UI
+/// already warned the user before calling us.
 #[tauri::command]
-pub fn delete_model(id: String) -> Result<(), String> {
-    models::delete_file(&id).map_err(|e| e.to_string())
+pub fn delete_model(id: String, state: State<ModelState>) -> Result<Option<String>, String> {
+    let path = models::entry_local_path(&id).map_err(|e| e.to_string())?;
+    let was_active = *state.model_path.lock().unwrap() == path;
+    models::delete_file(&id).map_err(|e| e.to_string())?;
+    if !was_active {
+        return Ok(None);
+    }
+
+    let Some(next) = models::fallback_for(&id) else {
+        tracing::warn!("deleted active model {}; no other model downloaded", id);
+        return Ok(None);
+    };
+    let next_path = models::local_path(next).map_err(|e| e.to_string())?;
+    *state.model_path.lock().unwrap() = next_path.clone();
+    crate::config::Config::write_model_path(&next_path).map_err(|e| e.to_string())?;
+    tracing::info!(
+        "deleted active model {}; switched to {} ({:?})",
+        id,
+        next.id,
+        next_path
+    );
+    Ok(Some(next.id.to_string()))
 }
 
 #[tauri::command]
@@ -59,8 +91,6 @@ pub fn set_active_model(id: String, state: State<ModelState>) -> Result<(), Stri
 /// flows over `model-progress` events keyed by `id`.
 #[tauri::command]
 pub fn download_model(id: String, state: State<ModelState>, app: AppHandle) -> Result<(), String> {
-    let url = models::entry_url(&id).map_err(|e| e.to_string())?;
-    let dest = models::entry_local_path(&id).map_err(|e| e.to_string())?;
     let expected_size = models::entry_size(&id).map_err(|e| e.to_string())?;
 
     {
@@ -68,13 +98,28 @@ pub fn download_model(id: String, state: State<ModelState>, app: AppHandle) -> R
         if guard.iter().any(|x| x == &id) {
             return Err(format!("download already in progress: {}", id));
         }
+        // Reclaim space from earlier interrupted downloads before measuring
+        // what's free; the ones still running are left alone.
+        models::cleanup_partials(&guard);
+        models::ensure_free_space(&id).map_err(|e| e.to_string())?;
         guard.push(id.clone());
     }
     let in_flight = state.in_flight.clone();
     let model_path_state = state.model_path.clone();
 
     std::thread::spawn(move || {
-        let result = run_download(&id, &url, &dest, expected_size, &app);
+        let result = models::download(&id, |downloaded, total| {
+            let _ = app.emit(
+                "model-progress",
+                ProgressEvent {
+                    id: id.clone(),
+                    downloaded,
+                    total,
+                    done: false,
+                    error: None,
+                },
+            );
+        });
         if let Err(e) = &result {
             tracing::error!("download {} failed: {}", id, e);
             let _ = app.emit(
@@ -131,66 +176,6 @@ pub fn download_model(id: String, state: State<ModelState>, app: AppHandle) -> R
     Ok(())
 }
 
-fn run_download(
-    id: &str,
-    url: &str,
-    dest: &std::path::Path,
-    expected_size: u64,
-    app: &AppHandle,
-) -> anyhow::Result<()> {
-    use anyhow::Context;
-
-    if let Some(parent) = dest.parent() {
-        std::fs::create_dir_all(parent).with_context(|| format!("mkdir {:?}", parent))?;
-    }
-    let tmp = dest.with_extension("part");
-
-    tracing::info!("download {} -> {:?}", url, tmp);
-    let resp = ureq::get(url)
-        .call()
-        .with_context(|| format!("GET {}", url))?;
-    let total = resp
-        .headers()
-        .get("content-length")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(expected_size);
-
-    let mut reader = resp.into_body().into_reader();
-    let mut file = std::fs::File::create(&tmp).with_context(|| format!("create {:?}", tmp))?;
-    let mut buf = vec![0u8; 256 * 1024];
-    let mut downloaded: u64 = 0;
-    let mut last_emit = std::time::Instant::now();
-
-    loop {
-        let n = reader.read(&mut buf).context("read response")?;
-        if n == 0 {
-            break;
-        }
-        file.write_all(&buf[..n]).context("write file")?;
-        downloaded += n as u64;
-        // Throttle progress events to ~10/s; UI doesn't need finer.
-        if last_emit.elapsed() > std::time::Duration::from_millis(100) {
-            let _ = app.emit(
-                "model-progress",
-                ProgressEvent {
-                    id: id.to_string(),
-                    downloaded,
-                    total,
-                    done: false,
-                    error: None,
-                },
-            );
-            last_emit = std::time::Instant::now();
-        }
-    }
-
-    file.sync_all().context("fsync")?;
-    drop(file);
-    std::fs::rename(&tmp, dest).with_context(|| format!("rename {:?} -> {:?}", tmp, dest))?;
-    Ok(())
-}
-
 #[tauri::command]
 pub fn show_models_window(app: AppHandle) -> Result<(), String> {
     crate::ui::open_settings_window(&app).map_err(|e| e.to_string())
@@ -97,3 +97,31 @@ pub fn models_dir(family: &str) -> Result<PathBuf> {
     std::fs::create_dir_all(&dir).with_context(|| format!("create {:?}", dir))?;
     Ok(dir)
 }
+
+/// `<data_dir>/bench/` — user-supplied benchmark clips (`<name>.wav` +
+/// `<name>.txt`), read alongside the bundled set.
+pub fn bench_dir() -> Result<PathBuf> {
+    Ok(user_data_dir()?.join("bench"))
+}
+
+/// Path to `bench.json` — results of the last benchmark run.
+pub fn bench_results_path() -> Result<PathBuf> {
+    Ok(user_data_dir()?.join("bench.json"))
+}
+
+/// `<data_dir>/denoise/` — before/after WAVs of each recording when
+/// `[audio] denoise_dump = true`.
+pub fn denoise_dump_dir() -> Result<PathBuf> {
+    Ok(user_data_dir()?.join("denoise"))
+}
+
+/// `<data_dir>/recordings/` — the opt-in recording archive (archive.rs).
+pub fn recordings_dir() -> Result<PathBuf> {
+    Ok(user_data_dir()?.join("recordings"))
+}
+
+/// Path to `control-token` — the bearer token for the local control API
+/// (control.rs). Generated on first start with the API enabled.
+pub fn control_token_path() -> Result<PathBuf> {
+    Ok(user_data_dir()?.join("control-token"))
+}
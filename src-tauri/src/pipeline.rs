@@ -0,0 +1,1455 @@
+// One push-to-talk cycle as a state machine over swappable parts.
+//
+// A hotkey press starts a cycle: check the model, record while the hotkey
+// is held, transcribe, post-process, insert. Each stage is a `Step` and
+// `Pipeline::advance` moves exactly one stage forward, talking to the
+// outside world only through the traits below. recording.rs plugs in cpal,
+// the sidecar, OpenRouter, the clipboard and Tauri events; the tests at the
+// bottom plug in fakes, so every branch runs without a microphone or model.
+//
+// A cycle splits at `Step::Transcribe`: `record_cycle` owns the microphone
+// and hands the recording over as a job, `process` takes a job the rest of
+// the way. recording.rs queues jobs for one worker thread, so the next
+// dictation can start while earlier ones are still being transcribed, and
+// their texts are still inserted in the order they were spoken, each
+// held back while the next recording has the hotkey down.
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::archive::Archive;
+use crate::audio::TRANSCRIBE_SAMPLE_RATE;
+use crate::audio_file;
+use crate::denoise::DenoiseStage;
+use crate::hotkey;
+use crate::levels::{Gain, Levels};
+use crate::subtitles::Cue;
+use crate::transcribe::Transcript;
+
+/// Recordings shorter than this (0.1 s at 16 kHz) are accidental taps of
+/// the hotkey and are dropped without transcribing.
+pub const MIN_SAMPLES: usize = 1600;
+
+/// The pill counts down this many seconds before `Pipeline::max_recording`
+/// stops the recording.
+pub const LIMIT_WARNING_SECS: u64 = 30;
+
+/// How often a held-back paste checks whether the keyboard is free.
+const PASTE_POLL: Duration = Duration::from_millis(20);
+
+/// Pill text when there is no model to transcribe with.
+pub const NO_MODEL_MESSAGE: &str = "Скачай модель: Settings → Models";
+
+pub trait AudioSource: Send + Sync {
+    /// Records until `keep_going` returns false. Spectrum frames (20 bands)
+    /// and the levels captured so far go to `on_spectrum` while recording.
+    fn record(
+        &self,
+        keep_going: Box<dyn Fn() -> bool + Send>,
+        on_spectrum: Box<dyn Fn(Vec<f32>, Levels) + Send>,
+    ) -> Result<Vec<f32>>;
+
+    /// Rate of the samples `record` returns.
+    fn sample_rate(&self) -> u32;
+}
+
+pub trait SpeechToText: Send + Sync {
+    /// Cheap pre-flight, checked before recording starts.
+    fn has_model(&self) -> bool;
+    fn transcribe(&self, samples: &[f32]) -> Result<Transcript>;
+
+    /// Transcribes a window that starts `offset` seconds into a longer
+    /// file, with `Transcript::segments` on the file's timeline. The
+    /// default has no segment boundaries and returns the whole window as
+    /// one segment.
+    fn transcribe_timed(&self, samples: &[f32], offset: f64) -> Result<Transcript> {
+        let mut transcript = self.transcribe(samples)?;
+        transcript.segments = vec![Cue {
+            start: offset,
+            end: offset + samples.len() as f64 / TRANSCRIBE_SAMPLE_RATE as f64,
+            text: transcript.text.trim().to_string(),
+        }];
+        Ok(transcript)
+    }
+}
+
+pub trait TextPostprocessor: Send + Sync {
+    /// False when post-processing is off or not configured; the pill then
+    /// skips its post-processing phase.
+    fn active(&self) -> bool;
+
+    /// `Ok(None)` when post-processing is off or not configured — the raw
+    /// transcript is inserted as-is. The answer goes to `on_delta` piece
+    /// by piece as it streams in; once that returns false the stream is
+    /// dropped.
+    fn process(&self, text: &str, on_delta: &mut dyn FnMut(&str) -> bool)
+        -> Result<Option<String>>;
+}
+
+pub trait TextSink: Send + Sync {
+    fn insert(&self, text: &str);
+
+    /// A finished sentence of a text that is still streaming in. Sinks
+    /// that only want whole texts ignore it and get `insert_rest`.
+    fn insert_partial(&self, _piece: &str) {}
+
+    /// The end of `text`, `rest`, after its beginning went out through
+    /// `insert_partial`.
+    fn insert_rest(&self, text: &str, _rest: &str) {
+        self.insert(text);
+    }
+}
+
+pub trait PipelineObserver: Send + Sync {
+    /// `cycle` is the hotkey press the event belongs to. Events of queued
+    /// jobs interleave with those of the recording in progress.
+    fn on_event(&self, cycle: u64, event: PipelineEvent);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineEvent {
+    /// Hotkey pressed; the pill should appear.
+    CycleStarted,
+    Recording,
+    /// A spectrum frame with the running input levels.
+    Spectrum {
+        bands: Vec<f32>,
+        levels: Levels,
+    },
+    /// Seconds left before the length limit stops the recording; sent
+    /// once a second through the last LIMIT_WARNING_SECS.
+    LimitApproaching(u64),
+    Transcribing,
+    /// The transcript went to the post-processor.
+    Postprocessing,
+    /// Recordings handed over for processing and not finished yet, sent
+    /// whenever the count changes.
+    Pending(usize),
+    /// A non-empty transcript came back (before post-processing).
+    Transcribed {
+        chars: u64,
+        seconds: f64,
+        /// Of the recording as captured, before `Pipeline::gain`.
+        levels: Levels,
+        /// Archive id of the recording, when the archive is on.
+        recording: Option<String>,
+    },
+    /// This text went to the sink (after post-processing).
+    Inserted(String),
+    /// The cycle ended quietly.
+    Idle,
+    /// The cycle ended with something the user should know about.
+    Notice(String),
+    Error(String),
+}
+
+#[derive(Debug)]
+pub enum Step {
+    Start,
+    Record {
+        cycle: u64,
+    },
+    Transcribe {
+        samples: Vec<f32>,
+        job: Job,
+    },
+    Postprocess {
+        text: String,
+        notice: Option<String>,
+        job: Job,
+    },
+    Insert {
+        text: String,
+        /// Bytes at the start of `text` already in the sink, pasted
+        /// sentence by sentence while post-processing streamed.
+        inserted: usize,
+        notice: Option<String>,
+        postprocess_failed: bool,
+        job: Job,
+    },
+    Done(Outcome),
+}
+
+/// A finished recording on its way through the processing steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub cycle: u64,
+    /// Of the recording as captured, before `Pipeline::gain`.
+    pub levels: Levels,
+    /// Archive id of the recording, when the archive is on.
+    pub recording: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    NoModel,
+    AudioFailed,
+    TooShort,
+    TranscribeFailed,
+    Empty,
+    Cancelled,
+    Inserted {
+        chars: usize,
+        postprocess_failed: bool,
+    },
+}
+
+impl Outcome {
+    /// The cycle ended while the hotkey may still be held; the caller has
+    /// to wait for the release before arming the next cycle.
+    pub fn needs_release(&self) -> bool {
+        matches!(self, Outcome::NoModel | Outcome::AudioFailed)
+    }
+}
+
+pub struct Pipeline {
+    pub audio: Arc<dyn AudioSource>,
+    pub stt: Arc<dyn SpeechToText>,
+    pub postprocessor: Arc<dyn TextPostprocessor>,
+    pub sink: Arc<dyn TextSink>,
+    pub observer: Arc<dyn PipelineObserver>,
+    /// Hotkey state: recording continues while this isn't MODE_IDLE.
+    pub active_mode: Arc<AtomicU8>,
+    /// Noise suppression, applied to the recording before `gain`.
+    pub denoise: Arc<DenoiseStage>,
+    /// Applied to the recording before it's transcribed.
+    pub gain: Gain,
+    /// Recording stops here even with the hotkey held (a stuck modifier,
+    /// a forgotten toggle). `None` = no limit.
+    pub max_recording: Option<Duration>,
+    /// Paste post-processed text a sentence at a time as it streams in,
+    /// instead of all at once at the end.
+    pub progressive_insert: bool,
+    /// Keeps the raw recording and what became of it, when enabled.
+    pub archive: Arc<Archive>,
+    /// True while the microphone is open; read by the state watchdog.
+    pub is_recording: Arc<AtomicBool>,
+    /// Set by the control surfaces' cancel: the cycle ends without
+    /// transcribing or, if it's already past that, without inserting.
+    pub cancel_requested: Arc<AtomicBool>,
+    /// Number of the latest cycle; each `Step::Start` takes the next one.
+    pub cycles: AtomicU64,
+    /// Jobs recorded but not finished processing.
+    pub pending: AtomicUsize,
+}
+
+impl Pipeline {
+    /// Runs one cycle from `Step::Start` to its outcome.
+    pub fn run_cycle(&self) -> Outcome {
+        match self.record_cycle() {
+            Ok(job) => self.process(job),
+            Err(outcome) => outcome,
+        }
+    }
+
+    /// Runs a cycle up to its `Step::Transcribe`, the job for `process`.
+    /// `Err` when it ended before there was anything to transcribe.
+    pub fn record_cycle(&self) -> Result<Step, Outcome> {
+        let cycle_start = Instant::now();
+        let mut step = Step::Start;
+        loop {
+            step = match self.advance(step) {
+                Step::Done(outcome) => {
+                    tracing::info!(
+                        "recording cycle finished in {:?}: {:?}",
+                        cycle_start.elapsed(),
+                        outcome
+                    );
+                    return Err(outcome);
+                }
+                job @ Step::Transcribe { .. } => return Ok(job),
+                next => next,
+            };
+        }
+    }
+
+    /// Takes a job from `record_cycle` to its outcome.
+    pub fn process(&self, mut step: Step) -> Outcome {
+        let job_start = Instant::now();
+        loop {
+            step = match self.advance(step) {
+                Step::Done(outcome) => {
+                    tracing::info!("job finished in {:?}: {:?}", job_start.elapsed(), outcome);
+                    return outcome;
+                }
+                next => next,
+            };
+        }
+    }
+
+    /// Performs `step` and returns the one after it.
+    pub fn advance(&self, step: Step) -> Step {
+        match step {
+            Step::Start => self.start(),
+            Step::Record { cycle } => self.record(cycle),
+            Step::Transcribe { samples, job } => self.transcribe(&samples, job),
+            Step::Postprocess { text, notice, job } => self.postprocess(text, notice, job),
+            Step::Insert {
+                text,
+                inserted,
+                notice,
+                postprocess_failed,
+                job,
+            } => self.insert(&text, inserted, notice, postprocess_failed, job),
+            done @ Step::Done(_) => done,
+        }
+    }
+
+    fn emit(&self, cycle: u64, event: PipelineEvent) {
+        self.observer.on_event(cycle, event);
+    }
+
+    /// Notes what became of an archived recording. Archive trouble is
+    /// logged and never fails the cycle.
+    fn note(&self, recording: &Option<String>, change: impl FnOnce(&mut crate::archive::Entry)) {
+        if let Some(id) = recording {
+            if let Err(e) = self.archive.update(id, change) {
+                tracing::warn!("recording archive update failed: {:#}", e);
+            }
+        }
+    }
+
+    /// Ends a job with `event`. The pending count goes out first, so the
+    /// observer knows whether more results are on the way.
+    fn end_job(&self, cycle: u64, event: PipelineEvent) {
+        let left = self
+            .pending
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                Some(n.saturating_sub(1))
+            })
+            .unwrap_or(0)
+            .saturating_sub(1);
+        self.emit(cycle, PipelineEvent::Pending(left));
+        self.emit(cycle, event);
+    }
+
+    fn finish_quietly(&self, cycle: u64, notice: Option<String>) {
+        self.end_job(
+            cycle,
+            match notice {
+                Some(notice) => PipelineEvent::Notice(notice),
+                None => PipelineEvent::Idle,
+            },
+        );
+    }
+
+    fn start(&self) -> Step {
+        self.is_recording.store(true, Ordering::SeqCst);
+        // A cancel that arrived after the last job ended is stale; with
+        // jobs still queued it's meant for one of them.
+        if self.pending.load(Ordering::SeqCst) == 0 {
+            self.cancel_requested.store(false, Ordering::SeqCst);
+        }
+        let cycle = self.cycles.fetch_add(1, Ordering::SeqCst) + 1;
+        tracing::info!("recording start (cycle {})", cycle);
+        self.emit(cycle, PipelineEvent::CycleStarted);
+        if !self.stt.has_model() {
+            tracing::warn!("hotkey pressed but no model is configured");
+            self.emit(cycle, PipelineEvent::Error(NO_MODEL_MESSAGE.to_string()));
+            self.is_recording.store(false, Ordering::SeqCst);
+            return Step::Done(Outcome::NoModel);
+        }
+        self.emit(cycle, PipelineEvent::Recording);
+        Step::Record { cycle }
+    }
+
+    fn record(&self, cycle: u64) -> Step {
+        let active_mode = self.active_mode.clone();
+        let observer = self.observer.clone();
+        let limit_observer = self.observer.clone();
+        let max_recording = self.max_recording;
+        let last_warning = AtomicU64::new(u64::MAX);
+        let record_start = Instant::now();
+        let result = self.audio.record(
+            Box::new(move || {
+                if active_mode.load(Ordering::SeqCst) == hotkey::MODE_IDLE {
+                    return false;
+                }
+                let Some(limit) = max_recording else {
+                    return true;
+                };
+                let elapsed = record_start.elapsed();
+                if elapsed >= limit {
+                    tracing::warn!("recording stopped at the {:?} limit", limit);
+                    // Release the hotkey on the user's behalf, so the
+                    // loop doesn't start another cycle straight away.
+                    active_mode.store(hotkey::MODE_IDLE, Ordering::SeqCst);
+                    return false;
+                }
+                let remaining = (limit - elapsed).as_secs_f64().ceil() as u64;
+                if remaining <= LIMIT_WARNING_SECS
+                    && last_warning.swap(remaining, Ordering::SeqCst) != remaining
+                {
+                    limit_observer.on_event(cycle, PipelineEvent::LimitApproaching(remaining));
+                }
+                true
+            }),
+            Box::new(move |bands, levels| {
+                observer.on_event(cycle, PipelineEvent::Spectrum { bands, levels });
+            }),
+        );
+        // Taken while the microphone still counts as open, so `insert`
+        // leaves a cancel meant for this recording alone.
+        let cancelled = self.cancel_requested.swap(false, Ordering::SeqCst);
+        self.is_recording.store(false, Ordering::SeqCst);
+
+        let mut samples = match result {
+            Ok(samples) => samples,
+            Err(e) => {
+                tracing::error!("audio recording failed: {:#}", e);
+                self.emit(cycle, PipelineEvent::Error(format!("Audio error: {e:#}")));
+                return Step::Done(Outcome::AudioFailed);
+            }
+        };
+        tracing::info!(
+            "recording stop in {:?}, samples={}",
+            record_start.elapsed(),
+            samples.len()
+        );
+        if cancelled {
+            tracing::info!("recording cancelled");
+            self.emit(cycle, PipelineEvent::Idle);
+            return Step::Done(Outcome::Cancelled);
+        }
+        if samples.len() < MIN_SAMPLES {
+            self.emit(cycle, PipelineEvent::Idle);
+            return Step::Done(Outcome::TooShort);
+        }
+        let levels = Levels::measure(&samples);
+        tracing::info!(
+            "input levels: rms {:.1} dBFS, peak {:.1} dBFS, {} clipped samples",
+            levels.rms_dbfs(),
+            levels.peak_dbfs(),
+            levels.clipped
+        );
+        if levels.clipped > 0 {
+            tracing::warn!("input clipped; the microphone gain is too high");
+        }
+        let recording = self.archive.save(&samples, levels).unwrap_or_else(|e| {
+            tracing::warn!("recording not archived: {:#}", e);
+            None
+        });
+        self.denoise.apply(&mut samples);
+        self.gain.apply(&mut samples);
+        self.emit(cycle, PipelineEvent::Transcribing);
+        let pending = self.pending.fetch_add(1, Ordering::SeqCst) + 1;
+        self.emit(cycle, PipelineEvent::Pending(pending));
+        Step::Transcribe {
+            samples,
+            job: Job {
+                cycle,
+                levels,
+                recording,
+            },
+        }
+    }
+
+    fn transcribe(&self, samples: &[f32], job: Job) -> Step {
+        let transcribe_start = Instant::now();
+        let ranges = audio_file::pause_chunk_ranges(samples);
+        let result = if ranges.len() > 1 {
+            self.transcribe_chunks(samples, &ranges)
+        } else {
+            self.stt.transcribe(samples)
+        };
+        let transcript = match result {
+            Ok(t) => t,
+            Err(e) => {
+                tracing::error!("transcribe failed: {}", e);
+                self.note(&job.recording, |entry| {
+                    entry.error = Some(format!("{:#}", e))
+                });
+                let msg = if e.to_string().contains("model file not found") {
+                    NO_MODEL_MESSAGE.to_string()
+                } else {
+                    format!("Transcribe error: {}", e)
+                };
+                self.end_job(job.cycle, PipelineEvent::Error(msg));
+                return Step::Done(Outcome::TranscribeFailed);
+            }
+        };
+        // Shown instead of the plain Idle at the end of the cycle, so the
+        // user learns their preferred backend is broken.
+        let notice = transcript.failed_backends.first().map(|failed| {
+            format!(
+                "{} failed — used {}",
+                backend_label(failed),
+                backend_label(&transcript.backend)
+            )
+        });
+        let text = transcript.text;
+        self.note(&job.recording, |entry| {
+            entry.transcript = Some(text.clone());
+            entry.backend = Some(transcript.backend.clone());
+        });
+        tracing::info!(
+            "transcribe returned in {:?} via {}, chars={}",
+            transcribe_start.elapsed(),
+            transcript.backend,
+            text.chars().count()
+        );
+        if text.is_empty() {
+            self.finish_quietly(job.cycle, notice);
+            return Step::Done(Outcome::Empty);
+        }
+        tracing::info!("transcript: {}", text);
+
+        self.emit(
+            job.cycle,
+            PipelineEvent::Transcribed {
+                chars: text.chars().count() as u64,
+                seconds: samples.len() as f64 / self.audio.sample_rate() as f64,
+                levels: job.levels,
+                recording: job.recording.clone(),
+            },
+        );
+        Step::Postprocess { text, notice, job }
+    }
+
+    /// A long dictation goes to the sidecar in pause-aligned pieces, each
+    /// well inside the sidecar timeout, and comes back as one transcript.
+    /// The first failing piece fails the whole recording.
+    fn transcribe_chunks(&self, samples: &[f32], ranges: &[Range<usize>]) -> Result<Transcript> {
+        let mut texts = Vec::with_capacity(ranges.len());
+        let mut backend = String::new();
+        let mut failed_backends: Vec<String> = Vec::new();
+        for (i, range) in ranges.iter().enumerate() {
+            tracing::info!("transcribing chunk {} of {}", i + 1, ranges.len());
+            let transcript = self
+                .stt
+                .transcribe(&samples[range.clone()])
+                .inspect_err(|_| tracing::error!("chunk {} of {} failed", i + 1, ranges.len()))?;
+            texts.push(transcript.text.trim().to_string());
+            backend = transcript.backend;
+            for failed in transcript.failed_backends {
+                if !failed_backends.contains(&failed) {
+                    failed_backends.push(failed);
+                }
+            }
+        }
+        texts.retain(|t| !t.is_empty());
+        Ok(Transcript {
+            text: texts.join(" "),
+            backend,
+            failed_backends,
+            segments: Vec::new(),
+        })
+    }
+
+    /// True when a cancel is waiting for the job being processed. While
+    /// the next dictation is recording, a cancel is meant for it.
+    fn job_cancelled(&self) -> bool {
+        !self.is_recording.load(Ordering::SeqCst) && self.cancel_requested.load(Ordering::SeqCst)
+    }
+
+    /// True when no recording is open and the hotkey isn't held. A paste
+    /// sent otherwise mixes its Ctrl+V with the held modifiers (Ctrl+Win
+    /// becomes Win+Ctrl+V).
+    fn keyboard_free(&self) -> bool {
+        !self.is_recording.load(Ordering::SeqCst)
+            && self.active_mode.load(Ordering::SeqCst) == hotkey::MODE_IDLE
+    }
+
+    /// Holds a queued job's paste back until `keyboard_free`. Not capped:
+    /// `max_recording` and the stuck-recording watchdog end any wait.
+    fn wait_for_keyboard(&self) {
+        if self.keyboard_free() {
+            return;
+        }
+        tracing::info!("paste held back until the hotkey is released");
+        while !self.keyboard_free() {
+            std::thread::sleep(PASTE_POLL);
+        }
+    }
+
+    fn postprocess(&self, text: String, mut notice: Option<String>, job: Job) -> Step {
+        if self.postprocessor.active() {
+            self.emit(job.cycle, PipelineEvent::Postprocessing);
+        }
+        let mut streamed = String::new();
+        let mut inserted = 0;
+        let mut cancelled = false;
+        let result = self.postprocessor.process(&text, &mut |delta| {
+            if self.job_cancelled() {
+                cancelled = true;
+                return false;
+            }
+            streamed.push_str(if streamed.is_empty() {
+                delta.trim_start()
+            } else {
+                delta
+            });
+            // While the keyboard is busy, finished sentences pile up and
+            // go out together once it's free.
+            if self.progressive_insert && self.keyboard_free() {
+                if let Some(end) = sentence_end(&streamed[inserted..]) {
+                    self.sink
+                        .insert_partial(&streamed[inserted..inserted + end]);
+                    inserted += end;
+                }
+            }
+            true
+        });
+        if cancelled {
+            self.cancel_requested.store(false, Ordering::SeqCst);
+            tracing::info!("post-processing cancelled");
+            self.finish_quietly(job.cycle, notice);
+            return Step::Done(Outcome::Cancelled);
+        }
+        let (text, postprocess_failed) = match result {
+            // What was pasted so far is the start of `streamed`; the
+            // returned answer is the same text, trimmed.
+            Ok(Some(_)) if inserted > 0 => (streamed.trim_end().to_string(), false),
+            Ok(Some(processed)) => (processed, false),
+            Ok(None) => (text, false),
+            Err(e) if inserted > 0 => {
+                tracing::error!("postprocess failed partway: {:#}", e);
+                notice.get_or_insert_with(|| "Post-processing stopped partway".to_string());
+                streamed.truncate(inserted);
+                (streamed, true)
+            }
+            Err(e) => {
+                tracing::error!(
+                    "postprocess failed, falling back to raw transcript: {:#}",
+                    e
+                );
+                // Says so even over a backend notice: the pasted text
+                // isn't what the user expects.
+                notice = Some(format!("Post-processing failed ({}) — raw text pasted", e));
+                (text, true)
+            }
+        };
+        Step::Insert {
+            inserted: inserted.min(text.len()),
+            text,
+            notice,
+            postprocess_failed,
+            job,
+        }
+    }
+
+    fn insert(
+        &self,
+        text: &str,
+        inserted: usize,
+        notice: Option<String>,
+        postprocess_failed: bool,
+        job: Job,
+    ) -> Step {
+        self.wait_for_keyboard();
+        if self.job_cancelled() {
+            self.cancel_requested.store(false, Ordering::SeqCst);
+            tracing::info!("transcript dropped: cancelled");
+            self.finish_quietly(job.cycle, notice);
+            return Step::Done(Outcome::Cancelled);
+        }
+        if inserted == 0 {
+            self.sink.insert(text);
+        } else {
+            self.sink.insert_rest(text, &text[inserted..]);
+        }
+        self.note(&job.recording, |entry| entry.text = Some(text.to_string()));
+        self.emit(job.cycle, PipelineEvent::Inserted(text.to_string()));
+        self.finish_quietly(job.cycle, notice);
+        Step::Done(Outcome::Inserted {
+            chars: text.chars().count(),
+            postprocess_failed,
+        })
+    }
+}
+
+/// End of the last finished sentence in `s`, including the whitespace
+/// after it, so the pasted pieces join up. `None` while the first
+/// sentence is still coming.
+fn sentence_end(s: &str) -> Option<usize> {
+    let mut end = None;
+    let mut prev = None;
+    for (i, c) in s.char_indices() {
+        if c == '\n' || (c.is_whitespace() && matches!(prev, Some('.' | '!' | '?' | '…'))) {
+            end = Some(i + c.len_utf8());
+        }
+        prev = Some(c);
+    }
+    end
+}
+
+/// Display name for a backend id, matching the Backend settings card.
+pub fn backend_label(backend: &str) -> String {
+    match backend {
+        "cuda" => "CUDA".into(),
+        "vulkan" => "Vulkan".into(),
+        "metal" => "Metal".into(),
+        "cpu" => "CPU".into(),
+        other => other.to_string(),
+    }
+}
+
+/// Catches `is_recording` stuck true after the hotkey was released (a
+/// cycle that died without clearing it). Fed every 2 s by the watchdog
+/// thread; three stuck ticks in a row (~6 s) mean "reset".
+#[derive(Debug, Default)]
+pub struct StuckRecordingWatchdog {
+    stuck_ticks: u8,
+}
+
+impl StuckRecordingWatchdog {
+    pub const STUCK_TICKS: u8 = 3;
+
+    /// Returns true when `is_recording` should be forced back to false.
+    pub fn tick(&mut self, recording: bool, mode: u8) -> bool {
+        if recording && mode == hotkey::MODE_IDLE {
+            self.stuck_ticks = self.stuck_ticks.saturating_add(1);
+            if self.stuck_ticks >= Self::STUCK_TICKS {
+                self.stuck_ticks = 0;
+                return true;
+            }
+        } else {
+            self.stuck_ticks = 0;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::denoise::Denoise;
+    use std::sync::Mutex;
+
+    /// Gives back its one recording, after which the hotkey (the mode
+    /// it shares with the pipeline) is released.
+    struct FakeAudio(Mutex<Option<Result<Vec<f32>>>>, Arc<AtomicU8>);
+
+    impl AudioSource for FakeAudio {
+        fn record(
+            &self,
+            keep_going: Box<dyn Fn() -> bool + Send>,
+            on_spectrum: Box<dyn Fn(Vec<f32>, Levels) + Send>,
+        ) -> Result<Vec<f32>> {
+            assert!(keep_going(), "hotkey should be held while recording");
+            on_spectrum(vec![0.5; 20], Levels::default());
+            self.1.store(hotkey::MODE_IDLE, Ordering::SeqCst);
+            self.0.lock().unwrap().take().expect("recorded twice")
+        }
+
+        fn sample_rate(&self) -> u32 {
+            16_000
+        }
+    }
+
+    /// Records until `keep_going` says stop, like the microphone does.
+    struct HeldAudio;
+
+    impl AudioSource for HeldAudio {
+        fn record(
+            &self,
+            keep_going: Box<dyn Fn() -> bool + Send>,
+            _: Box<dyn Fn(Vec<f32>, Levels) + Send>,
+        ) -> Result<Vec<f32>> {
+            let started = Instant::now();
+            while keep_going() {
+                assert!(started.elapsed() < Duration::from_secs(5), "never stopped");
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            Ok(vec![0.1; 16_000])
+        }
+
+        fn sample_rate(&self) -> u32 {
+            16_000
+        }
+    }
+
+    /// One recording per call, of the next of these lengths.
+    struct QueuedAudio(Mutex<Vec<usize>>);
+
+    impl AudioSource for QueuedAudio {
+        fn record(
+            &self,
+            _: Box<dyn Fn() -> bool + Send>,
+            _: Box<dyn Fn(Vec<f32>, Levels) + Send>,
+        ) -> Result<Vec<f32>> {
+            Ok(vec![0.1; self.0.lock().unwrap().remove(0)])
+        }
+
+        fn sample_rate(&self) -> u32 {
+            16_000
+        }
+    }
+
+    struct FakeStt {
+        has_model: bool,
+        result: Mutex<Option<Result<Transcript>>>,
+    }
+
+    impl SpeechToText for FakeStt {
+        fn has_model(&self) -> bool {
+            self.has_model
+        }
+
+        fn transcribe(&self, _: &[f32]) -> Result<Transcript> {
+            self.result
+                .lock()
+                .unwrap()
+                .take()
+                .expect("transcribed twice")
+        }
+    }
+
+    /// Answers each call with the length it was given.
+    struct LengthStt;
+
+    impl SpeechToText for LengthStt {
+        fn has_model(&self) -> bool {
+            true
+        }
+
+        fn transcribe(&self, samples: &[f32]) -> Result<Transcript> {
+            transcript(&samples.len().to_string())
+        }
+    }
+
+    struct FakePostprocessor(fn(&str) -> Result<Option<String>>);
+
+    impl TextPostprocessor for FakePostprocessor {
+        fn active(&self) -> bool {
+            true
+        }
+
+        fn process(&self, text: &str, _: &mut dyn FnMut(&str) -> bool) -> Result<Option<String>> {
+            (self.0)(text)
+        }
+    }
+
+    /// Streams `pieces`, then answers with all of them, or fails.
+    struct StreamingPostprocessor {
+        pieces: Vec<&'static str>,
+        fail: bool,
+        /// Set after the first piece, like a cancel arriving mid-stream.
+        cancel: Option<Arc<AtomicBool>>,
+    }
+
+    impl TextPostprocessor for StreamingPostprocessor {
+        fn active(&self) -> bool {
+            true
+        }
+
+        fn process(
+            &self,
+            _: &str,
+            on_delta: &mut dyn FnMut(&str) -> bool,
+        ) -> Result<Option<String>> {
+            for piece in &self.pieces {
+                if !on_delta(piece) {
+                    anyhow::bail!("stream stopped");
+                }
+                if let Some(cancel) = &self.cancel {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+            }
+            if self.fail {
+                anyhow::bail!("connection reset");
+            }
+            Ok(Some(self.pieces.concat().trim().to_string()))
+        }
+    }
+
+    /// Keeps every piece that reached it, whole texts and parts alike.
+    #[derive(Default)]
+    struct FakeSink(Mutex<Vec<String>>);
+
+    impl TextSink for FakeSink {
+        fn insert(&self, text: &str) {
+            self.0.lock().unwrap().push(text.to_string());
+        }
+
+        fn insert_partial(&self, piece: &str) {
+            self.0.lock().unwrap().push(piece.to_string());
+        }
+
+        fn insert_rest(&self, _: &str, rest: &str) {
+            if !rest.is_empty() {
+                self.0.lock().unwrap().push(rest.to_string());
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct EventLog(Mutex<Vec<(u64, PipelineEvent)>>);
+
+    impl PipelineObserver for EventLog {
+        fn on_event(&self, cycle: u64, event: PipelineEvent) {
+            self.0.lock().unwrap().push((cycle, event));
+        }
+    }
+
+    struct Harness {
+        pipeline: Pipeline,
+        sink: Arc<FakeSink>,
+        events: Arc<EventLog>,
+    }
+
+    impl Harness {
+        fn events(&self) -> Vec<PipelineEvent> {
+            self.events
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(_, e)| e)
+                .filter(|e| {
+                    !matches!(
+                        e,
+                        PipelineEvent::Spectrum { .. } | PipelineEvent::Pending(_)
+                    )
+                })
+                .cloned()
+                .collect()
+        }
+
+        fn pending_counts(&self) -> Vec<(u64, usize)> {
+            self.events
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|(cycle, e)| match e {
+                    PipelineEvent::Pending(n) => Some((*cycle, *n)),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn inserted(&self) -> Vec<String> {
+            self.sink.0.lock().unwrap().clone()
+        }
+    }
+
+    fn transcript(text: &str) -> Result<Transcript> {
+        Ok(Transcript {
+            text: text.to_string(),
+            backend: "cpu".into(),
+            failed_backends: Vec::new(),
+            segments: Vec::new(),
+        })
+    }
+
+    fn harness(
+        has_model: bool,
+        audio: Result<Vec<f32>>,
+        stt: Result<Transcript>,
+        pp: fn(&str) -> Result<Option<String>>,
+    ) -> Harness {
+        let sink = Arc::new(FakeSink::default());
+        let events = Arc::new(EventLog::default());
+        let active_mode = Arc::new(AtomicU8::new(hotkey::MODE_TRANSCRIBE));
+        let pipeline = Pipeline {
+            audio: Arc::new(FakeAudio(Mutex::new(Some(audio)), active_mode.clone())),
+            stt: Arc::new(FakeStt {
+                has_model,
+                result: Mutex::new(Some(stt)),
+            }),
+            postprocessor: Arc::new(FakePostprocessor(pp)),
+            sink: sink.clone(),
+            observer: events.clone(),
+            active_mode,
+            denoise: Arc::new(DenoiseStage::new(Denoise::Off, None)),
+            gain: Gain::Off,
+            max_recording: None,
+            progressive_insert: false,
+            archive: Arc::new(Archive::new(std::env::temp_dir(), false, 0, 0)),
+            is_recording: Arc::new(AtomicBool::new(false)),
+            cancel_requested: Arc::new(AtomicBool::new(false)),
+            cycles: AtomicU64::new(0),
+            pending: AtomicUsize::new(0),
+        };
+        Harness {
+            pipeline,
+            sink,
+            events,
+        }
+    }
+
+    fn one_second() -> Result<Vec<f32>> {
+        Ok(vec![0.1; 16_000])
+    }
+
+    fn no_pp(_: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    #[test]
+    fn happy_path_inserts_postprocessed_text() {
+        let h = harness(true, one_second(), transcript("привет мир"), |t| {
+            Ok(Some(format!("{}!", t)))
+        });
+
+        let outcome = h.pipeline.run_cycle();
+
+        assert_eq!(
+            outcome,
+            Outcome::Inserted {
+                chars: 11,
+                postprocess_failed: false
+            }
+        );
+        assert_eq!(h.inserted(), vec!["привет мир!"]);
+        assert_eq!(
+            h.events(),
+            vec![
+                PipelineEvent::CycleStarted,
+                PipelineEvent::Recording,
+                PipelineEvent::Transcribing,
+                PipelineEvent::Transcribed {
+                    chars: 10,
+                    seconds: 1.0,
+                    levels: Levels {
+                        rms: 0.1,
+                        peak: 0.1,
+                        clipped: 0
+                    },
+                    recording: None,
+                },
+                PipelineEvent::Postprocessing,
+                PipelineEvent::Inserted("привет мир!".into()),
+                PipelineEvent::Idle,
+            ]
+        );
+        assert!(!h.pipeline.is_recording.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn missing_model_stops_before_recording() {
+        let h = harness(false, one_second(), transcript("unused"), no_pp);
+
+        let outcome = h.pipeline.run_cycle();
+
+        assert_eq!(outcome, Outcome::NoModel);
+        assert!(outcome.needs_release());
+        assert_eq!(
+            h.events(),
+            vec![
+                PipelineEvent::CycleStarted,
+                PipelineEvent::Error(NO_MODEL_MESSAGE.into()),
+            ]
+        );
+        assert!(h.inserted().is_empty());
+        assert!(!h.pipeline.is_recording.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn short_tap_is_dropped_without_transcribing() {
+        let h = harness(true, Ok(vec![0.0; MIN_SAMPLES - 1]), transcript("x"), no_pp);
+
+        assert_eq!(h.pipeline.run_cycle(), Outcome::TooShort);
+        assert_eq!(h.events().last(), Some(&PipelineEvent::Idle));
+        assert!(!h.events().contains(&PipelineEvent::Transcribing));
+    }
+
+    #[test]
+    fn audio_failure_reports_and_waits_for_release() {
+        let h = harness(
+            true,
+            Err(anyhow::anyhow!("device unplugged")),
+            transcript("x"),
+            no_pp,
+        );
+
+        let outcome = h.pipeline.run_cycle();
+
+        assert_eq!(outcome, Outcome::AudioFailed);
+        assert!(outcome.needs_release());
+        assert_eq!(
+            h.events().last(),
+            Some(&PipelineEvent::Error(
+                "Audio error: device unplugged".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn empty_transcript_inserts_nothing() {
+        let h = harness(true, one_second(), transcript(""), no_pp);
+
+        assert_eq!(h.pipeline.run_cycle(), Outcome::Empty);
+        assert!(h.inserted().is_empty());
+        assert_eq!(h.events().last(), Some(&PipelineEvent::Idle));
+    }
+
+    #[test]
+    fn missing_model_file_during_transcribe_maps_to_download_hint() {
+        let h = harness(
+            true,
+            one_second(),
+            Err(anyhow::anyhow!("model file not found: \"x.bin\"")),
+            no_pp,
+        );
+
+        assert_eq!(h.pipeline.run_cycle(), Outcome::TranscribeFailed);
+        assert_eq!(
+            h.events().last(),
+            Some(&PipelineEvent::Error(NO_MODEL_MESSAGE.into()))
+        );
+    }
+
+    #[test]
+    fn postprocess_failure_inserts_raw_transcript() {
+        let h = harness(true, one_second(), transcript("raw text"), |_| {
+            anyhow::bail!("HTTP 500")
+        });
+
+        let outcome = h.pipeline.run_cycle();
+
+        assert_eq!(
+            outcome,
+            Outcome::Inserted {
+                chars: 8,
+                postprocess_failed: true
+            }
+        );
+        assert_eq!(h.inserted(), vec!["raw text"]);
+        assert_eq!(
+            h.events().last(),
+            Some(&PipelineEvent::Notice(
+                "Post-processing failed (HTTP 500) — raw text pasted".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn streamed_text_is_pasted_a_sentence_at_a_time() {
+        let mut h = harness(true, one_second(), transcript("raw"), no_pp);
+        h.pipeline.progressive_insert = true;
+        h.pipeline.postprocessor = Arc::new(StreamingPostprocessor {
+            pieces: vec![" Первое пред", "ложение. Второе", " предложение! Хво", "ст"],
+            fail: false,
+            cancel: None,
+        });
+
+        h.pipeline.run_cycle();
+
+        assert_eq!(
+            h.inserted(),
+            vec!["Первое предложение. ", "Второе предложение! ", "Хвост"]
+        );
+        assert!(h.events().contains(&PipelineEvent::Inserted(
+            "Первое предложение. Второе предложение! Хвост".into()
+        )));
+    }
+
+    #[test]
+    fn streamed_sentences_wait_while_the_hotkey_is_held() {
+        let mut h = harness(true, one_second(), transcript("raw"), no_pp);
+        h.pipeline.progressive_insert = true;
+        h.pipeline.postprocessor = Arc::new(StreamingPostprocessor {
+            pieces: vec!["One. ", "Two. ", "Three."],
+            fail: false,
+            cancel: None,
+        });
+        let job = h.pipeline.record_cycle().unwrap();
+        // The next dictation's hotkey goes down before the stream starts.
+        h.pipeline
+            .active_mode
+            .store(hotkey::MODE_TRANSCRIBE, Ordering::SeqCst);
+
+        std::thread::scope(|scope| {
+            let processing = scope.spawn(|| h.pipeline.process(job));
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(h.inserted().is_empty());
+
+            h.pipeline
+                .active_mode
+                .store(hotkey::MODE_IDLE, Ordering::SeqCst);
+            processing.join().unwrap();
+        });
+
+        assert_eq!(h.inserted(), vec!["One. Two. Three."]);
+    }
+
+    #[test]
+    fn streamed_text_waits_for_the_end_by_default() {
+        let mut h = harness(true, one_second(), transcript("raw"), no_pp);
+        h.pipeline.postprocessor = Arc::new(StreamingPostprocessor {
+            pieces: vec!["One. ", "Two."],
+            fail: false,
+            cancel: None,
+        });
+
+        h.pipeline.run_cycle();
+
+        assert_eq!(h.inserted(), vec!["One. Two."]);
+    }
+
+    #[test]
+    fn stream_failure_after_a_paste_keeps_what_was_pasted() {
+        let mut h = harness(true, one_second(), transcript("raw"), no_pp);
+        h.pipeline.progressive_insert = true;
+        h.pipeline.postprocessor = Arc::new(StreamingPostprocessor {
+            pieces: vec!["One. ", "Tw"],
+            fail: true,
+            cancel: None,
+        });
+
+        let outcome = h.pipeline.run_cycle();
+
+        assert_eq!(
+            outcome,
+            Outcome::Inserted {
+                chars: 5,
+                postprocess_failed: true
+            }
+        );
+        assert_eq!(h.inserted(), vec!["One. "]);
+        assert_eq!(
+            h.events().last(),
+            Some(&PipelineEvent::Notice(
+                "Post-processing stopped partway".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn cancel_mid_stream_drops_the_rest() {
+        let mut h = harness(true, one_second(), transcript("raw"), no_pp);
+        h.pipeline.postprocessor = Arc::new(StreamingPostprocessor {
+            pieces: vec!["One. ", "Two."],
+            fail: false,
+            cancel: Some(h.pipeline.cancel_requested.clone()),
+        });
+
+        assert_eq!(h.pipeline.run_cycle(), Outcome::Cancelled);
+        assert!(h.inserted().is_empty());
+        assert!(!h.pipeline.cancel_requested.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn sentence_ends_include_the_following_space() {
+        assert_eq!(sentence_end("Hi. There"), Some(4));
+        assert_eq!(sentence_end("a\nb"), Some(2));
+        assert_eq!(sentence_end("v1.2 is out"), None);
+        assert_eq!(sentence_end("Всё… да"), Some("Всё… ".len()));
+    }
+
+    #[test]
+    fn backend_fallback_ends_with_notice() {
+        let stt = Ok(Transcript {
+            text: "hi".into(),
+            backend: "cpu".into(),
+            failed_backends: vec!["cuda".into()],
+            segments: Vec::new(),
+        });
+        let h = harness(true, one_second(), stt, no_pp);
+
+        h.pipeline.run_cycle();
+
+        assert_eq!(
+            h.events().last(),
+            Some(&PipelineEvent::Notice("CUDA failed — used CPU".into()))
+        );
+    }
+
+    #[test]
+    fn cancel_during_recording_skips_transcription() {
+        let h = harness(true, one_second(), transcript("unused"), no_pp);
+
+        let step = h.pipeline.advance(Step::Start);
+        h.pipeline.cancel_requested.store(true, Ordering::SeqCst);
+        let step = h.pipeline.advance(step);
+
+        assert!(matches!(step, Step::Done(Outcome::Cancelled)));
+        assert!(!h.events().contains(&PipelineEvent::Transcribing));
+        assert_eq!(h.events().last(), Some(&PipelineEvent::Idle));
+    }
+
+    #[test]
+    fn cancel_during_transcription_inserts_nothing() {
+        let h = harness(true, one_second(), transcript("late"), no_pp);
+
+        let mut step = Step::Start;
+        while !matches!(step, Step::Insert { .. }) {
+            step = h.pipeline.advance(step);
+        }
+        h.pipeline.cancel_requested.store(true, Ordering::SeqCst);
+
+        assert!(matches!(
+            h.pipeline.advance(step),
+            Step::Done(Outcome::Cancelled)
+        ));
+        assert!(h.inserted().is_empty());
+        assert!(!h.pipeline.cancel_requested.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn stale_cancel_is_cleared_on_start() {
+        let h = harness(true, one_second(), transcript("kept"), no_pp);
+        h.pipeline.cancel_requested.store(true, Ordering::SeqCst);
+
+        h.pipeline.run_cycle();
+
+        assert_eq!(h.inserted(), vec!["kept"]);
+    }
+
+    #[test]
+    fn advance_moves_one_step_at_a_time() {
+        let h = harness(true, one_second(), transcript("a"), no_pp);
+
+        let step = h.pipeline.advance(Step::Start);
+        assert!(matches!(step, Step::Record { cycle: 1 }));
+        assert!(h.pipeline.is_recording.load(Ordering::SeqCst));
+
+        let step = h.pipeline.advance(step);
+        assert!(matches!(step, Step::Transcribe { ref samples, .. } if samples.len() == 16_000));
+        assert!(!h.pipeline.is_recording.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn gain_is_applied_after_metering() {
+        let mut h = harness(true, one_second(), transcript("a"), no_pp);
+        h.pipeline.gain = Gain::Peak;
+
+        let step = h.pipeline.advance(Step::Start);
+        let Step::Transcribe { samples, job } = h.pipeline.advance(step) else {
+            panic!("expected a transcribe step");
+        };
+
+        assert_eq!(job.levels.peak, 0.1);
+        assert!(samples.iter().all(|&s| s > 0.8));
+    }
+
+    #[test]
+    fn length_limit_stops_a_held_hotkey_with_a_countdown() {
+        let mut h = harness(true, one_second(), transcript("long"), no_pp);
+        h.pipeline.audio = Arc::new(HeldAudio);
+        h.pipeline.max_recording = Some(Duration::from_millis(50));
+
+        let outcome = h.pipeline.run_cycle();
+
+        assert!(matches!(outcome, Outcome::Inserted { .. }));
+        assert_eq!(
+            h.pipeline.active_mode.load(Ordering::SeqCst),
+            hotkey::MODE_IDLE
+        );
+        let warnings: Vec<u64> = h
+            .events
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(_, e)| match e {
+                PipelineEvent::LimitApproaching(secs) => Some(*secs),
+                _ => None,
+            })
+            .collect();
+        // One per second left; 50 ms rounds up to a single "1".
+        assert_eq!(warnings, vec![1]);
+    }
+
+    #[test]
+    fn long_recordings_are_transcribed_in_chunks() {
+        let chunk = (audio_file::CHUNK_SECS * TRANSCRIBE_SAMPLE_RATE) as usize;
+        let mut h = harness(
+            true,
+            Ok(vec![0.0; 2 * chunk + 8_000]),
+            transcript("x"),
+            no_pp,
+        );
+        h.pipeline.stt = Arc::new(LengthStt);
+
+        h.pipeline.run_cycle();
+
+        assert_eq!(h.inserted(), vec![format!("{} {} 8000", chunk, chunk)]);
+    }
+
+    #[test]
+    fn next_recording_starts_while_earlier_jobs_are_queued() {
+        let mut h = harness(true, one_second(), transcript("x"), no_pp);
+        h.pipeline.audio = Arc::new(QueuedAudio(Mutex::new(vec![16_000, 24_000, 32_000])));
+        h.pipeline.stt = Arc::new(LengthStt);
+
+        let first = h.pipeline.record_cycle().unwrap();
+        let second = h.pipeline.record_cycle().unwrap();
+        assert_eq!(h.pipeline.pending.load(Ordering::SeqCst), 2);
+
+        // The first job finishes during the third recording, but its paste
+        // waits for the hotkey to come up. A cancel meanwhile is meant for
+        // the recording, not for the job.
+        let third = h.pipeline.advance(Step::Start);
+        h.pipeline.cancel_requested.store(true, Ordering::SeqCst);
+        std::thread::scope(|scope| {
+            let job = scope.spawn(|| h.pipeline.process(first));
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!job.is_finished());
+            assert!(h.inserted().is_empty());
+
+            h.pipeline
+                .active_mode
+                .store(hotkey::MODE_IDLE, Ordering::SeqCst);
+            assert!(matches!(
+                h.pipeline.advance(third),
+                Step::Done(Outcome::Cancelled)
+            ));
+            assert!(matches!(job.join().unwrap(), Outcome::Inserted { .. }));
+        });
+        h.pipeline.process(second);
+
+        assert_eq!(h.inserted(), vec!["16000", "24000"]);
+        assert_eq!(h.pending_counts(), vec![(1, 1), (2, 2), (1, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn archived_recording_is_linked_to_its_transcripts() {
+        let dir = std::env::temp_dir().join(format!(
+            "flov-pipeline-archive-{}",
+            crate::archive::now_ms()
+        ));
+        let mut h = harness(true, one_second(), transcript("raw"), |t| {
+            Ok(Some(format!("{}!", t)))
+        });
+        h.pipeline.archive = Arc::new(Archive::new(dir.clone(), true, 0, 0));
+        h.pipeline.gain = Gain::Peak;
+
+        h.pipeline.run_cycle();
+
+        let entries = h.pipeline.archive.list();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.transcript.as_deref(), Some("raw"));
+        assert_eq!(entry.backend.as_deref(), Some("cpu"));
+        assert_eq!(entry.text.as_deref(), Some("raw!"));
+        // Saved as captured, before gain.
+        let samples = h.pipeline.archive.load_samples(&entry.id).unwrap();
+        assert!(samples.iter().all(|&s| (s - 0.1).abs() < 1e-3));
+        assert!(h.events().iter().any(|e| matches!(
+            e,
+            PipelineEvent::Transcribed { recording: Some(id), .. } if *id == entry.id
+        )));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn watchdog_resets_after_three_stuck_ticks() {
+        let mut w = StuckRecordingWatchdog::default();
+
+        assert!(!w.tick(true, hotkey::MODE_IDLE));
+        assert!(!w.tick(true, hotkey::MODE_IDLE));
+        assert!(w.tick(true, hotkey::MODE_IDLE));
+        // Counter starts over after a reset.
+        assert!(!w.tick(true, hotkey::MODE_IDLE));
+    }
+
+    #[test]
+    fn watchdog_ignores_healthy_states() {
+        let mut w = StuckRecordingWatchdog::default();
+
+        assert!(!w.tick(true, hotkey::MODE_IDLE));
+        assert!(!w.tick(true, hotkey::MODE_IDLE));
+        // Hotkey held while recording is normal and clears the streak.
+        assert!(!w.tick(true, hotkey::MODE_TRANSCRIBE));
+        assert!(!w.tick(true, hotkey::MODE_IDLE));
+        assert!(!w.tick(false, hotkey::MODE_IDLE));
+        assert!(!w.tick(true, hotkey::MODE_IDLE));
+    }
+}
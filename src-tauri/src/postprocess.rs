@@ -1,29 +1,354 @@
-use anyhow::{Context, Result};
-use std::time::Duration;
+// OpenRouter post-processing of a transcript.
+//
+// The completion is requested with `"stream": true` and read as
+// server-sent events, so the pipeline gets the text as it is written: the
+// pill can show the phase, finished sentences can be pasted early, and a
+// cancel takes effect between chunks instead of after the whole answer.
+//
+// Failures are classified (`PostprocessError`). Network trouble, timeouts,
+// 429 and 5xx are retried with jittered exponential backoff, or after the
+// server's `Retry-After`, within `Limits`; nothing is retried once text
+// has started streaming, since it may already be pasted. Requests that
+// keep failing open a circuit breaker, which skips OpenRouter for a while
+// so every dictation doesn't sit through the whole retry budget.
+//
+// `[openrouter] model` may list fallbacks: when a model is unknown,
+// overloaded or down, the next one gets the text, within the same time
+// budget. Token counts and cost are asked for in the stream and reported
+// for every attempt that got them, failed ones included, for the Stats
+// tab.
+
+use anyhow::Result;
+use std::io::BufRead;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::OpenRouterConfig;
+
+const ENDPOINT: &str = "https://openrouter.ai/api/v1/chat/completions";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Upper bound for one whole streamed answer. `Limits::request_timeout`
+/// only bounds the gaps in it.
+const STREAM_TIMEOUT: Duration = Duration::from_secs(300);
+/// First retry waits about this long, each next one twice as long.
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(8);
+/// Failed requests in a row (each after its retries) that open the
+/// circuit breaker.
+pub const BREAKER_THRESHOLD: u32 = 3;
+/// How long an open breaker skips OpenRouter.
+pub const BREAKER_COOLDOWN: Duration = Duration::from_secs(60);
 
 pub struct PostProcessor {
     api_key: String,
-    model: String,
+    models: Vec<String>,
     system_prompt: String,
+    limits: Limits,
+    breaker: CircuitBreaker,
+    endpoint: String,
     agent: ureq::Agent,
 }
 
+/// Time and retry budget of one post-processing call, from
+/// `[openrouter]` in flov.toml.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Waiting for the answer to start, and then for each next chunk of
+    /// it.
+    pub request_timeout: Duration,
+    /// All attempts together, waits between them included, until one
+    /// starts answering.
+    pub total_timeout: Duration,
+    /// Attempts after the first.
+    pub retries: u32,
+}
+
+impl Limits {
+    pub fn from_config(cfg: &OpenRouterConfig) -> Self {
+        Self {
+            request_timeout: Duration::from_secs(cfg.timeout_secs),
+            total_timeout: Duration::from_secs(cfg.total_timeout_secs),
+            retries: cfg.retries,
+        }
+    }
+}
+
+/// Why post-processing failed. `Display` is short enough for the pill;
+/// `detail` has the rest for the log.
+#[derive(Debug, PartialEq)]
+pub enum PostprocessError {
+    /// No connection: DNS, refused, reset, TLS.
+    Network(String),
+    Timeout,
+    /// Non-2xx answer. `retry_after` is the server's `Retry-After`.
+    Http {
+        status: u16,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    /// The stream broke off or ended before `[DONE]`, reported an error,
+    /// or had no text.
+    BadResponse(String),
+    /// Skipped: the circuit breaker is open.
+    CircuitOpen,
+    /// `on_delta` asked to stop.
+    Stopped,
+}
+
+impl PostprocessError {
+    /// Worth another attempt: the next one may well succeed. A rejected
+    /// key, unknown model or missing credits won't.
+    pub fn retryable(&self) -> bool {
+        match self {
+            PostprocessError::Network(_)
+            | PostprocessError::Timeout
+            | PostprocessError::BadResponse(_) => true,
+            PostprocessError::Http { status, .. } => {
+                matches!(status, 408 | 429 | 500..=599)
+            }
+            PostprocessError::CircuitOpen | PostprocessError::Stopped => false,
+        }
+    }
+
+    /// The model is the problem — unknown, overloaded or down — so the
+    /// next one in the fallback list may well answer. A bad key, missing
+    /// credits or no network fail every model alike.
+    pub fn model_specific(&self) -> bool {
+        match self {
+            PostprocessError::BadResponse(_) => true,
+            PostprocessError::Http { status, .. } => {
+                matches!(status, 400 | 404 | 408 | 429 | 500..=599)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn detail(&self) -> String {
+        match self {
+            PostprocessError::Network(msg) | PostprocessError::BadResponse(msg) => {
+                format!("{}: {}", self, msg)
+            }
+            PostprocessError::Http { body, .. } => format!("{}: {}", self, body),
+            _ => self.to_string(),
+        }
+    }
+
+    fn from_ureq(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Timeout(_) => PostprocessError::Timeout,
+            ureq::Error::Io(io) if io.kind() == std::io::ErrorKind::TimedOut => {
+                PostprocessError::Timeout
+            }
+            other => PostprocessError::Network(other.to_string()),
+        }
+    }
+
+    fn from_read(e: std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::TimedOut {
+            PostprocessError::Timeout
+        } else {
+            PostprocessError::BadResponse(format!("stream broke off: {}", e))
+        }
+    }
+}
+
+impl std::fmt::Display for PostprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostprocessError::Network(_) => f.write_str("network error"),
+            PostprocessError::Timeout => f.write_str("timed out"),
+            PostprocessError::Http { status: 429, .. } => f.write_str("rate limited"),
+            PostprocessError::Http { status, .. } => write!(f, "HTTP {}", status),
+            PostprocessError::BadResponse(_) => f.write_str("bad response"),
+            PostprocessError::CircuitOpen => f.write_str("paused after repeated failures"),
+            PostprocessError::Stopped => f.write_str("stopped"),
+        }
+    }
+}
+
+impl std::error::Error for PostprocessError {}
+
+/// One `data:` line of the completion stream.
+#[derive(Debug, PartialEq)]
+pub enum StreamEvent {
+    Delta(String),
+    /// OpenRouter sends usage in a chunk of its own after the last text.
+    Usage(Usage),
+    Done,
+}
+
+/// Tokens billed for one completion.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// In USD, when OpenRouter reports it.
+    pub cost: Option<f64>,
+}
+
+/// A finished post-processing call.
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    /// The model from the fallback list that answered.
+    pub model: String,
+}
+
 impl PostProcessor {
-    pub fn new(api_key: String, model: String, system_prompt: String) -> Self {
+    /// `models` is the fallback list, tried in order; it must not be
+    /// empty.
+    pub fn new(
+        api_key: String,
+        models: Vec<String>,
+        system_prompt: String,
+        limits: Limits,
+    ) -> Self {
         let config = ureq::Agent::config_builder()
-            .timeout_global(Some(Duration::from_secs(120)))
+            .timeout_connect(Some(CONNECT_TIMEOUT))
+            // 429 / 5xx are read here, Retry-After included.
+            .http_status_as_error(false)
             .build();
         Self {
             api_key,
-            model,
+            models,
             system_prompt,
+            limits,
+            breaker: CircuitBreaker::default(),
+            endpoint: ENDPOINT.to_string(),
             agent: config.into(),
         }
     }
 
-    pub fn process(&self, text: &str) -> Result<String> {
+    /// Sends `text` through the models in turn until one answers, and
+    /// returns its trimmed answer. Each piece of the answer goes to
+    /// `on_delta` as it arrives; when that returns false the stream is
+    /// dropped and this fails with `PostprocessError::Stopped`. Every
+    /// attempt that reported token usage goes to `on_usage` with its
+    /// model, failed and abandoned ones too.
+    pub fn process(
+        &self,
+        text: &str,
+        on_delta: &mut dyn FnMut(&str) -> bool,
+        on_usage: &mut dyn FnMut(&str, &Usage),
+    ) -> Result<Completion> {
+        if let Some(left) = self.breaker.open_for(Instant::now()) {
+            tracing::warn!("openrouter: skipped, circuit open for another {:?}", left);
+            return Err(PostprocessError::CircuitOpen.into());
+        }
+        let started = Instant::now();
+        let deadline = started + self.limits.total_timeout;
+        let mut streamed = false;
+        let mut models = self.models.iter().peekable();
+        let err = loop {
+            let Some(model) = models.next() else {
+                break PostprocessError::BadResponse("no model configured".into());
+            };
+            let result =
+                self.with_retries(model, text, deadline, &mut streamed, on_delta, on_usage);
+            let err = match result {
+                Ok(completion) => {
+                    self.breaker.record_success();
+                    return Ok(completion);
+                }
+                Err(PostprocessError::Stopped) => return Err(PostprocessError::Stopped.into()),
+                Err(e) => e,
+            };
+            match models.peek() {
+                Some(next) if err.model_specific() && !streamed && Instant::now() < deadline => {
+                    tracing::warn!(
+                        "openrouter: {} failed ({}), falling back to {}",
+                        model,
+                        err.detail(),
+                        next
+                    );
+                }
+                _ => break err,
+            }
+        };
+        tracing::error!(
+            "openrouter: failed in {:?}: {}",
+            started.elapsed(),
+            err.detail()
+        );
+        if self.breaker.record_failure(Instant::now()) {
+            tracing::warn!(
+                "openrouter: {} failures in a row, skipping it for {:?}",
+                BREAKER_THRESHOLD,
+                BREAKER_COOLDOWN
+            );
+        }
+        Err(err.into())
+    }
+
+    /// Asks one model, retrying transient failures until `deadline`.
+    /// `streamed` is set once any text has gone to `on_delta`.
+    fn with_retries(
+        &self,
+        model: &str,
+        text: &str,
+        deadline: Instant,
+        streamed: &mut bool,
+        on_delta: &mut dyn FnMut(&str) -> bool,
+        on_usage: &mut dyn FnMut(&str, &Usage),
+    ) -> Result<Completion, PostprocessError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let timeout = self
+                .limits
+                .request_timeout
+                .min(deadline.saturating_duration_since(Instant::now()));
+            let mut on_delta = |delta: &str| {
+                *streamed = true;
+                on_delta(delta)
+            };
+            let err = match self.attempt(model, text, timeout, &mut on_delta, on_usage) {
+                Ok(completion) => return Ok(completion),
+                Err(e) => e,
+            };
+            let retry_after = match &err {
+                PostprocessError::Http { retry_after, .. } => *retry_after,
+                _ => None,
+            };
+            let delay = backoff(attempt, retry_after, jitter());
+            // Pasted text can't be taken back, so a stream that already
+            // produced some isn't started over.
+            let give_up = !err.retryable()
+                || *streamed
+                || attempt > self.limits.retries
+                || Instant::now() + delay >= deadline;
+            if give_up {
+                tracing::warn!(
+                    "openrouter: {} gave up after {} attempt(s): {}",
+                    model,
+                    attempt,
+                    err.detail()
+                );
+                return Err(err);
+            }
+            tracing::warn!(
+                "openrouter: attempt {} failed ({}), retrying in {:?}",
+                attempt,
+                err.detail(),
+                delay
+            );
+            std::thread::sleep(delay);
+        }
+    }
+
+    fn attempt(
+        &self,
+        model: &str,
+        text: &str,
+        timeout: Duration,
+        on_delta: &mut dyn FnMut(&str) -> bool,
+        on_usage: &mut dyn FnMut(&str, &Usage),
+    ) -> Result<Completion, PostprocessError> {
         let body = serde_json::json!({
-            "model": self.model,
+            "model": model,
+            "stream": true,
+            "usage": { "include": true },
             "messages": [
                 { "role": "system", "content": self.system_prompt },
                 { "role": "user",   "content": text }
@@ -41,29 +366,32 @@ impl PostProcessor {
             .collect::<String>();
         tracing::info!(
             "openrouter: POST chat/completions model={} key=…{} chars={}",
-            self.model,
+            model,
             key_tail,
             text.chars().count()
         );
 
-        let started = std::time::Instant::now();
-        let mut resp = match self
+        let started = Instant::now();
+        let mut resp = self
             .agent
-            .post("https://openrouter.ai/api/v1/chat/completions")
+            .post(&self.endpoint)
+            .config()
+            .timeout_recv_response(Some(timeout))
+            .timeout_recv_body(Some(STREAM_TIMEOUT))
+            .build()
             .header("Authorization", &format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .send_json(&body)
-        {
-            Ok(r) => r,
-            Err(e) => {
-                tracing::error!("openrouter: send failed: {:?}", e);
-                return Err(e).context("Failed to send request to OpenRouter");
-            }
-        };
+            .map_err(PostprocessError::from_ureq)?;
 
         let status = resp.status();
         if !status.is_success() {
-            let body_text = resp
+            let retry_after = resp
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_retry_after);
+            let body = resp
                 .body_mut()
                 .read_to_string()
                 .unwrap_or_else(|_| "<no body>".into());
@@ -71,31 +399,614 @@ impl PostProcessor {
                 "openrouter: HTTP {} after {:?} body={}",
                 status.as_u16(),
                 started.elapsed(),
-                body_text
-            );
-            anyhow::bail!(
-                "OpenRouter returned HTTP {}: {}",
-                status.as_u16(),
-                body_text
+                body
             );
+            return Err(PostprocessError::Http {
+                status: status.as_u16(),
+                body,
+                retry_after,
+            });
         }
 
-        let response: serde_json::Value = resp
-            .body_mut()
-            .read_json()
-            .context("Failed to parse OpenRouter response")?;
+        let mut received = Received::default();
+        let result = self.receive(resp.into_body(), started, on_delta, &mut received);
+        // Billed whatever became of the answer: a stream that broke off or
+        // was dropped after its usage chunk still cost the tokens.
+        if let Some(usage) = &received.usage {
+            on_usage(model, usage);
+        }
+        result?;
 
-        let content = response["choices"][0]["message"]["content"]
-            .as_str()
-            .context("No content in OpenRouter response")?
-            .trim()
-            .to_string();
+        let content = received.content.trim().to_string();
+        if content.is_empty() {
+            return Err(PostprocessError::BadResponse(
+                "no content in OpenRouter response".into(),
+            ));
+        }
         tracing::info!(
-            "openrouter: ok in {:?} ({} chars in → {} chars out)",
+            "openrouter: {} ok in {:?}, first text after {:?} ({} chars in → {} chars out, {:?})",
+            model,
             started.elapsed(),
+            received.first_delta.unwrap_or_default(),
             text.chars().count(),
-            content.chars().count()
+            content.chars().count(),
+            received.usage
+        );
+        Ok(Completion {
+            text: content,
+            model: model.to_string(),
+        })
+    }
+
+    /// Reads the event stream into `received` up to `[DONE]`.
+    fn receive(
+        &self,
+        body: ureq::Body,
+        started: Instant,
+        on_delta: &mut dyn FnMut(&str) -> bool,
+        received: &mut Received,
+    ) -> Result<(), PostprocessError> {
+        let lines = read_lines(body);
+        loop {
+            let line = match lines.recv_timeout(self.limits.request_timeout) {
+                Ok(line) => line.map_err(PostprocessError::from_read)?,
+                // A connection closed early reads as a clean EOF; only
+                // the terminator says the answer is whole.
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(PostprocessError::BadResponse(
+                        "stream ended before [DONE]".into(),
+                    ))
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    tracing::warn!(
+                        "openrouter: stream stalled for {:?} after {:?}",
+                        self.limits.request_timeout,
+                        started.elapsed()
+                    );
+                    return Err(PostprocessError::Timeout);
+                }
+            };
+            match parse_stream_line(&line)? {
+                Some(StreamEvent::Delta(delta)) => {
+                    received
+                        .first_delta
+                        .get_or_insert_with(|| started.elapsed());
+                    received.content.push_str(&delta);
+                    if !on_delta(&delta) {
+                        tracing::info!("openrouter: stream dropped after {:?}", started.elapsed());
+                        return Err(PostprocessError::Stopped);
+                    }
+                }
+                Some(StreamEvent::Usage(usage)) => received.usage = Some(usage),
+                Some(StreamEvent::Done) => return Ok(()),
+                None => {}
+            }
+        }
+    }
+}
+
+/// What a stream delivered before it ended, one way or another.
+#[derive(Default)]
+struct Received {
+    content: String,
+    usage: Option<Usage>,
+    first_delta: Option<Duration>,
+}
+
+/// The lines of `body`, read on a thread of their own so the stream can
+/// be given up on after a gap without blocking in a read. An abandoned
+/// reader ends with the connection, or at STREAM_TIMEOUT.
+fn read_lines(body: ureq::Body) -> mpsc::Receiver<std::io::Result<String>> {
+    let (tx, rx) = mpsc::channel();
+    let reader = std::io::BufReader::new(body.into_reader());
+    std::thread::Builder::new()
+        .name("flov-openrouter-stream".into())
+        .spawn(move || {
+            for line in reader.lines() {
+                let failed = line.is_err();
+                if tx.send(line).is_err() || failed {
+                    return;
+                }
+            }
+        })
+        .expect("spawn stream reader thread");
+    rx
+}
+
+/// Reads one line of the event stream. Blank lines, `:` comments (the
+/// keep-alives OpenRouter sends while the model is queued) and chunks
+/// with neither text nor usage are `None`; an error reported mid-stream
+/// is `Err`.
+pub fn parse_stream_line(line: &str) -> Result<Option<StreamEvent>, PostprocessError> {
+    let Some(data) = line.strip_prefix("data:") else {
+        return Ok(None);
+    };
+    let data = data.trim();
+    if data == "[DONE]" {
+        return Ok(Some(StreamEvent::Done));
+    }
+    let chunk: serde_json::Value = serde_json::from_str(data)
+        .map_err(|e| PostprocessError::BadResponse(format!("unparsable chunk: {}", e)))?;
+    if let Some(error) = chunk.get("error") {
+        return Err(PostprocessError::BadResponse(
+            error["message"]
+                .as_str()
+                .unwrap_or("unknown stream error")
+                .to_string(),
+        ));
+    }
+    if let Some(delta) = chunk["choices"][0]["delta"]["content"]
+        .as_str()
+        .filter(|s| !s.is_empty())
+    {
+        return Ok(Some(StreamEvent::Delta(delta.to_string())));
+    }
+    let usage = &chunk["usage"];
+    if !usage.is_object() {
+        return Ok(None);
+    }
+    Ok(Some(StreamEvent::Usage(Usage {
+        prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        cost: usage["cost"]
+            .as_f64()
+            .filter(|c| c.is_finite() && *c >= 0.0),
+    })))
+}
+
+/// `Retry-After` in its seconds form. The HTTP-date form is left to the
+/// regular backoff.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let secs: f64 = value.trim().parse().ok()?;
+    (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+/// Wait before retrying after failed attempt `attempt` (1-based): the
+/// server's `retry_after` if it sent one, otherwise doubling from
+/// BACKOFF_BASE up to BACKOFF_MAX, spread over its upper half by `jitter`
+/// (0..1) so clients that failed together don't retry together.
+pub fn backoff(attempt: u32, retry_after: Option<Duration>, jitter: f64) -> Duration {
+    if let Some(wait) = retry_after {
+        return wait;
+    }
+    let exp = BACKOFF_BASE
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(BACKOFF_MAX);
+    exp.mul_f64(0.5 + 0.5 * jitter.clamp(0.0, 1.0))
+}
+
+fn jitter() -> f64 {
+    let mut bytes = [0u8; 4];
+    match getrandom::getrandom(&mut bytes) {
+        Ok(()) => u32::from_le_bytes(bytes) as f64 / u32::MAX as f64,
+        Err(_) => 0.5,
+    }
+}
+
+/// Opens after BREAKER_THRESHOLD failed requests in a row and then
+/// rejects calls for BREAKER_COOLDOWN. The first call after that goes
+/// through; if it fails too, the breaker opens again straight away.
+#[derive(Default)]
+pub struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Time left while the breaker is open.
+    pub fn open_for(&self, now: Instant) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        state
+            .open_until
+            .filter(|&until| until > now)
+            .map(|until| until - now)
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    /// Returns true when this failure opened the breaker.
+    pub fn record_failure(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures < BREAKER_THRESHOLD {
+            return false;
+        }
+        state.open_until = Some(now + BREAKER_COOLDOWN);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_lines_yield_text_and_end() {
+        assert_eq!(
+            parse_stream_line(r#"data: {"choices":[{"delta":{"content":"При"}}]}"#).unwrap(),
+            Some(StreamEvent::Delta("При".into()))
+        );
+        assert_eq!(
+            parse_stream_line("data: [DONE]").unwrap(),
+            Some(StreamEvent::Done)
+        );
+    }
+
+    #[test]
+    fn keepalives_and_empty_chunks_are_skipped() {
+        assert_eq!(parse_stream_line(": OPENROUTER PROCESSING").unwrap(), None);
+        assert_eq!(parse_stream_line("").unwrap(), None);
+        assert_eq!(
+            parse_stream_line(r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn usage_chunk_is_read() {
+        assert_eq!(
+            parse_stream_line(
+                r#"data: {"choices":[],"usage":{"prompt_tokens":412,"completion_tokens":96,"total_tokens":508,"cost":0.000119}}"#
+            )
+            .unwrap(),
+            Some(StreamEvent::Usage(Usage {
+                prompt_tokens: 412,
+                completion_tokens: 96,
+                cost: Some(0.000119),
+            }))
+        );
+        assert_eq!(
+            parse_stream_line(r#"data: {"usage":{"prompt_tokens":5,"completion_tokens":1}}"#)
+                .unwrap(),
+            Some(StreamEvent::Usage(Usage {
+                prompt_tokens: 5,
+                completion_tokens: 1,
+                cost: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn mid_stream_error_fails() {
+        let err = parse_stream_line(
+            r#"data: {"error":{"message":"Provider disconnected"},"choices":[{"finish_reason":"error"}]}"#,
+        )
+        .unwrap_err();
+
+        assert!(err.detail().contains("Provider disconnected"));
+        assert!(err.retryable());
+    }
+
+    #[test]
+    fn only_transient_statuses_are_retried() {
+        let http = |status| PostprocessError::Http {
+            status,
+            body: String::new(),
+            retry_after: None,
+        };
+
+        assert!(http(429).retryable());
+        assert!(http(503).retryable());
+        assert!(!http(401).retryable());
+        assert!(!http(402).retryable());
+        assert!(http(404).model_specific());
+        assert!(!http(401).model_specific());
+        assert!(!PostprocessError::Timeout.model_specific());
+        assert_eq!(http(429).to_string(), "rate limited");
+        assert_eq!(http(502).to_string(), "HTTP 502");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_and_honours_retry_after() {
+        assert_eq!(backoff(1, None, 1.0), BACKOFF_BASE);
+        assert_eq!(backoff(3, None, 1.0), BACKOFF_BASE * 4);
+        assert_eq!(backoff(3, None, 0.0), BACKOFF_BASE * 2);
+        assert_eq!(backoff(30, None, 1.0), BACKOFF_MAX);
+        assert_eq!(
+            backoff(1, Some(Duration::from_secs(20)), 0.3),
+            Duration::from_secs(20)
+        );
+    }
+
+    #[test]
+    fn retry_after_seconds_parse() {
+        assert_eq!(parse_retry_after(" 7 "), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+    }
+
+    #[test]
+    fn breaker_opens_after_repeated_failures_and_retries_after_cooldown() {
+        let breaker = CircuitBreaker::default();
+        let now = Instant::now();
+        for _ in 1..BREAKER_THRESHOLD {
+            assert!(!breaker.record_failure(now));
+        }
+        assert_eq!(breaker.open_for(now), None);
+
+        assert!(breaker.record_failure(now));
+        assert_eq!(breaker.open_for(now), Some(BREAKER_COOLDOWN));
+
+        let later = now + BREAKER_COOLDOWN;
+        assert_eq!(breaker.open_for(later), None);
+        // One more failure after the cooldown reopens it at once.
+        assert!(breaker.record_failure(later));
+        breaker.record_success();
+        assert_eq!(breaker.open_for(later), None);
+        assert!(!breaker.record_failure(later));
+    }
+
+    /// Status, headers and body of a canned answer.
+    type Canned = (u16, Vec<(&'static str, &'static str)>, &'static str);
+
+    /// Serves `responses` in order to successive requests.
+    fn server(responses: Vec<Canned>) -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", server.server_addr().to_ip().unwrap());
+        std::thread::spawn(move || {
+            for (status, headers, body) in responses {
+                let request = server.recv().unwrap();
+                let mut response = tiny_http::Response::from_string(body).with_status_code(status);
+                for (name, value) in headers {
+                    response.add_header(
+                        tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap(),
+                    );
+                }
+                let _ = request.respond(response);
+            }
+        });
+        url
+    }
+
+    /// Answers one request with an event stream written a piece at a
+    /// time, each after its wait. tiny_http buffers chunked bodies, so
+    /// this speaks HTTP by hand.
+    fn slow_server(pieces: Vec<(Duration, &'static str)>) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                request.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            request.read_exact(&mut vec![0; length]).unwrap();
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n",
+            );
+            for (wait, piece) in pieces {
+                std::thread::sleep(wait);
+                if stream.write_all(piece.as_bytes()).is_err() {
+                    return;
+                }
+            }
+        });
+        url
+    }
+
+    fn processor(endpoint: String, models: &[&str], retries: u32) -> PostProcessor {
+        PostProcessor {
+            endpoint,
+            ..PostProcessor::new(
+                "sk-test".into(),
+                models.iter().map(|m| m.to_string()).collect(),
+                "p".into(),
+                Limits {
+                    request_timeout: Duration::from_secs(5),
+                    total_timeout: Duration::from_secs(10),
+                    retries,
+                },
+            )
+        }
+    }
+
+    const STREAM: &str =
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Done.\"}}]}\n\ndata: [DONE]\n\n";
+
+    #[test]
+    fn rate_limit_is_retried_after_retry_after() {
+        let url = server(vec![
+            (429, vec![("Retry-After", "0")], "slow down"),
+            (200, vec![("Content-Type", "text/event-stream")], STREAM),
+        ]);
+
+        let done = processor(url, &["m"], 2)
+            .process("raw", &mut |_| true, &mut |_, _| {})
+            .unwrap();
+
+        assert_eq!(done.text, "Done.");
+    }
+
+    #[test]
+    fn long_stream_outlasts_the_request_timeout() {
+        let gap = Duration::from_millis(150);
+        let chunk = "data: {\"choices\":[{\"delta\":{\"content\":\"la \"}}]}\n\n";
+        let url = slow_server(vec![
+            (gap, chunk),
+            (gap, chunk),
+            (gap, chunk),
+            (gap, chunk),
+            (gap, "data: [DONE]\n\n"),
+        ]);
+        let mut pp = processor(url, &["m"], 0);
+        pp.limits.request_timeout = Duration::from_millis(400);
+        pp.limits.total_timeout = Duration::from_millis(500);
+
+        let done = pp.process("raw", &mut |_| true, &mut |_, _| {}).unwrap();
+
+        assert_eq!(done.text, "la la la la");
+    }
+
+    #[test]
+    fn stalled_stream_times_out() {
+        let url = slow_server(vec![
+            (
+                Duration::ZERO,
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Half\"}}]}\n\n",
+            ),
+            (Duration::from_secs(2), "data: [DONE]\n\n"),
+        ]);
+        let mut pp = processor(url, &["m"], 0);
+        pp.limits.request_timeout = Duration::from_millis(200);
+        let started = Instant::now();
+
+        let err = pp
+            .process("raw", &mut |_| true, &mut |_, _| {})
+            .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<PostprocessError>(),
+            Some(&PostprocessError::Timeout)
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn stream_cut_off_before_done_fails() {
+        let url = server(vec![(
+            200,
+            vec![("Content-Type", "text/event-stream")],
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Half a sent\"}}]}\n\n",
+        )]);
+        let mut streamed = String::new();
+
+        let err = processor(url, &["m"], 2)
+            .process(
+                "raw",
+                &mut |delta| {
+                    streamed.push_str(delta);
+                    true
+                },
+                &mut |_, _| {},
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<PostprocessError>(),
+            Some(&PostprocessError::BadResponse(
+                "stream ended before [DONE]".into()
+            ))
+        );
+        // Not retried: the start already went out.
+        assert_eq!(streamed, "Half a sent");
+    }
+
+    #[test]
+    fn unknown_model_falls_back_to_the_next_and_reports_usage() {
+        let url = server(vec![
+            (404, vec![], "No endpoints found for gone/model"),
+            (
+                200,
+                vec![("Content-Type", "text/event-stream")],
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Done.\"}}]}\n\n\
+                 data: {\"choices\":[],\"usage\":{\"prompt_tokens\":10,\"completion_tokens\":2,\"cost\":0.5}}\n\n\
+                 data: [DONE]\n\n",
+            ),
+        ]);
+        let mut usages = Vec::new();
+
+        let done = processor(url, &["gone/model", "backup/model"], 0)
+            .process("raw", &mut |_| true, &mut |model, usage| {
+                usages.push((model.to_string(), *usage))
+            })
+            .unwrap();
+
+        assert_eq!(
+            done,
+            Completion {
+                text: "Done.".into(),
+                model: "backup/model".into(),
+            }
+        );
+        assert_eq!(
+            usages,
+            vec![(
+                "backup/model".to_string(),
+                Usage {
+                    prompt_tokens: 10,
+                    completion_tokens: 2,
+                    cost: Some(0.5),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn usage_of_failed_attempts_is_reported_too() {
+        let url = server(vec![
+            // Billed, then cut off before any text.
+            (
+                200,
+                vec![("Content-Type", "text/event-stream")],
+                "data: {\"usage\":{\"prompt_tokens\":10,\"completion_tokens\":0}}\n\n",
+            ),
+            (
+                200,
+                vec![("Content-Type", "text/event-stream")],
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Half.\"}}]}\n\n\
+                 data: {\"usage\":{\"prompt_tokens\":10,\"completion_tokens\":3}}\n\n",
+            ),
+        ]);
+        let mut usages = Vec::new();
+
+        // The first model's answer is abandoned for the second, whose
+        // stream then breaks off after its usage chunk.
+        let err = processor(url, &["flaky", "backup"], 0)
+            .process("raw", &mut |_| true, &mut |model, usage| {
+                usages.push((model.to_string(), usage.completion_tokens))
+            })
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<PostprocessError>(),
+            Some(PostprocessError::BadResponse(_))
+        ));
+        assert_eq!(
+            usages,
+            vec![("flaky".to_string(), 0), ("backup".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn rejected_key_is_not_retried_and_trips_the_breaker() {
+        let url = server(vec![(401, vec![], "bad key"); BREAKER_THRESHOLD as usize]);
+        // A rejected key fails every model, so the backup isn't asked.
+        let pp = processor(url, &["m", "backup"], 2);
+
+        for _ in 0..BREAKER_THRESHOLD {
+            let err = pp
+                .process("raw", &mut |_| true, &mut |_, _| {})
+                .unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<PostprocessError>()
+                    .map(|e| e.to_string()),
+                Some("HTTP 401".into())
+            );
+        }
+        let err = pp
+            .process("raw", &mut |_| true, &mut |_, _| {})
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PostprocessError>(),
+            Some(&PostprocessError::CircuitOpen)
         );
-        Ok(content)
     }
 }
@@ -1,10 +1,22 @@
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+// Tauri side of the push-to-talk cycle: wires the real microphone,
+// sidecar, OpenRouter and paste into `pipeline::Pipeline`, and runs the
+// background threads (hotkey loop, job worker, state watchdog, webview
+// reloader).
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
 
 use tauri::{Emitter, Manager};
 
-use crate::{audio, hotkey, input, postprocess, stats, transcribe, tray, ui};
+use crate::pipeline::{
+    AudioSource, Pipeline, PipelineEvent, PipelineObserver, SpeechToText, Step,
+    StuckRecordingWatchdog, TextPostprocessor, TextSink,
+};
+use crate::{
+    archive, audio, config, control, denoise, hotkey, input, levels, postprocess, sinks, stats,
+    transcribe, tray, ui,
+};
 
 pub struct RecordingRuntime {
     pub app: tauri::AppHandle,
@@ -15,21 +27,41 @@ pub struct RecordingRuntime {
     pub post_processor: Arc<Mutex<Option<Arc<postprocess::PostProcessor>>>>,
     pub postprocess_enabled: Arc<AtomicBool>,
     pub stats: Arc<stats::Stats>,
+    pub control: Arc<control::ControlHub>,
+    pub cancel_requested: Arc<AtomicBool>,
+    pub output: config::OutputConfig,
     pub sample_rate: u32,
+    pub denoise: Arc<denoise::DenoiseStage>,
+    pub gain: levels::Gain,
+    pub max_recording: Option<std::time::Duration>,
+    pub progressive_insert: bool,
+    pub archive: Arc<archive::Archive>,
 }
 
+/// Held by the hotkey loop while recording and by the job worker while
+/// processing; the two overlap, so the flag drops with the last guard.
 struct RecordingCycleGuard;
 
+static CYCLE_GUARDS: Mutex<usize> = Mutex::new(0);
+
 impl RecordingCycleGuard {
     fn start() -> Self {
-        ui::set_recording_cycle_active(true);
+        let mut guards = CYCLE_GUARDS.lock().unwrap();
+        if *guards == 0 {
+            ui::set_recording_cycle_active(true);
+        }
+        *guards += 1;
         Self
     }
 }
 
 impl Drop for RecordingCycleGuard {
     fn drop(&mut self) {
-        ui::set_recording_cycle_active(false);
+        let mut guards = CYCLE_GUARDS.lock().unwrap();
+        *guards -= 1;
+        if *guards == 0 {
+            ui::set_recording_cycle_active(false);
+        }
     }
 }
 
@@ -44,28 +76,73 @@ pub fn spawn_state_watchdog(is_recording: Arc<AtomicBool>, active_mode: Arc<Atom
     std::thread::Builder::new()
         .name("flov-state-watchdog".into())
         .spawn(move || {
-            let mut stuck_ticks = 0u8;
+            let mut watchdog = StuckRecordingWatchdog::default();
             loop {
                 std::thread::sleep(std::time::Duration::from_secs(2));
                 let recording = is_recording.load(Ordering::SeqCst);
                 let mode = active_mode.load(Ordering::SeqCst);
-                if recording && mode == hotkey::MODE_IDLE {
-                    stuck_ticks = stuck_ticks.saturating_add(1);
-                    if stuck_ticks >= 3 {
-                        tracing::warn!(
-                            "watchdog: is_recording stuck true with mode=IDLE for ~6s, resetting"
-                        );
-                        is_recording.store(false, Ordering::SeqCst);
-                        stuck_ticks = 0;
-                    }
-                } else {
-                    stuck_ticks = 0;
+                if watchdog.tick(recording, mode) {
+                    tracing::warn!(
+                        "watchdog: is_recording stuck true with mode=IDLE for ~6s, resetting"
+                    );
+                    is_recording.store(false, Ordering::SeqCst);
                 }
             }
         })
         .expect("spawn watchdog thread");
 }
 
+/// Polls the system default input so a default switch or an unplugged
+/// device is picked up without a restart. Only the default's name is read
+/// each tick; the full device list (which opens every device on ALSA and
+/// WASAPI) is enumerated only while the saved device is missing, to
+/// notice it coming back. Reopens never overlap a recording; the recorder
+/// handles a device lost mid-recording itself.
+pub fn spawn_device_watcher(
+    app: tauri::AppHandle,
+    recorder: Arc<audio::AudioRecorder>,
+    is_recording: Arc<AtomicBool>,
+) {
+    const TICK: std::time::Duration = std::time::Duration::from_secs(3);
+    // Ticks between enumerations while fallen back.
+    const LIST_EVERY: u32 = 5;
+    std::thread::Builder::new()
+        .name("flov-device-watcher".into())
+        .spawn(move || {
+            let mut last_default = audio::default_input_name();
+            let mut tick = 0u32;
+            loop {
+                std::thread::sleep(TICK);
+                tick = tick.wrapping_add(1);
+                let default = audio::default_input_name();
+                let changed = default != last_default
+                    || recorder.preroll_lost()
+                    || (recorder.fell_back()
+                        && tick.is_multiple_of(LIST_EVERY)
+                        && audio::list_input_devices()
+                            .iter()
+                            .any(|name| recorder.prefers(name)));
+                if !changed {
+                    continue;
+                }
+                match recorder.reopen_when_idle(|| !is_recording.load(Ordering::SeqCst)) {
+                    // Recording: look again next tick.
+                    Ok(None) => continue,
+                    Ok(Some(_)) => {}
+                    Err(e) => tracing::warn!("no usable input after device change: {:#}", e),
+                }
+                tracing::info!(
+                    "input device changed, now '{}' (default {:?})",
+                    recorder.device_name(),
+                    default
+                );
+                last_default = default;
+                let _ = app.emit("audio-inputs-changed", ());
+            }
+        })
+        .expect("spawn device watcher thread");
+}
+
 pub fn spawn_webview_reloader(
     app: tauri::AppHandle,
     is_recording: Arc<AtomicBool>,
@@ -138,132 +215,337 @@ fn recording_loop(runtime: RecordingRuntime) {
         post_processor,
         postprocess_enabled,
         stats,
+        control,
+        cancel_requested,
+        output,
         sample_rate,
+        denoise,
+        gain,
+        max_recording,
+        progressive_insert,
+        archive,
     } = runtime;
 
+    let pipeline = Arc::new(Pipeline {
+        audio: Arc::new(Microphone {
+            recorder,
+            sample_rate,
+        }),
+        stt: transcriber,
+        postprocessor: Arc::new(SharedPostprocessor {
+            app: app.clone(),
+            enabled: postprocess_enabled,
+            slot: post_processor,
+            stats: stats.clone(),
+        }),
+        sink: Arc::new(sinks::Outputs::new(
+            output
+                .paste
+                .then(|| Arc::new(PasteSink) as Arc<dyn TextSink>),
+            sinks::build(&output.sinks),
+        )),
+        observer: Arc::new(TauriObserver {
+            app,
+            stats,
+            control,
+            recording: AtomicU64::new(0),
+            pending: AtomicUsize::new(0),
+            deferred: Mutex::new(None),
+        }),
+        active_mode: active_mode.clone(),
+        denoise,
+        gain,
+        max_recording,
+        progressive_insert,
+        archive,
+        is_recording,
+        cancel_requested,
+        cycles: AtomicU64::new(0),
+        pending: AtomicUsize::new(0),
+    });
+    let jobs = spawn_job_worker(pipeline.clone());
+
     loop {
         while active_mode.load(Ordering::SeqCst) == hotkey::MODE_IDLE {
             std::thread::sleep(std::time::Duration::from_millis(10));
         }
 
-        is_recording.store(true, Ordering::SeqCst);
         let _cycle_guard = RecordingCycleGuard::start();
-        let cycle_start = Instant::now();
-        tracing::info!("recording start");
-
-        let model_present = transcriber.has_model();
-        if !model_present {
-            show_pill_window(&app, true);
-            emit_transcribe_error(&app, "Скачай модель: Settings → Models");
-            tracing::warn!("hotkey pressed but no model is configured");
-            is_recording.store(false, Ordering::SeqCst);
-            wait_for_hotkey_release(&active_mode);
-            continue;
+        match pipeline.record_cycle() {
+            Ok(job) => {
+                if jobs.send(job).is_err() {
+                    tracing::error!("job worker is gone; recording dropped");
+                }
+            }
+            Err(outcome) if outcome.needs_release() => wait_for_hotkey_release(&active_mode),
+            Err(_) => {}
         }
+    }
+}
 
-        show_pill_window(&app, true);
-        emit_state(&app, ui::PillState::Recording);
-        tray::set_state(&app, tray::TrayState::Recording);
-
-        let active_for_record = active_mode.clone();
-        let app_for_spec = app.clone();
-        let record_start = Instant::now();
-        let samples = match recorder.record_while_with_spectrum(
-            move || active_for_record.load(Ordering::SeqCst) != hotkey::MODE_IDLE,
-            move |spec| {
-                let _ = app_for_spec.emit("audio-spectrum", spec);
-            },
-        ) {
-            Ok(samples) => samples,
-            Err(e) => {
-                tracing::error!("audio recording failed: {:#}", e);
-                emit_transcribe_error(&app, &format!("Audio error: {e:#}"));
-                tray::set_state(&app, tray::TrayState::Idle);
-                is_recording.store(false, Ordering::SeqCst);
-                wait_for_hotkey_release(&active_mode);
-                continue;
+/// Transcribes, post-processes and inserts recordings one at a time, in
+/// the order they were made, while the hotkey loop is free to record the
+/// next one.
+fn spawn_job_worker(pipeline: Arc<Pipeline>) -> mpsc::Sender<Step> {
+    let (tx, rx) = mpsc::channel::<Step>();
+    std::thread::Builder::new()
+        .name("flov-job-worker".into())
+        .spawn(move || {
+            for job in rx {
+                let _cycle_guard = RecordingCycleGuard::start();
+                pipeline.process(job);
             }
-        };
+        })
+        .expect("spawn job worker");
+    tx
+}
 
-        is_recording.store(false, Ordering::SeqCst);
-        tracing::info!(
-            "recording stop in {:?}, samples={}",
-            record_start.elapsed(),
-            samples.len()
-        );
-
-        if samples.len() < 1600 {
-            emit_state(&app, ui::PillState::Idle);
-            tray::set_state(&app, tray::TrayState::Idle);
-            continue;
-        }
+/// The cpal recorder. Its output rate is fixed at startup, so it's
+/// captured alongside rather than queried per cycle.
+struct Microphone {
+    recorder: Arc<audio::AudioRecorder>,
+    sample_rate: u32,
+}
 
-        emit_state(&app, ui::PillState::Transcribing);
-        tray::set_state(&app, tray::TrayState::Transcribing);
-
-        let transcribe_start = Instant::now();
-        let raw_text = match transcriber.transcribe(&samples) {
-            Ok(t) => t,
-            Err(e) => {
-                tracing::error!("transcribe failed: {}", e);
-                let msg = if e.to_string().contains("model file not found") {
-                    "Скачай модель: Settings → Models".to_string()
-                } else {
-                    format!("Transcribe error: {}", e)
-                };
-                emit_transcribe_error(&app, &msg);
-                tray::set_state(&app, tray::TrayState::Idle);
-                continue;
-            }
-        };
-        tracing::info!(
-            "recording loop: transcribe returned in {:?}, chars={}",
-            transcribe_start.elapsed(),
-            raw_text.chars().count()
-        );
-        if raw_text.is_empty() {
-            emit_state(&app, ui::PillState::Idle);
-            tray::set_state(&app, tray::TrayState::Idle);
-            continue;
-        }
-        tracing::info!("transcript: {}", raw_text);
+impl AudioSource for Microphone {
+    fn record(
+        &self,
+        keep_going: Box<dyn Fn() -> bool + Send>,
+        on_spectrum: Box<dyn Fn(Vec<f32>, levels::Levels) + Send>,
+    ) -> anyhow::Result<Vec<f32>> {
+        self.recorder
+            .record_while_with_spectrum(keep_going, on_spectrum)
+    }
 
-        let chars = raw_text.chars().count() as u64;
-        let seconds = samples.len() as f64 / sample_rate as f64;
-        stats.record(chars, seconds);
-        let _ = app.emit("stats-updated", ());
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
 
-        let pp_snapshot: Option<Arc<postprocess::PostProcessor>> =
-            post_processor.lock().unwrap().clone();
+impl SpeechToText for transcribe::Transcriber {
+    fn has_model(&self) -> bool {
+        transcribe::Transcriber::has_model(self)
+    }
 
-        let final_text = if postprocess_enabled.load(Ordering::SeqCst) {
-            if let Some(p) = pp_snapshot.as_ref() {
-                match p.process(&raw_text) {
-                    Ok(t) => t,
-                    Err(e) => {
-                        tracing::error!(
-                            "postprocess failed, falling back to raw transcript: {:#}",
-                            e
-                        );
-                        raw_text
-                    }
-                }
-            } else {
+    fn transcribe(&self, samples: &[f32]) -> anyhow::Result<transcribe::Transcript> {
+        transcribe::Transcriber::transcribe(self, samples)
+    }
+
+    fn transcribe_timed(
+        &self,
+        samples: &[f32],
+        offset: f64,
+    ) -> anyhow::Result<transcribe::Transcript> {
+        transcribe::Transcriber::transcribe_timed(self, samples, offset)
+    }
+}
+
+/// The OpenRouter post-processor as configured right now: the toggle and
+/// the processor are swapped from Settings between cycles. Token usage
+/// goes to stats for every attempt that reported it, answered or not.
+struct SharedPostprocessor {
+    app: tauri::AppHandle,
+    enabled: Arc<AtomicBool>,
+    slot: Arc<Mutex<Option<Arc<postprocess::PostProcessor>>>>,
+    stats: Arc<stats::Stats>,
+}
+
+impl TextPostprocessor for SharedPostprocessor {
+    fn active(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst) && self.slot.lock().unwrap().is_some()
+    }
+
+    fn process(
+        &self,
+        text: &str,
+        on_delta: &mut dyn FnMut(&str) -> bool,
+    ) -> anyhow::Result<Option<String>> {
+        if !self.enabled.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let snapshot = self.slot.lock().unwrap().clone();
+        match snapshot {
+            Some(p) => {
+                let done = p.process(text, on_delta, &mut |model, usage| {
+                    self.stats.record_usage(model, usage);
+                    let _ = self.app.emit("stats-updated", ());
+                })?;
+                Ok(Some(done.text))
+            }
+            None => {
                 tracing::warn!("postprocess enabled but no API key configured");
-                raw_text
+                Ok(None)
             }
+        }
+    }
+}
+
+/// Clipboard + synthetic paste into the focused window.
+struct PasteSink;
+
+impl TextSink for PasteSink {
+    fn insert(&self, text: &str) {
+        input::type_text(text);
+    }
+
+    fn insert_partial(&self, piece: &str) {
+        input::type_text(piece);
+    }
+
+    fn insert_rest(&self, _text: &str, rest: &str) {
+        if !rest.is_empty() {
+            input::type_text(rest);
+        }
+    }
+}
+
+/// Payload of `audio-spectrum`: the bands and the levels in one event per
+/// frame.
+#[derive(Clone, serde::Serialize)]
+struct SpectrumFrame {
+    bands: Vec<f32>,
+    levels: levels::Levels,
+}
+
+/// Turns pipeline events into pill / tray updates, webview events, stats
+/// writes and control API state.
+///
+/// Queued jobs finish while the next recording is in progress; the pill
+/// belongs to the recording then, and a job's notice or error waits until
+/// it's over.
+struct TauriObserver {
+    app: tauri::AppHandle,
+    stats: Arc<stats::Stats>,
+    control: Arc<control::ControlHub>,
+    /// Cycle whose microphone is open; 0 when none is.
+    recording: AtomicU64,
+    pending: AtomicUsize,
+    deferred: Mutex<Option<PipelineEvent>>,
+}
+
+impl TauriObserver {
+    /// True when `cycle` is a queued job finishing under someone else's
+    /// recording.
+    fn pill_taken(&self, cycle: u64) -> bool {
+        let recording = self.recording.load(Ordering::SeqCst);
+        recording != 0 && recording != cycle
+    }
+
+    /// Clears the recording mark if it belongs to `cycle`.
+    fn recording_ended(&self, cycle: u64) {
+        let _ = self
+            .recording
+            .compare_exchange(cycle, 0, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Where the pill goes once a job has ended: back to transcribing
+    /// while more are queued, otherwise idle.
+    fn settle(&self) {
+        let app = &self.app;
+        if self.pending.load(Ordering::SeqCst) > 0 {
+            emit_state(app, ui::PillState::Transcribing);
+            tray::set_state(app, tray::TrayState::Transcribing);
+        } else {
+            emit_state(app, ui::PillState::Idle);
+            tray::set_state(app, tray::TrayState::Idle);
+        }
+    }
+
+    /// Shows a job's notice or error, or holds it back while another
+    /// recording has the pill.
+    fn report(&self, cycle: u64, event: PipelineEvent) {
+        if self.pill_taken(cycle) {
+            *self.deferred.lock().unwrap() = Some(event);
+            return;
+        }
+        let app = &self.app;
+        match event {
+            PipelineEvent::Notice(message) => emit_notice(app, &message),
+            PipelineEvent::Error(message) => emit_transcribe_error(app, &message),
+            _ => return,
+        }
+        let tray_state = if self.pending.load(Ordering::SeqCst) > 0 {
+            tray::TrayState::Transcribing
         } else {
-            raw_text
+            tray::TrayState::Idle
         };
+        tray::set_state(app, tray_state);
+    }
+
+    /// A held-back notice or error, for when the recording ends.
+    fn take_deferred(&self) -> Option<PipelineEvent> {
+        self.deferred.lock().unwrap().take()
+    }
+}
 
-        input::type_text(&final_text);
-        emit_state(&app, ui::PillState::Idle);
-        tray::set_state(&app, tray::TrayState::Idle);
-        tracing::info!(
-            "recording cycle complete in {:?}, final_chars={}",
-            cycle_start.elapsed(),
-            final_text.chars().count()
-        );
+impl PipelineObserver for TauriObserver {
+    fn on_event(&self, cycle: u64, event: PipelineEvent) {
+        self.control.on_event(cycle, &event);
+        let app = &self.app;
+        match event {
+            PipelineEvent::CycleStarted => show_pill_window(app, true),
+            PipelineEvent::Recording => {
+                self.recording.store(cycle, Ordering::SeqCst);
+                emit_state(app, ui::PillState::Recording);
+                tray::set_state(app, tray::TrayState::Recording);
+            }
+            PipelineEvent::Spectrum { bands, levels } => {
+                let _ = app.emit("audio-spectrum", SpectrumFrame { bands, levels });
+            }
+            PipelineEvent::LimitApproaching(remaining) => {
+                let _ = app.emit("recording-limit", remaining);
+            }
+            PipelineEvent::Transcribing => {
+                self.recording_ended(cycle);
+                match self.take_deferred() {
+                    Some(held) => self.report(cycle, held),
+                    None => {
+                        emit_state(app, ui::PillState::Transcribing);
+                        tray::set_state(app, tray::TrayState::Transcribing);
+                    }
+                }
+            }
+            PipelineEvent::Postprocessing => {
+                if !self.pill_taken(cycle) {
+                    emit_state(app, ui::PillState::Postprocessing);
+                }
+            }
+            PipelineEvent::Pending(count) => {
+                self.pending.store(count, Ordering::SeqCst);
+                let _ = app.emit("jobs-pending", count);
+            }
+            PipelineEvent::Transcribed {
+                chars,
+                seconds,
+                levels,
+                recording,
+            } => {
+                self.stats.record(chars, seconds, &levels);
+                let _ = app.emit("stats-updated", ());
+                if recording.is_some() {
+                    let _ = app.emit("recordings-updated", ());
+                }
+            }
+            PipelineEvent::Inserted(_) => {}
+            PipelineEvent::Idle => {
+                self.recording_ended(cycle);
+                if self.pill_taken(cycle) {
+                    return;
+                }
+                match self.take_deferred() {
+                    Some(held) => self.report(cycle, held),
+                    None => self.settle(),
+                }
+            }
+            notice @ (PipelineEvent::Notice(_) | PipelineEvent::Error(_)) => {
+                self.recording_ended(cycle);
+                if !self.pill_taken(cycle) {
+                    // This one is newer than anything held back.
+                    self.take_deferred();
+                }
+                self.report(cycle, notice);
+            }
+        }
     }
 }
 
@@ -277,6 +559,11 @@ fn emit_transcribe_error(app: &tauri::AppHandle, message: &str) {
     let _ = app.emit("transcribe-error", message);
 }
 
+fn emit_notice(app: &tauri::AppHandle, message: &str) {
+    ui::set_pill_notice(message);
+    let _ = app.emit("transcribe-notice", message);
+}
+
 fn wait_for_hotkey_release(active_mode: &AtomicU8) {
     while active_mode.load(Ordering::SeqCst) != hotkey::MODE_IDLE {
         std::thread::sleep(std::time::Duration::from_millis(20));
@@ -0,0 +1,406 @@
+// Band-limited sample-rate conversion: rational polyphase with a
+// Kaiser-windowed sinc. For 48 kHz → 16 kHz the old linear interpolation
+// just kept every third sample, folding everything between 8 and 24 kHz
+// (fans, sibilants, keyboard clicks) back into the band whisper listens
+// to; this filters it out first.
+//
+// Streaming: `Resampler::process` takes blocks as the capture callback
+// delivers them and keeps its delay line between calls, so the recorder
+// converts as it goes and `flush` drains the last few milliseconds.
+
+use serde::Deserialize;
+
+/// `[audio] resample_quality`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    /// Short filter: aliases ~60 dB down, but it starts rolling off
+    /// around 6 kHz.
+    Fast,
+    /// Aliases ~80–100 dB down, flat to ~6.5 kHz.
+    #[default]
+    Balanced,
+    /// Aliases >110 dB down, flat to ~7.2 kHz; four times Fast's work.
+    Best,
+}
+
+impl Quality {
+    pub const NAMES: &'static [&'static str] = &["fast", "balanced", "best"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Quality::Fast => "fast",
+            Quality::Balanced => "balanced",
+            Quality::Best => "best",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "fast" => Some(Quality::Fast),
+            "balanced" => Some(Quality::Balanced),
+            "best" => Some(Quality::Best),
+            _ => None,
+        }
+    }
+
+    /// (sinc zero crossings per side at the lower rate, Kaiser beta,
+    /// cutoff as a fraction of the lower Nyquist).
+    fn params(self) -> (usize, f64, f64) {
+        match self {
+            Quality::Fast => (8, 5.0, 0.85),
+            Quality::Balanced => (16, 7.5, 0.90),
+            Quality::Best => (32, 10.0, 0.93),
+        }
+    }
+}
+
+/// Converts `from` Hz to `to` Hz by upsampling by `l`, filtering, and
+/// downsampling by `m` (`to / from == l / m`), computing only the outputs
+/// it keeps.
+pub struct Resampler {
+    l: u64,
+    m: u64,
+    /// `phases[p][k]` is prototype tap `p + l * k`.
+    phases: Vec<Vec<f32>>,
+    /// Upsampled-rate index of the next output.
+    t: u64,
+    /// Index of the first sample kept in `history`.
+    history_start: u64,
+    history: Vec<f32>,
+    received: u64,
+    produced: u64,
+    /// Initial `t`: half the filter, so outputs line up with the input.
+    delay: u64,
+}
+
+impl Resampler {
+    pub fn new(from: u32, to: u32, quality: Quality) -> Self {
+        let g = gcd(from as u64, to as u64).max(1);
+        let (l, m) = (to as u64 / g, from as u64 / g);
+        let (zero_crossings, beta, rolloff) = quality.params();
+
+        let (phases, delay) = if l == m {
+            (vec![vec![1.0]], 0)
+        } else {
+            let stretch = l.max(m) as usize;
+            let half = zero_crossings * stretch;
+            // Cycles per upsampled sample.
+            let cutoff = rolloff * 0.5 / stretch as f64;
+            let taps_per_phase = (2 * half + 1).div_ceil(l as usize);
+            let i0_beta = bessel_i0(beta);
+            let mut phases = vec![vec![0.0f32; taps_per_phase]; l as usize];
+            for (p, phase) in phases.iter_mut().enumerate() {
+                for (k, tap) in phase.iter_mut().enumerate() {
+                    let n = p + l as usize * k;
+                    let x = n as f64 - half as f64;
+                    if x.abs() > half as f64 {
+                        continue;
+                    }
+                    let sinc = if x == 0.0 {
+                        2.0 * cutoff
+                    } else {
+                        (2.0 * std::f64::consts::PI * cutoff * x).sin() / (std::f64::consts::PI * x)
+                    };
+                    let r = x / half as f64;
+                    let window = bessel_i0(beta * (1.0 - r * r).sqrt()) / i0_beta;
+                    // ×l makes up for the zeros upsampling stuffs in.
+                    *tap = (sinc * window * l as f64) as f32;
+                }
+            }
+            (phases, half as u64)
+        };
+
+        Self {
+            l,
+            m,
+            phases,
+            t: delay,
+            history_start: 0,
+            history: Vec::new(),
+            received: 0,
+            produced: 0,
+            delay,
+        }
+    }
+
+    fn is_passthrough(&self) -> bool {
+        self.l == self.m
+    }
+
+    /// Appends every output `input` completes to `out`. Outputs trail the
+    /// input by half the filter length until `flush`.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        self.received += input.len() as u64;
+        if self.is_passthrough() {
+            out.extend_from_slice(input);
+            self.produced += input.len() as u64;
+            return;
+        }
+        self.history.extend_from_slice(input);
+
+        let taps = self.phases[0].len() as u64;
+        loop {
+            let newest = self.t / self.l;
+            if newest >= self.received {
+                break;
+            }
+            let phase = &self.phases[(self.t % self.l) as usize];
+            let mut acc = 0.0f32;
+            for (k, &c) in phase.iter().enumerate() {
+                // Before the first sample is silence.
+                let Some(j) = newest.checked_sub(k as u64) else {
+                    break;
+                };
+                acc += c * self.history[(j - self.history_start) as usize];
+            }
+            out.push(acc);
+            self.produced += 1;
+            self.t += self.m;
+        }
+
+        // Keep what the next output can still reach.
+        let keep_from = (self.t / self.l)
+            .saturating_sub(taps - 1)
+            .min(self.received);
+        if keep_from > self.history_start {
+            self.history
+                .drain(..(keep_from - self.history_start) as usize);
+            self.history_start = keep_from;
+        }
+    }
+
+    /// Emits the outputs still held back by the filter, as if the input
+    /// went on in silence, so the total is `received * to / from`; then
+    /// starts over for a new stream.
+    pub fn flush(&mut self, out: &mut Vec<f32>) {
+        let target = self.received * self.l / self.m;
+        if self.produced < target {
+            let missing = (target - self.produced) as usize;
+            let start = out.len();
+            let silence = vec![0.0; self.phases[0].len()];
+            while out.len() - start < missing {
+                self.process(&silence, out);
+            }
+            out.truncate(start + missing);
+        }
+        self.t = self.delay;
+        self.history_start = 0;
+        self.history.clear();
+        self.received = 0;
+        self.produced = 0;
+    }
+}
+
+/// One-shot conversion of a whole buffer.
+pub fn resample(samples: &[f32], from: u32, to: u32, quality: Quality) -> Vec<f32> {
+    let mut resampler = Resampler::new(from, to, quality);
+    let mut out = Vec::with_capacity(samples.len() * to as usize / from.max(1) as usize + 1);
+    resampler.process(samples, &mut out);
+    resampler.flush(&mut out);
+    out
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind, for the
+/// Kaiser window. The series converges fast for the betas used here.
+fn bessel_i0(x: f64) -> f64 {
+    let q = x * x / 4.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..64 {
+        term *= q / (k * k) as f64;
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `audio::resample` used to do: linear interpolation, no filter.
+    fn linear(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+        let ratio = from as f64 / to as f64;
+        let new_len = (samples.len() as f64 / ratio) as usize;
+        (0..new_len)
+            .map(|i| {
+                let src = i as f64 * ratio;
+                let idx = src as usize;
+                let frac = (src - idx as f64) as f32;
+                match (samples.get(idx), samples.get(idx + 1)) {
+                    (Some(&a), Some(&b)) => a * (1.0 - frac) + b * frac,
+                    (Some(&a), None) => a,
+                    _ => 0.0,
+                }
+            })
+            .collect()
+    }
+
+    fn sine(freq: f64, rate: u32, secs: f64) -> Vec<f32> {
+        let n = (rate as f64 * secs) as usize;
+        (0..n)
+            .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// Amplitude of `freq` in `samples` (a full-scale sine reads 1.0),
+    /// via a Hann-windowed DFT bin over the middle half, away from the
+    /// edges.
+    fn tone_level(samples: &[f32], rate: u32, freq: f64) -> f64 {
+        let mid = &samples[samples.len() / 4..samples.len() * 3 / 4];
+        let n = mid.len() as f64;
+        let (mut re, mut im, mut wsum) = (0.0, 0.0, 0.0);
+        for (i, &x) in mid.iter().enumerate() {
+            let w = 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / n).cos();
+            let phase = 2.0 * std::f64::consts::PI * freq * i as f64 / rate as f64;
+            re += x as f64 * w * phase.cos();
+            im += x as f64 * w * phase.sin();
+            wsum += w;
+        }
+        2.0 * (re * re + im * im).sqrt() / wsum
+    }
+
+    fn db(level: f64) -> f64 {
+        20.0 * level.max(1e-12).log10()
+    }
+
+    #[test]
+    fn equal_rates_pass_through() {
+        let samples = [0.0, 0.25, -0.5, 1.0];
+
+        assert_eq!(resample(&samples, 16_000, 16_000, Quality::Best), samples);
+    }
+
+    #[test]
+    fn output_length_matches_rate_ratio() {
+        for (from, to) in [
+            (48_000, 16_000),
+            (44_100, 16_000),
+            (8_000, 16_000),
+            (22_050, 16_000),
+        ] {
+            let input = vec![0.1f32; from as usize / 3 + 7];
+            let out = resample(&input, from, to, Quality::Balanced);
+            assert_eq!(
+                out.len(),
+                input.len() * to as usize / from as usize,
+                "{} → {}",
+                from,
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn streaming_in_odd_blocks_matches_one_shot() {
+        let input = sine(440.0, 44_100, 0.3);
+        let expected = resample(&input, 44_100, 16_000, Quality::Balanced);
+
+        let mut resampler = Resampler::new(44_100, 16_000, Quality::Balanced);
+        let mut out = Vec::new();
+        for block in input.chunks(441) {
+            resampler.process(block, &mut out);
+        }
+        resampler.flush(&mut out);
+
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn flush_starts_a_fresh_stream() {
+        let input = sine(300.0, 48_000, 0.1);
+        let mut resampler = Resampler::new(48_000, 16_000, Quality::Fast);
+        let mut first = Vec::new();
+        resampler.process(&input, &mut first);
+        resampler.flush(&mut first);
+
+        let mut second = Vec::new();
+        resampler.process(&input, &mut second);
+        resampler.flush(&mut second);
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn passband_tone_keeps_its_level_and_stays_aligned() {
+        for quality in [Quality::Fast, Quality::Balanced, Quality::Best] {
+            let input = sine(1_000.0, 48_000, 0.5);
+            let out = resample(&input, 48_000, 16_000, quality);
+
+            let level = tone_level(&out, 16_000, 1_000.0);
+            assert!(
+                (db(level)).abs() < 0.1,
+                "{:?}: {:.3} dB",
+                quality,
+                db(level)
+            );
+            // No group delay: output i is input 3i.
+            let err = (out[4000] - input[12_000]).abs();
+            assert!(err < 0.01, "{:?}: misaligned by {}", quality, err);
+        }
+    }
+
+    #[test]
+    fn downsampling_suppresses_aliases_that_linear_folds_back() {
+        // 12 kHz is above the 8 kHz Nyquist of 16 kHz audio and lands on
+        // 4 kHz if nothing filters it first.
+        let input = sine(12_000.0, 48_000, 0.5);
+
+        let linear_leak = db(tone_level(&linear(&input, 48_000, 16_000), 16_000, 4_000.0));
+        let fast = db(tone_level(
+            &resample(&input, 48_000, 16_000, Quality::Fast),
+            16_000,
+            4_000.0,
+        ));
+        let balanced = db(tone_level(
+            &resample(&input, 48_000, 16_000, Quality::Balanced),
+            16_000,
+            4_000.0,
+        ));
+        let best = db(tone_level(
+            &resample(&input, 48_000, 16_000, Quality::Best),
+            16_000,
+            4_000.0,
+        ));
+
+        assert!(linear_leak > -1.0, "linear: {:.1} dB", linear_leak);
+        assert!(fast < -60.0, "fast: {:.1} dB", fast);
+        assert!(balanced < -90.0, "balanced: {:.1} dB", balanced);
+        assert!(best < -110.0, "best: {:.1} dB", best);
+    }
+
+    #[test]
+    fn non_integer_ratio_suppresses_aliases_too() {
+        // 44.1 kHz → 16 kHz: 10 kHz folds to 6 kHz.
+        let input = sine(10_000.0, 44_100, 0.5);
+
+        let linear_leak = db(tone_level(&linear(&input, 44_100, 16_000), 16_000, 6_000.0));
+        let balanced = db(tone_level(
+            &resample(&input, 44_100, 16_000, Quality::Balanced),
+            16_000,
+            6_000.0,
+        ));
+
+        assert!(linear_leak > -12.0, "linear: {:.1} dB", linear_leak);
+        assert!(balanced < -75.0, "balanced: {:.1} dB", balanced);
+    }
+
+    #[test]
+    fn quality_names_round_trip() {
+        for name in Quality::NAMES {
+            assert_eq!(Quality::parse(name).unwrap().name(), *name);
+        }
+        assert_eq!(Quality::parse("ultra"), None);
+    }
+}
@@ -0,0 +1,638 @@
+// Where a finished transcript goes. Pasting into the focused window is one
+// destination; `[[output.sinks]]` in flov.toml adds more:
+//
+//   daily_notes  append to <dir>/YYYY-MM-DD.md under a local-time heading
+//   webhook      POST {"text", "timestamp", "chars"} as JSON
+//   command      run a program with the text on stdin
+//   stdout       print it (for running flov_app under a terminal/supervisor)
+//
+// The paste runs inline, as before. Every other sink gets its own worker
+// thread fed by a channel, so a slow webhook or a hung command never
+// delays the paste, the next dictation, or the other sinks; errors and
+// panics are logged per sink and go no further.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::SinkConfig;
+use crate::pipeline::TextSink;
+
+/// Per-request limit for webhooks.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// A command hook still running after this is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Delivery {
+    pub text: String,
+    /// Unix seconds.
+    pub at: u64,
+}
+
+pub trait Sink: Send + Sync {
+    /// For logs, e.g. `webhook https://example.com/hook`.
+    fn label(&self) -> String;
+    fn deliver(&self, delivery: &Delivery) -> Result<()>;
+}
+
+pub fn build(configs: &[SinkConfig]) -> Vec<Box<dyn Sink>> {
+    configs
+        .iter()
+        .map(|c| -> Box<dyn Sink> {
+            match c {
+                SinkConfig::DailyNotes { dir } => Box::new(DailyNotes { dir: dir.clone() }),
+                SinkConfig::Webhook { url, headers } => Box::new(Webhook::new(url, headers)),
+                SinkConfig::Command { command } => Box::new(CommandHook {
+                    command: command.clone(),
+                }),
+                SinkConfig::Stdout => Box::new(Stdout),
+            }
+        })
+        .collect()
+}
+
+/// The pipeline's `TextSink`: the paste (unless `[output] paste = false`)
+/// and then a hand-off to every configured sink.
+pub struct Outputs {
+    paste: Option<Arc<dyn TextSink>>,
+    workers: Vec<Sender<Arc<Delivery>>>,
+}
+
+impl Outputs {
+    pub fn new(paste: Option<Arc<dyn TextSink>>, sinks: Vec<Box<dyn Sink>>) -> Self {
+        let workers = sinks.into_iter().filter_map(spawn_worker).collect();
+        Self { paste, workers }
+    }
+}
+
+impl Outputs {
+    fn hand_off(&self, text: &str) {
+        if self.workers.is_empty() {
+            return;
+        }
+        let delivery = Arc::new(Delivery {
+            text: text.to_string(),
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        });
+        for worker in &self.workers {
+            let _ = worker.send(delivery.clone());
+        }
+    }
+}
+
+/// A text streamed from post-processing is pasted sentence by sentence;
+/// the other sinks get it once, whole.
+impl TextSink for Outputs {
+    fn insert(&self, text: &str) {
+        if let Some(paste) = &self.paste {
+            paste.insert(text);
+        }
+        self.hand_off(text);
+    }
+
+    fn insert_partial(&self, piece: &str) {
+        if let Some(paste) = &self.paste {
+            paste.insert_partial(piece);
+        }
+    }
+
+    fn insert_rest(&self, text: &str, rest: &str) {
+        if let Some(paste) = &self.paste {
+            paste.insert_rest(text, rest);
+        }
+        self.hand_off(text);
+    }
+}
+
+fn spawn_worker(sink: Box<dyn Sink>) -> Option<Sender<Arc<Delivery>>> {
+    let (tx, rx) = mpsc::channel::<Arc<Delivery>>();
+    let label = sink.label();
+    let spawned = std::thread::Builder::new()
+        .name("flov-sink".into())
+        .spawn(move || {
+            for delivery in rx {
+                deliver_isolated(sink.as_ref(), &delivery);
+            }
+        });
+    match spawned {
+        Ok(_) => {
+            tracing::info!("output sink: {}", label);
+            Some(tx)
+        }
+        Err(e) => {
+            tracing::error!("output sink {} disabled: {}", label, e);
+            None
+        }
+    }
+}
+
+/// Runs one delivery; a failure or even a panic is logged and the worker
+/// carries on with the next transcript.
+fn deliver_isolated(sink: &dyn Sink, delivery: &Delivery) {
+    let started = Instant::now();
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| sink.deliver(delivery))) {
+        Ok(Ok(())) => tracing::info!("{}: delivered in {:?}", sink.label(), started.elapsed()),
+        Ok(Err(e)) => tracing::warn!("{} failed: {:#}", sink.label(), e),
+        Err(_) => tracing::error!("{} panicked", sink.label()),
+    }
+}
+
+struct DailyNotes {
+    dir: PathBuf,
+}
+
+impl Sink for DailyNotes {
+    fn label(&self) -> String {
+        format!("daily notes {:?}", self.dir)
+    }
+
+    fn deliver(&self, delivery: &Delivery) -> Result<()> {
+        let (date, time) = local_date_time(delivery.at);
+        append_daily_note(&self.dir, &date, &time, &delivery.text)
+    }
+}
+
+/// Appends `text` under a `## HH:MM` heading to `<dir>/<date>.md`,
+/// starting the file with a `# <date>` title.
+fn append_daily_note(dir: &Path, date: &str, time: &str, text: &str) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("create {:?}", dir))?;
+    let path = dir.join(format!("{}.md", date));
+    let is_new = !path.exists();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("open {:?}", path))?;
+    let mut entry = String::new();
+    if is_new {
+        entry.push_str(&format!("# {}\n\n", date));
+    }
+    entry.push_str(&format!("## {}\n\n{}\n\n", time, text.trim()));
+    file.write_all(entry.as_bytes())
+        .with_context(|| format!("append to {:?}", path))
+}
+
+/// `("YYYY-MM-DD", "HH:MM")` in the local time zone.
+fn local_date_time(unix: u64) -> (String, String) {
+    let local = unix as i64 + utc_offset_secs(unix as i64);
+    let (y, m, d) = crate::stats::days_to_ymd(local.div_euclid(86_400));
+    let minutes = local.rem_euclid(86_400) / 60;
+    (
+        format!("{:04}-{:02}-{:02}", y, m, d),
+        format!("{:02}:{:02}", minutes / 60, minutes % 60),
+    )
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn utc_offset_secs(at: i64) -> i64 {
+    let t = at as libc::time_t;
+    // SAFETY: localtime_r only writes the tm we hand it.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(target_os = "windows")]
+fn utc_offset_secs(_at: i64) -> i64 {
+    use windows::Win32::System::Time::{GetTimeZoneInformation, TIME_ZONE_INFORMATION};
+
+    const TIME_ZONE_ID_DAYLIGHT: u32 = 2;
+    let mut tz = TIME_ZONE_INFORMATION::default();
+    // SAFETY: GetTimeZoneInformation only fills the struct we pass.
+    let id = unsafe { GetTimeZoneInformation(&mut tz) };
+    // Bias is in minutes *west* of UTC.
+    let bias = tz.Bias
+        + if id == TIME_ZONE_ID_DAYLIGHT {
+            tz.DaylightBias
+        } else {
+            tz.StandardBias
+        };
+    -(bias as i64) * 60
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn utc_offset_secs(_at: i64) -> i64 {
+    0
+}
+
+struct Webhook {
+    url: String,
+    headers: BTreeMap<String, String>,
+    agent: ureq::Agent,
+}
+
+impl Webhook {
+    fn new(url: &str, headers: &BTreeMap<String, String>) -> Self {
+        let config = ureq::Agent::config_builder()
+            .timeout_global(Some(WEBHOOK_TIMEOUT))
+            .http_status_as_error(false)
+            .build();
+        Self {
+            url: url.to_string(),
+            headers: headers.clone(),
+            agent: config.into(),
+        }
+    }
+}
+
+impl Sink for Webhook {
+    fn label(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    fn deliver(&self, delivery: &Delivery) -> Result<()> {
+        let body = serde_json::json!({
+            "text": delivery.text,
+            "timestamp": delivery.at,
+            "chars": delivery.text.chars().count(),
+        });
+        let mut request = self.agent.post(&self.url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let resp = request
+            .send_json(&body)
+            .with_context(|| format!("POST {}", self.url))?;
+        if !resp.status().is_success() {
+            bail!("POST {}: HTTP {}", self.url, resp.status().as_u16());
+        }
+        Ok(())
+    }
+}
+
+struct CommandHook {
+    /// Program and arguments; no shell unless the user names one.
+    command: Vec<String>,
+}
+
+impl Sink for CommandHook {
+    fn label(&self) -> String {
+        format!("command {:?}", self.command)
+    }
+
+    fn deliver(&self, delivery: &Delivery) -> Result<()> {
+        let (program, args) = self.command.split_first().context("`command` is empty")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("spawn {:?}", program))?;
+        // Fed and drained on their own threads: a command that writes to
+        // stderr before reading its input would otherwise block on the pipe
+        // until the timeout, or block our write with no timeout at all.
+        let mut stdin = child.stdin.take().context("command stdin missing")?;
+        let text = delivery.text.clone();
+        std::thread::spawn(move || {
+            // A command that exits without reading stdin isn't an error.
+            // Dropping the pipe afterwards closes it.
+            let _ = stdin.write_all(text.as_bytes());
+        });
+        let mut err = child.stderr.take().context("command stderr missing")?;
+        let stderr_thread = std::thread::spawn(move || {
+            let mut buf = String::new();
+            let _ = std::io::Read::read_to_string(&mut err, &mut buf);
+            buf
+        });
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() > COMMAND_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!(
+                    "{:?} still running after {:?}, killed",
+                    program,
+                    COMMAND_TIMEOUT
+                );
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        if !status.success() {
+            let stderr = stderr_thread.join().unwrap_or_default();
+            bail!("{:?} exited with {}: {}", program, status, stderr.trim());
+        }
+        Ok(())
+    }
+}
+
+struct Stdout;
+
+impl Sink for Stdout {
+    fn label(&self) -> String {
+        "stdout".into()
+    }
+
+    fn deliver(&self, delivery: &Delivery) -> Result<()> {
+        let mut out = std::io::stdout().lock();
+        writeln!(out, "{}", delivery.text)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("flov-sinks-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn delivery(text: &str) -> Delivery {
+        Delivery {
+            text: text.into(),
+            at: 1_700_000_000,
+        }
+    }
+
+    struct Recorder(Mutex<Sender<String>>);
+
+    impl Sink for Recorder {
+        fn label(&self) -> String {
+            "recorder".into()
+        }
+
+        fn deliver(&self, delivery: &Delivery) -> Result<()> {
+            self.0.lock().unwrap().send(delivery.text.clone())?;
+            Ok(())
+        }
+    }
+
+    struct Failing {
+        panic: bool,
+    }
+
+    impl Sink for Failing {
+        fn label(&self) -> String {
+            "failing".into()
+        }
+
+        fn deliver(&self, _: &Delivery) -> Result<()> {
+            if self.panic {
+                panic!("sink bug");
+            }
+            bail!("connection refused")
+        }
+    }
+
+    #[derive(Default)]
+    struct Paste(Mutex<Vec<String>>);
+
+    impl TextSink for Paste {
+        fn insert(&self, text: &str) {
+            self.0.lock().unwrap().push(text.to_string());
+        }
+
+        fn insert_partial(&self, piece: &str) {
+            self.insert(piece);
+        }
+
+        fn insert_rest(&self, _: &str, rest: &str) {
+            self.insert(rest);
+        }
+    }
+
+    #[test]
+    fn failing_sinks_do_not_affect_paste_or_each_other() {
+        let paste = Arc::new(Paste::default());
+        let (tx, rx) = mpsc::channel();
+        let outputs = Outputs::new(
+            Some(paste.clone()),
+            vec![
+                Box::new(Failing { panic: true }),
+                Box::new(Failing { panic: false }),
+                Box::new(Recorder(Mutex::new(tx))),
+            ],
+        );
+
+        outputs.insert("one");
+        outputs.insert("two");
+
+        assert_eq!(*paste.0.lock().unwrap(), vec!["one", "two"]);
+        let received: Vec<String> = rx.iter().take(2).collect();
+        assert_eq!(received, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn streamed_text_reaches_other_sinks_whole() {
+        let paste = Arc::new(Paste::default());
+        let (tx, rx) = mpsc::channel();
+        let outputs = Outputs::new(
+            Some(paste.clone()),
+            vec![Box::new(Recorder(Mutex::new(tx)))],
+        );
+
+        outputs.insert_partial("One. ");
+        outputs.insert_rest("One. Two.", "Two.");
+
+        assert_eq!(*paste.0.lock().unwrap(), vec!["One. ", "Two."]);
+        assert_eq!(rx.recv().unwrap(), "One. Two.");
+    }
+
+    #[test]
+    fn sinks_parse_from_toml() {
+        let cfg: crate::config::Config = toml::from_str(
+            r#"
+            [output]
+            paste = false
+
+            [[output.sinks]]
+            kind = "daily_notes"
+            dir = "/notes/journal"
+
+            [[output.sinks]]
+            kind = "webhook"
+            url = "http://localhost:5678/webhook/flov"
+            headers = { Authorization = "Bearer x" }
+
+            [[output.sinks]]
+            kind = "command"
+            command = ["notify-send", "flov"]
+
+            [[output.sinks]]
+            kind = "stdout"
+            "#,
+        )
+        .unwrap();
+
+        assert!(!cfg.output.paste);
+        assert_eq!(
+            cfg.output.sinks,
+            vec![
+                SinkConfig::DailyNotes {
+                    dir: "/notes/journal".into()
+                },
+                SinkConfig::Webhook {
+                    url: "http://localhost:5678/webhook/flov".into(),
+                    headers: BTreeMap::from([("Authorization".into(), "Bearer x".into())]),
+                },
+                SinkConfig::Command {
+                    command: vec!["notify-send".into(), "flov".into()]
+                },
+                SinkConfig::Stdout,
+            ]
+        );
+        assert_eq!(build(&cfg.output.sinks).len(), 4);
+        let defaults: crate::config::Config = toml::from_str("").unwrap();
+        assert!(defaults.output.paste && defaults.output.sinks.is_empty());
+    }
+
+    #[test]
+    fn paste_can_be_turned_off() {
+        let (tx, rx) = mpsc::channel();
+        let outputs = Outputs::new(None, vec![Box::new(Recorder(Mutex::new(tx)))]);
+
+        outputs.insert("only here");
+
+        assert_eq!(rx.recv().unwrap(), "only here");
+    }
+
+    #[test]
+    fn daily_notes_start_a_file_per_day() {
+        let dir = temp_dir("notes");
+
+        append_daily_note(&dir, "2026-03-01", "09:15", "first thought\n").unwrap();
+        append_daily_note(&dir, "2026-03-01", "18:40", "second").unwrap();
+        append_daily_note(&dir, "2026-03-02", "08:00", "next day").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.join("2026-03-01.md")).unwrap(),
+            "# 2026-03-01\n\n## 09:15\n\nfirst thought\n\n## 18:40\n\nsecond\n\n"
+        );
+        assert!(std::fs::read_to_string(dir.join("2026-03-02.md"))
+            .unwrap()
+            .starts_with("# 2026-03-02\n\n## 08:00"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn local_date_time_is_well_formed() {
+        let (date, time) = local_date_time(1_700_000_000);
+        // 2023-11-14 22:13 UTC; the local zone moves it by at most a day.
+        assert!(
+            ["2023-11-14", "2023-11-15"].contains(&date.as_str()),
+            "{}",
+            date
+        );
+        assert_eq!(time.len(), 5);
+        assert_eq!(&time[2..3], ":");
+    }
+
+    #[test]
+    fn webhook_posts_json_with_headers() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", server.server_addr().to_ip().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut request = server.recv().unwrap();
+            let auth = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("X-Token"))
+                .map(|h| h.value.to_string());
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            request.respond(tiny_http::Response::empty(204)).unwrap();
+            (auth, body)
+        });
+        let headers = BTreeMap::from([("X-Token".to_string(), "abc".to_string())]);
+
+        Webhook::new(&url, &headers)
+            .deliver(&delivery("привет"))
+            .unwrap();
+
+        let (auth, body) = handle.join().unwrap();
+        assert_eq!(auth.as_deref(), Some("abc"));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "text": "привет", "timestamp": 1_700_000_000, "chars": 6 })
+        );
+    }
+
+    #[test]
+    fn webhook_error_status_fails() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", server.server_addr().to_ip().unwrap());
+        std::thread::spawn(move || {
+            let request = server.recv().unwrap();
+            let _ = request.respond(tiny_http::Response::empty(500));
+        });
+
+        let err = Webhook::new(&url, &BTreeMap::new())
+            .deliver(&delivery("x"))
+            .unwrap_err();
+
+        assert!(format!("{:#}", err).contains("HTTP 500"), "{:#}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_gets_text_on_stdin() {
+        let dir = temp_dir("command");
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out.txt");
+        let hook = CommandHook {
+            command: vec![
+                "sh".into(),
+                "-c".into(),
+                format!("cat > '{}'", out.display()),
+            ],
+        };
+
+        hook.deliver(&delivery("piped text")).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "piped text");
+        let failing = CommandHook {
+            command: vec!["sh".into(), "-c".into(), "echo nope >&2; exit 3".into()],
+        };
+        let err = failing.deliver(&delivery("x")).unwrap_err();
+        assert!(format!("{:#}", err).contains("nope"), "{:#}", err);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chatty_command_does_not_fill_its_pipes() {
+        let dir = temp_dir("chatty");
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out.txt");
+        // Well over a pipe buffer to stderr before stdin is read, and well
+        // over one on stdin too.
+        let hook = CommandHook {
+            command: vec![
+                "sh".into(),
+                "-c".into(),
+                format!(
+                    "head -c 300000 /dev/zero | tr '\\0' y >&2; cat > '{}'",
+                    out.display()
+                ),
+            ],
+        };
+        let text = "x".repeat(300_000);
+
+        let started = Instant::now();
+        hook.deliver(&delivery(&text)).unwrap();
+
+        assert!(started.elapsed() < COMMAND_TIMEOUT);
+        assert_eq!(std::fs::read_to_string(&out).unwrap().len(), 300_000);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
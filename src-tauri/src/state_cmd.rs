@@ -5,33 +5,44 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 
-use crate::postprocess::PostProcessor;
+use crate::audio::{AudioRecorder, MAX_PREROLL_MS};
+use crate::denoise::{Denoise, DenoiseStage};
+use crate::postprocess::{Limits, PostProcessor};
 use crate::stats::{Stats, StatsFile};
+use crate::transcribe::BackendProbe;
 
 #[derive(Clone)]
 pub struct AppState {
     pub backend_choice: Arc<Mutex<String>>,
     pub available_backends: Vec<String>,
+    /// Startup `--probe` results; `None` while the probe is still running.
+    pub backend_probes: Arc<Mutex<Option<Vec<BackendProbe>>>>,
     pub postprocess_enabled: Arc<AtomicBool>,
     /// `None` when no API key — the Settings form fills this in.
     pub post_processor: Arc<Mutex<Option<Arc<PostProcessor>>>>,
     /// Latest known settings (mirrors flov.toml `[openrouter]`). UI reads
     /// these into its form; saving rebuilds the processor above.
     pub pp_settings: Arc<Mutex<PostprocessSettings>>,
+    /// Timeouts and retries from `[openrouter]`, read at startup.
+    pub pp_limits: Limits,
     /// Current key combo string for the global hotkey (e.g. "Ctrl+Win").
     pub hotkey_combo: Arc<Mutex<String>>,
     /// Selected microphone (cpal device name). `None` → system default.
-    /// Changing this only takes effect on the next launch — the running
-    /// AudioRecorder holds the open stream and is not re-created.
+    /// Applied live via `AudioRecorder::set_device`.
     pub audio_device: Arc<Mutex<Option<String>>>,
+    /// For the mic picker and the pre-roll toggle.
+    pub recorder: Arc<AudioRecorder>,
+    /// Noise suppression mode, switched live from Settings.
+    pub denoise: Arc<DenoiseStage>,
     pub stats: Arc<Stats>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PostprocessSettings {
     pub api_key: String,
+    /// Fallback list, comma-separated as the Settings field shows it.
     pub model: String,
     pub system_prompt: String,
 }
@@ -40,6 +51,21 @@ pub struct PostprocessSettings {
 pub struct BackendStateView {
     pub choice: String,
     pub available: Vec<String>,
+    /// Usable backends best-first — `ranked[0]` is what "auto" runs on.
+    /// Empty until probing finishes.
+    pub ranked: Vec<String>,
+    /// Per-backend probe details (device, memory, skip reason). `None`
+    /// while probing.
+    pub probes: Option<Vec<BackendProbe>>,
+    /// Largest model that fits the backend that would run right now.
+    pub recommended_model: Option<RecommendedModelView>,
+}
+
+#[derive(Serialize)]
+pub struct RecommendedModelView {
+    pub backend: String,
+    pub id: String,
+    pub label: String,
 }
 
 #[derive(Serialize)]
@@ -59,9 +85,44 @@ pub struct PostprocessConfigView {
 
 #[tauri::command]
 pub fn get_backend_state(state: State<AppState>) -> BackendStateView {
+    let choice = state.backend_choice.lock().unwrap().clone();
+    let probes = state.backend_probes.lock().unwrap().clone();
+    let ranked = probes
+        .as_deref()
+        .map(crate::transcribe::rank_backends)
+        .unwrap_or_default();
+    // Once probed, a backend whose sidecar exists but can't initialise is
+    // no longer offered.
+    let available = match &probes {
+        Some(p) => p
+            .iter()
+            .filter(|p| p.usable)
+            .map(|p| p.backend.clone())
+            .collect(),
+        None => state.available_backends.clone(),
+    };
+
+    let effective = if choice == "auto" {
+        ranked.first().cloned()
+    } else {
+        Some(choice.clone())
+    };
+    let recommended_model = effective.and_then(|backend| {
+        let probe = probes.as_ref()?.iter().find(|p| p.backend == backend)?;
+        let entry = crate::models::find(probe.recommended_model.as_deref()?).ok()?;
+        Some(RecommendedModelView {
+            backend,
+            id: entry.id.to_string(),
+            label: entry.label.to_string(),
+        })
+    });
+
     BackendStateView {
-        choice: state.backend_choice.lock().unwrap().clone(),
-        available: state.available_backends.clone(),
+        choice,
+        available,
+        ranked,
+        probes,
+        recommended_model,
     }
 }
 
@@ -116,9 +177,13 @@ pub fn set_postprocess_config(
     system_prompt: String,
     state: State<AppState>,
 ) -> Result<(), String> {
+    let models = crate::config::parse_models(&model);
+    if models.is_empty() {
+        return Err("Enter at least one model".into());
+    }
     crate::config::Config::write_openrouter_field("api_key", &api_key)
         .map_err(|e| e.to_string())?;
-    crate::config::Config::write_openrouter_field("model", &model).map_err(|e| e.to_string())?;
+    crate::config::Config::write_openrouter_models(&models).map_err(|e| e.to_string())?;
     crate::config::Config::write_openrouter_field("system_prompt", &system_prompt)
         .map_err(|e| e.to_string())?;
 
@@ -127,14 +192,15 @@ pub fn set_postprocess_config(
     } else {
         Some(Arc::new(PostProcessor::new(
             api_key.clone(),
-            model.clone(),
+            models.clone(),
             system_prompt.clone(),
+            state.pp_limits,
         )))
     };
     *state.post_processor.lock().unwrap() = new_processor;
     *state.pp_settings.lock().unwrap() = PostprocessSettings {
         api_key,
-        model,
+        model: models.join(", "),
         system_prompt,
     };
 
@@ -173,6 +239,13 @@ pub struct AudioInputsView {
     pub devices: Vec<String>,
     /// Saved choice; `None` means "use system default".
     pub selected: Option<String>,
+    /// What recordings actually use: the default when `selected` is
+    /// unplugged.
+    pub active: String,
+    /// Channel count of `active`'s stream config.
+    pub channels: u16,
+    /// 1-based channel recorded from `active`; 0 mixes them all.
+    pub channel: u16,
 }
 
 #[tauri::command]
@@ -180,6 +253,9 @@ pub fn list_audio_inputs(state: State<AppState>) -> AudioInputsView {
     AudioInputsView {
         devices: crate::audio::list_input_devices(),
         selected: state.audio_device.lock().unwrap().clone(),
+        active: state.recorder.device_name(),
+        channels: state.recorder.device_channels(),
+        channel: state.recorder.channel(),
     }
 }
 
@@ -197,10 +273,70 @@ pub fn set_audio_input(device: Option<String>, state: State<AppState>) -> Result
     crate::config::Config::write_audio_device(cleaned.as_deref().unwrap_or(""))
         .map_err(|e| e.to_string())?;
     *state.audio_device.lock().unwrap() = cleaned.clone();
-    tracing::info!(
-        "audio device selected: {:?} (takes effect after restart)",
-        cleaned
-    );
+    tracing::info!("audio device selected: {:?}", cleaned);
+    state
+        .recorder
+        .set_device(cleaned.as_deref())
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Records only `channel` (1-based; 0 = mix all) from the active device.
+/// Saved per device name, so other microphones keep their own choice.
+#[tauri::command]
+pub fn set_audio_channel(channel: u16, state: State<AppState>) -> Result<(), String> {
+    let device = state.recorder.device_name();
+    crate::config::Config::write_audio_channel(&device, channel).map_err(|e| e.to_string())?;
+    tracing::info!("audio channel for '{}': {}", device, channel);
+    state
+        .recorder
+        .set_channel(channel)
+        .map_err(|e| format!("{:#}", e))
+}
+
+#[derive(Serialize)]
+pub struct PrerollView {
+    /// 0 when off.
+    pub ms: u32,
+    pub max_ms: u32,
+}
+
+#[tauri::command]
+pub fn get_preroll(state: State<AppState>) -> PrerollView {
+    PrerollView {
+        ms: state.recorder.preroll_ms(),
+        max_ms: MAX_PREROLL_MS,
+    }
+}
+
+#[tauri::command]
+pub fn set_preroll(ms: u32, app: AppHandle, state: State<AppState>) -> Result<PrerollView, String> {
+    apply_preroll(&app, &state.recorder, ms).map_err(|e| format!("{:#}", e))?;
+    Ok(get_preroll(state))
+}
+
+/// Opens or closes the pre-roll stream, saves the choice and refreshes
+/// the tray indicator. Shared by Settings and the tray toggle.
+pub fn apply_preroll(app: &AppHandle, recorder: &AudioRecorder, ms: u32) -> anyhow::Result<()> {
+    let result = recorder.set_preroll(ms);
+    let current = recorder.preroll_ms();
+    crate::tray::set_mic_open(app, current > 0);
+    let _ = app.emit("preroll-changed", current);
+    result?;
+    crate::config::Config::write_preroll_ms(current)
+}
+
+/// "off" / "light" / "strong".
+#[tauri::command]
+pub fn get_denoise(state: State<AppState>) -> String {
+    state.denoise.mode().name().to_string()
+}
+
+#[tauri::command]
+pub fn set_denoise(mode: String, state: State<AppState>) -> Result<(), String> {
+    let parsed = Denoise::parse(&mode).ok_or_else(|| format!("unknown denoise mode '{}'", mode))?;
+    crate::config::Config::write_denoise(parsed.name()).map_err(|e| e.to_string())?;
+    state.denoise.set_mode(parsed);
+    tracing::info!("denoise: {}", parsed.name());
     Ok(())
 }
 
@@ -1,4 +1,6 @@
-// Lightweight usage stats: per-day recording count + character count.
+// Lightweight usage stats: per-day recording count + character count,
+// plus how many recordings clipped or came in too quiet, and what
+// OpenRouter post-processing used per model.
 //
 // Persisted as JSON next to the exe (`<exe_dir>/stats.json`). Updated
 // synchronously on every successful transcription. Read by the Settings
@@ -11,12 +13,34 @@ use std::path::PathBuf;
 use std::sync::Mutex;
 use std::time::SystemTime;
 
+use crate::levels::Levels;
+use crate::postprocess::Usage;
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DayStats {
     pub recordings: u64,
     pub chars: u64,
     #[serde(default, deserialize_with = "deser_finite_f64")]
     pub seconds: f64,
+    /// Recordings with at least one clipped sample.
+    #[serde(default)]
+    pub clipped: u64,
+    /// Recordings peaking under `levels::QUIET_PEAK`.
+    #[serde(default)]
+    pub quiet: u64,
+    /// OpenRouter model → post-processing usage that day.
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelUsage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// USD. Requests OpenRouter reported no cost for add nothing.
+    #[serde(default, deserialize_with = "deser_finite_f64")]
+    pub cost: f64,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -25,6 +49,9 @@ pub struct StatsFile {
     pub total_chars: u64,
     #[serde(default, deserialize_with = "deser_finite_f64")]
     pub total_seconds: f64,
+    /// Post-processing spend in USD, all models.
+    #[serde(default, deserialize_with = "deser_finite_f64")]
+    pub total_cost: f64,
     /// `YYYY-MM-DD` → counts. BTreeMap so JSON dumps sorted.
     pub by_day: BTreeMap<String, DayStats>,
 }
@@ -68,8 +95,9 @@ impl Stats {
     }
 
     /// Record one transcription. `chars` is the rendered transcript length;
-    /// `audio_seconds` is the recording duration the user spent talking.
-    pub fn record(&self, chars: u64, audio_seconds: f64) {
+    /// `audio_seconds` is the recording duration the user spent talking;
+    /// `levels` are its input levels before any gain.
+    pub fn record(&self, chars: u64, audio_seconds: f64, levels: &Levels) {
         let today = today_utc_date();
         {
             let mut s = self.inner.lock().unwrap();
@@ -80,6 +108,8 @@ impl Stats {
             entry.recordings += 1;
             entry.chars += chars;
             entry.seconds += audio_seconds;
+            entry.clipped += (levels.clipped > 0) as u64;
+            entry.quiet += levels.is_quiet() as u64;
             // Best-effort persistence; ignore IO errors but log.
             if let Err(e) = self.write_locked(&s) {
                 tracing::warn!("stats write failed: {}", e);
@@ -87,6 +117,17 @@ impl Stats {
         }
     }
 
+    /// Record the tokens one post-processing request to `model` used,
+    /// whether it was answered, failed or abandoned for a fallback.
+    pub fn record_usage(&self, model: &str, usage: &Usage) {
+        let today = today_utc_date();
+        let mut s = self.inner.lock().unwrap();
+        s.add_usage(today, model, usage);
+        if let Err(e) = self.write_locked(&s) {
+            tracing::warn!("stats write failed: {}", e);
+        }
+    }
+
     fn write_locked(&self, s: &StatsFile) -> Result<()> {
         let json = serde_json::to_string_pretty(s).context("serialize stats")?;
         // Atomic-ish: write to .tmp then rename.
@@ -98,6 +139,24 @@ impl Stats {
     }
 }
 
+impl StatsFile {
+    fn add_usage(&mut self, day: String, model: &str, usage: &Usage) {
+        let cost = usage.cost.unwrap_or(0.0);
+        self.total_cost += cost;
+        let entry = self
+            .by_day
+            .entry(day)
+            .or_default()
+            .models
+            .entry(model.to_string())
+            .or_default();
+        entry.requests += 1;
+        entry.prompt_tokens += usage.prompt_tokens;
+        entry.completion_tokens += usage.completion_tokens;
+        entry.cost += cost;
+    }
+}
+
 fn stats_path() -> Result<PathBuf> {
     crate::paths::stats_path()
 }
@@ -117,7 +176,7 @@ fn today_utc_date() -> String {
 
 /// Convert "days since 1970-01-01 (UTC)" to (year, month, day).
 /// Uses Howard Hinnant's civil-from-days algorithm.
-fn days_to_ymd(days: i64) -> (i32, u32, u32) {
+pub(crate) fn days_to_ymd(days: i64) -> (i32, u32, u32) {
     let z = days + 719_468;
     let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
     let doe = (z - era * 146_097) as u64; // [0, 146096]
@@ -130,3 +189,33 @@ fn days_to_ymd(days: i64) -> (i32, u32, u32) {
     let y = if m <= 2 { y + 1 } else { y };
     (y as i32, m, d)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_adds_up_per_day_and_model() {
+        let mut s = StatsFile::default();
+        let usage = |prompt_tokens, cost| Usage {
+            prompt_tokens,
+            completion_tokens: 10,
+            cost,
+        };
+        s.add_usage("2026-10-18".into(), "a", &usage(100, Some(0.25)));
+        s.add_usage("2026-10-18".into(), "a", &usage(50, None));
+        s.add_usage("2026-10-18".into(), "b", &usage(7, Some(0.5)));
+        s.add_usage("2026-10-19".into(), "a", &usage(1, Some(1.0)));
+
+        let day = &s.by_day["2026-10-18"].models;
+        assert_eq!(day["a"].requests, 2);
+        assert_eq!(day["a"].prompt_tokens, 150);
+        assert_eq!(day["a"].completion_tokens, 20);
+        assert_eq!(day["a"].cost, 0.25);
+        assert_eq!(day["b"].cost, 0.5);
+        assert_eq!(s.by_day["2026-10-19"].models["a"].requests, 1);
+        assert_eq!(s.total_cost, 1.75);
+        // Usage alone isn't a recording.
+        assert_eq!(s.by_day["2026-10-18"].recordings, 0);
+    }
+}
@@ -0,0 +1,162 @@
+// Subtitle output for file transcription: a transcript as timed cues,
+// rendered as SubRip (.srt) or WebVTT (.vtt).
+
+use std::fmt::Write;
+
+use serde::Serialize;
+
+/// One stretch of text with its position in the source audio, in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    /// Parses `srt` / `vtt` (any case), as the CLI and the UI pass them.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::Vtt),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+        }
+    }
+
+    pub fn render(self, cues: &[Cue]) -> String {
+        match self {
+            Self::Srt => to_srt(cues),
+            Self::Vtt => to_vtt(cues),
+        }
+    }
+}
+
+/// Renders cues as SRT. Empty cues are skipped and the rest renumbered
+/// from 1, since players choke on blank entries.
+pub fn to_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues
+        .iter()
+        .filter(|c| !c.text.trim().is_empty())
+        .enumerate()
+    {
+        if i > 0 {
+            out.push('\n');
+        }
+        let _ = writeln!(
+            out,
+            "{}\n{} --> {}\n{}",
+            i + 1,
+            srt_timestamp(cue.start),
+            srt_timestamp(cue.end),
+            cue.text.trim()
+        );
+    }
+    out
+}
+
+/// Renders cues as WebVTT. Blank cues are skipped like in SRT; cue
+/// identifiers are optional in VTT and left out.
+pub fn to_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for cue in cues.iter().filter(|c| !c.text.trim().is_empty()) {
+        let _ = write!(
+            out,
+            "\n{} --> {}\n{}\n",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.'),
+            // "-->" inside the payload would be read as a timing line.
+            cue.text.trim().replace("-->", "->")
+        );
+    }
+    out
+}
+
+/// `HH:MM:SS,mmm`, the SRT clock format.
+fn srt_timestamp(secs: f64) -> String {
+    timestamp(secs, ',')
+}
+
+/// `HH:MM:SS<sep>mmm`: SRT uses a comma before the milliseconds, VTT a dot.
+fn timestamp(secs: f64, sep: char) -> String {
+    let ms = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        sep,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f64, end: f64, text: &str) -> Cue {
+        Cue {
+            start,
+            end,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn formats_srt_timestamps() {
+        assert_eq!(srt_timestamp(0.0), "00:00:00,000");
+        assert_eq!(srt_timestamp(3_723.456), "01:02:03,456");
+    }
+
+    #[test]
+    fn renders_numbered_cues_and_skips_blank_ones() {
+        let srt = to_srt(&[
+            cue(0.0, 30.0, " Hello there. "),
+            cue(30.0, 60.0, "  "),
+            cue(60.0, 61.5, "Bye."),
+        ]);
+
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:30,000\nHello there.\n\n\
+             2\n00:01:00,000 --> 00:01:01,500\nBye.\n"
+        );
+    }
+
+    #[test]
+    fn renders_vtt_with_header_and_dot_millis() {
+        let vtt = to_vtt(&[
+            cue(0.0, 1.25, "Hi --> there"),
+            cue(1.25, 2.0, ""),
+            cue(61.0, 62.5, "Bye."),
+        ]);
+
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.250\nHi -> there\n\n\
+             00:01:01.000 --> 00:01:02.500\nBye.\n"
+        );
+    }
+
+    #[test]
+    fn format_names_round_trip() {
+        for f in [SubtitleFormat::Srt, SubtitleFormat::Vtt] {
+            assert_eq!(SubtitleFormat::from_name(f.extension()), Some(f));
+        }
+        assert_eq!(SubtitleFormat::from_name("VTT"), Some(SubtitleFormat::Vtt));
+        assert_eq!(SubtitleFormat::from_name("txt"), None);
+    }
+}
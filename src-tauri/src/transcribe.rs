@@ -2,19 +2,24 @@
 //
 // The previous in-process whisper-rs link is replaced by a Command::spawn
 // of `flov-whisper-<backend>.exe` that lives next to flov.exe. The protocol
-// is documented in crates/flov-whisper-cpu/src/main.rs — args carry model
-// + language, stdin carries raw f32 LE PCM, stdout returns text.
+// is documented in crates/flov-whisper-core/src/lib.rs — args carry model
+// + language, stdin carries raw f32 LE PCM, stdout returns text (or timed
+// segments with `--segments`, for file import and subtitles).
 //
 // Each transcription resolves the active sidecar fresh, so the tray menu
 // can switch backends at runtime without recreating the Transcriber.
 
 use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use crate::subtitles::Cue;
+
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
@@ -75,11 +80,330 @@ fn cuda_runtime_present() -> bool {
     false
 }
 
+/// Upper bound for one `--probe` run. GPU runtimes can take a few seconds
+/// to initialise on a cold driver; anything slower is treated as broken.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// One ggml device as reported by `<sidecar> --probe`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProbeDevice {
+    /// `cpu`, `gpu`, `igpu` or `accel` (ggml's device type).
+    pub kind: String,
+    /// `ggml_backend_dev_init` succeeded for this device.
+    pub initialised: bool,
+    /// VRAM for GPUs, system RAM for the CPU device.
+    pub memory_total: u64,
+    pub memory_free: u64,
+    pub description: String,
+}
+
+/// Probe result for one backend, shown in the Backend settings card.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendProbe {
+    pub backend: String,
+    pub usable: bool,
+    pub devices: Vec<ProbeDevice>,
+    /// Why the backend was skipped (missing binary / driver, probe error).
+    pub skip_reason: Option<String>,
+    /// Catalog id of the largest model that fits the device memory.
+    pub recommended_model: Option<String>,
+}
+
+impl BackendProbe {
+    fn skipped(backend: &str, reason: impl Into<String>) -> Self {
+        Self {
+            backend: backend.to_string(),
+            usable: false,
+            devices: Vec::new(),
+            skip_reason: Some(reason.into()),
+            recommended_model: None,
+        }
+    }
+
+    /// The device this backend would actually run on: the initialised
+    /// device of the backend's own kind with the most memory.
+    pub fn primary_device(&self) -> Option<&ProbeDevice> {
+        let wants_gpu = self.backend != "cpu";
+        self.devices
+            .iter()
+            .filter(|d| d.initialised)
+            .filter(|d| {
+                if wants_gpu {
+                    d.kind == "gpu" || d.kind == "igpu"
+                } else {
+                    d.kind == "cpu"
+                }
+            })
+            .max_by_key(|d| d.memory_total)
+    }
+
+    /// Ranking class: discrete GPU, then integrated GPU, then CPU.
+    fn class(&self) -> u8 {
+        match self.primary_device().map(|d| d.kind.as_str()) {
+            Some("gpu") => 0,
+            Some("igpu") => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Runs `--probe` against every backend in `BACKEND_PRIORITY`. Spawns one
+/// sidecar at a time; call it off the main thread.
+pub fn probe_backends() -> Vec<BackendProbe> {
+    let dir = match exe_dir() {
+        Ok(d) => d,
+        Err(e) => {
+            return BACKEND_PRIORITY
+                .iter()
+                .map(|b| BackendProbe::skipped(b, format!("{:#}", e)))
+                .collect()
+        }
+    };
+    BACKEND_PRIORITY
+        .iter()
+        .map(|b| probe_backend(&dir, b))
+        .collect()
+}
+
+fn probe_backend(dir: &Path, backend: &str) -> BackendProbe {
+    let sidecar = dir.join(backend_bin_name(backend));
+    if !sidecar.exists() {
+        return BackendProbe::skipped(backend, "sidecar not installed");
+    }
+    // Spawning the CUDA sidecar without the driver pops a modal
+    // "nvcuda.dll was not found" dialog on Windows — never run it.
+    if backend == "cuda" && !cuda_runtime_present() {
+        return BackendProbe::skipped(backend, "NVIDIA driver not found");
+    }
+
+    let started = Instant::now();
+    let (devices, error) = match run_probe(&sidecar) {
+        Ok(result) => result,
+        Err(e) => (Vec::new(), Some(format!("{:#}", e))),
+    };
+    let mut probe = BackendProbe {
+        backend: backend.to_string(),
+        usable: false,
+        devices,
+        skip_reason: None,
+        recommended_model: None,
+    };
+    match (probe.primary_device().cloned(), error) {
+        (Some(dev), None) => {
+            probe.usable = true;
+            let budget = if dev.memory_free > 0 {
+                dev.memory_free
+            } else {
+                dev.memory_total
+            };
+            probe.recommended_model =
+                crate::models::recommend_for_memory(budget).map(|e| e.id.to_string());
+        }
+        (_, Some(reason)) => probe.skip_reason = Some(reason),
+        (None, None) => probe.skip_reason = Some("no usable device reported".into()),
+    }
+    tracing::info!(
+        "probe {}: usable={} device={:?} reason={:?} in {:?}",
+        backend,
+        probe.usable,
+        probe.primary_device().map(|d| d.description.as_str()),
+        probe.skip_reason,
+        started.elapsed()
+    );
+    probe
+}
+
+/// Spawns `<sidecar> --probe`. Returns the parsed devices plus the
+/// sidecar's stderr when it exited non-zero (that's the skip reason).
+fn run_probe(sidecar: &Path) -> Result<(Vec<ProbeDevice>, Option<String>)> {
+    let mut cmd = Command::new(sidecar);
+    cmd.arg("--probe")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("failed to spawn {:?}", sidecar))?;
+
+    // Drained while waiting: a probe that fills a pipe buffer would
+    // otherwise block until the timeout kills it.
+    let mut out = child.stdout.take().context("probe stdout missing")?;
+    let stdout_thread = std::thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = out.read_to_string(&mut buf);
+        buf
+    });
+    let mut err = child.stderr.take().context("probe stderr missing")?;
+    let stderr_thread = std::thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = err.read_to_string(&mut buf);
+        buf
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().context("probe wait failed")? {
+            break status;
+        }
+        if started.elapsed() > PROBE_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("probe timed out after {:?}", PROBE_TIMEOUT);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    let stdout = stdout_thread.join().unwrap_or_default();
+    let stderr = stderr_thread.join().unwrap_or_default();
+
+    let devices = parse_probe_output(&stdout);
+    if status.success() {
+        return Ok((devices, None));
+    }
+    let reason = match stderr.trim() {
+        "" => format!("probe exited with {:?}", status.code()),
+        s => s.to_string(),
+    };
+    Ok((devices, Some(reason)))
+}
+
+/// Parses the tab-separated `--probe` lines (see crates/README.md).
+/// Malformed lines are skipped rather than failing the whole probe.
+fn parse_probe_output(stdout: &str) -> Vec<ProbeDevice> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(5, '\t');
+            let kind = parts.next()?.trim();
+            let initialised = parts.next()?.trim() == "1";
+            let memory_total = parts.next()?.trim().parse().ok()?;
+            let memory_free = parts.next()?.trim().parse().ok()?;
+            let description = parts.next().unwrap_or("").trim();
+            if kind.is_empty() {
+                return None;
+            }
+            Some(ProbeDevice {
+                kind: kind.to_string(),
+                initialised,
+                memory_total,
+                memory_free,
+                description: description.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Usable backends, best first: discrete GPU, integrated GPU, CPU, with
+/// `BACKEND_PRIORITY` order breaking ties. This is what "auto" walks.
+pub fn rank_backends(probes: &[BackendProbe]) -> Vec<String> {
+    let mut usable: Vec<&BackendProbe> = probes.iter().filter(|p| p.usable).collect();
+    usable.sort_by_key(|p| {
+        let priority = BACKEND_PRIORITY
+            .iter()
+            .position(|b| *b == p.backend)
+            .unwrap_or(BACKEND_PRIORITY.len());
+        (p.class(), priority)
+    });
+    usable.into_iter().map(|p| p.backend.clone()).collect()
+}
+
+/// Consecutive sidecar failures after which "auto" stops trying a backend
+/// first for the rest of the session (it is still the last resort).
+const UNHEALTHY_AFTER_FAILURES: u32 = 2;
+
+/// Result of one `Transcriber::transcribe` call.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    pub text: String,
+    /// Backend whose sidecar produced `text`.
+    pub backend: String,
+    /// Backends that failed on these same samples before `backend`
+    /// succeeded, in the order tried. Empty on the normal path.
+    pub failed_backends: Vec<String>,
+    /// Whisper's segments, in seconds of the source audio. Only filled by
+    /// `transcribe_timed`; dictation has no use for them.
+    pub segments: Vec<Cue>,
+}
+
+/// Marks a sidecar killed by `transcription_timeout`. Timeouts are not
+/// retried on the next backend: the user already waited the full budget
+/// and the next backend in line is usually the slower one.
+#[derive(Debug)]
+pub struct SidecarTimedOut;
+
+impl std::fmt::Display for SidecarTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("sidecar timed out")
+    }
+}
+
+impl std::error::Error for SidecarTimedOut {}
+
+/// What one sidecar run produced. `load_ms` / `decode_ms` come from the
+/// sidecar itself; `elapsed` is the wall clock seen from here, including
+/// process spawn and piping the samples.
+#[derive(Debug, Clone)]
+pub struct SidecarOutput {
+    pub text: String,
+    pub elapsed: Duration,
+    pub load_ms: Option<u64>,
+    pub decode_ms: Option<u64>,
+}
+
+/// Per-session sidecar health, keyed by backend name. Lives only in memory:
+/// a driver that was broken yesterday may be fixed after a reboot.
+#[derive(Debug, Default)]
+struct BackendHealth {
+    consecutive_failures: HashMap<String, u32>,
+}
+
+impl BackendHealth {
+    /// Returns true when this failure just crossed the unhealthy threshold.
+    fn record_failure(&mut self, backend: &str) -> bool {
+        let count = self
+            .consecutive_failures
+            .entry(backend.to_string())
+            .or_insert(0);
+        *count += 1;
+        *count == UNHEALTHY_AFTER_FAILURES
+    }
+
+    fn record_success(&mut self, backend: &str) {
+        self.consecutive_failures.remove(backend);
+    }
+
+    fn is_unhealthy(&self, backend: &str) -> bool {
+        self.consecutive_failures
+            .get(backend)
+            .is_some_and(|n| *n >= UNHEALTHY_AFTER_FAILURES)
+    }
+
+    /// Healthy candidates keep their order; unhealthy ones move to the end
+    /// so they're only tried when everything else failed too.
+    fn order(&self, candidates: Vec<(String, PathBuf)>) -> Vec<(String, PathBuf)> {
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = candidates
+            .into_iter()
+            .partition(|(backend, _)| !self.is_unhealthy(backend));
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+}
+
 pub struct Transcriber {
     model_path: Arc<Mutex<PathBuf>>,
     language: String,
     /// Shared with the tray menu; updated when the user picks a backend.
     backend_choice: Arc<Mutex<String>>,
+    /// Startup `--probe` results; `None` until the probe thread finishes.
+    backend_probes: Arc<Mutex<Option<Vec<BackendProbe>>>>,
+    health: Mutex<BackendHealth>,
+    /// Where sidecar binaries live; `None` = next to the running exe.
+    sidecar_dir: Option<PathBuf>,
+    /// Fixed timeout instead of the audio-length based one.
+    timeout: Option<Duration>,
 }
 
 impl Transcriber {
@@ -87,6 +411,7 @@ impl Transcriber {
         model_path: Arc<Mutex<PathBuf>>,
         language: String,
         backend_choice: Arc<Mutex<String>>,
+        backend_probes: Arc<Mutex<Option<Vec<BackendProbe>>>>,
     ) -> Result<Self> {
         // Existence is checked per-transcribe so the user can launch the app
         // before downloading a model and pick one from the Models window.
@@ -96,9 +421,35 @@ impl Transcriber {
             model_path,
             language,
             backend_choice,
+            backend_probes,
+            health: Mutex::new(BackendHealth::default()),
+            sidecar_dir: None,
+            timeout: None,
         })
     }
 
+    /// Looks for sidecars in `dir` instead of next to the running exe.
+    /// Lets integration tests drive the real spawn / pipe / timeout path
+    /// with the mock sidecar on a machine without whisper builds.
+    pub fn with_sidecar_dir(mut self, dir: PathBuf) -> Self {
+        self.sidecar_dir = Some(dir);
+        self
+    }
+
+    /// Replaces `transcription_timeout` with a fixed value, so a timeout
+    /// test doesn't have to wait the 30 s minimum.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn sidecar_dir(&self) -> Result<PathBuf> {
+        match &self.sidecar_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => exe_dir(),
+        }
+    }
+
     /// Lightweight pre-flight: does the configured model file exist on
     /// disk right now? Used before starting a recording so we can show
     /// "no model" before the user wastes a sentence into the void.
@@ -106,10 +457,46 @@ impl Transcriber {
         self.model_path.lock().unwrap().exists()
     }
 
-    pub fn transcribe(&self, samples: &[f32]) -> Result<String> {
-        let total_start = Instant::now();
+    /// Transcribes `samples` on the chosen backend. In "auto" mode a sidecar
+    /// that fails (crash, missing DLL, bad exit) is followed by the next
+    /// backend in the ranking on the same samples, so the dictation isn't
+    /// lost; backends that keep failing sink to the end of the order.
+    pub fn transcribe(&self, samples: &[f32]) -> Result<Transcript> {
+        self.transcribe_with(samples, false)
+    }
+
+    /// Like `transcribe`, but asks the sidecar for whisper's segments and
+    /// shifts them by `offset` seconds — where `samples` starts in the
+    /// file it was cut from — so a long file transcribed window by window
+    /// gets one continuous timeline. Segment ends are clamped to the
+    /// window: whisper occasionally reports a last segment running past
+    /// the audio it was given.
+    pub fn transcribe_timed(&self, samples: &[f32], offset: f64) -> Result<Transcript> {
+        let mut transcript = self.transcribe_with(samples, true)?;
+        let (text, segments) = parse_segments(&transcript.text)?;
+        let window_end =
+            offset + samples.len() as f64 / crate::audio::TRANSCRIBE_SAMPLE_RATE as f64;
+        transcript.text = text;
+        transcript.segments = segments
+            .into_iter()
+            .map(|c| Cue {
+                start: (offset + c.start).min(window_end),
+                end: (offset + c.end).min(window_end),
+                text: c.text,
+            })
+            .collect();
+        Ok(transcript)
+    }
+
+    fn transcribe_with(&self, samples: &[f32], segments: bool) -> Result<Transcript> {
         let choice = self.backend_choice.lock().unwrap().clone();
-        let (backend, sidecar) = resolve_sidecar(&choice)?;
+        let ranking = self
+            .backend_probes
+            .lock()
+            .unwrap()
+            .as_deref()
+            .map(rank_backends);
+        let candidates = resolve_sidecars(&self.sidecar_dir()?, &choice, ranking.as_deref())?;
         let model_path = self.model_path.lock().unwrap().clone();
         if !model_path.exists() {
             anyhow::bail!(
@@ -117,6 +504,78 @@ impl Transcriber {
                 model_path
             );
         }
+
+        let candidates = self.health.lock().unwrap().order(candidates);
+        let mut failed_backends = Vec::new();
+        let mut last_err = None;
+        for (backend, sidecar) in candidates {
+            match self.run_sidecar(&backend, &sidecar, &model_path, samples, segments) {
+                Ok(SidecarOutput { text, .. }) => {
+                    self.health.lock().unwrap().record_success(&backend);
+                    if !failed_backends.is_empty() {
+                        tracing::warn!(
+                            "transcribed via fallback backend {} after {:?} failed",
+                            backend,
+                            failed_backends
+                        );
+                    }
+                    return Ok(Transcript {
+                        text,
+                        backend,
+                        failed_backends,
+                        segments: Vec::new(),
+                    });
+                }
+                Err(e) => {
+                    if self.health.lock().unwrap().record_failure(&backend) {
+                        tracing::warn!(
+                            "backend {} failed {} times in a row; deprioritised for this session",
+                            backend,
+                            UNHEALTHY_AFTER_FAILURES
+                        );
+                    }
+                    if e.downcast_ref::<SidecarTimedOut>().is_some() {
+                        return Err(e);
+                    }
+                    tracing::error!("backend {} failed: {:#}", backend, e);
+                    failed_backends.push(backend);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.expect("resolve_sidecars returns at least one candidate"))
+    }
+
+    /// Runs `samples` through one specific backend and model, bypassing the
+    /// user's choice, the ranking and the fallback chain. Used by the
+    /// benchmark, which wants the numbers of exactly that pairing.
+    pub fn transcribe_on(
+        &self,
+        backend: &str,
+        model_path: &Path,
+        samples: &[f32],
+    ) -> Result<SidecarOutput> {
+        let sidecar = self.sidecar_dir()?.join(backend_bin_name(backend));
+        if !sidecar.exists() {
+            anyhow::bail!("backend '{}' not found at {:?}", backend, sidecar);
+        }
+        if !model_path.exists() {
+            anyhow::bail!("model file not found: {:?}", model_path);
+        }
+        self.run_sidecar(backend, &sidecar, model_path, samples, false)
+    }
+
+    /// One attempt: spawn `sidecar`, stream `samples`, collect the text
+    /// (the raw `--segments` lines when `segments` is set).
+    fn run_sidecar(
+        &self,
+        backend: &str,
+        sidecar: &Path,
+        model_path: &Path,
+        samples: &[f32],
+        segments: bool,
+    ) -> Result<SidecarOutput> {
+        let total_start = Instant::now();
         tracing::info!(
             "transcribe via {} | model={:?} | sidecar={:?}",
             backend,
@@ -124,11 +583,12 @@ impl Transcriber {
             sidecar
         );
 
-        let mut cmd = Command::new(&sidecar);
+        let mut cmd = Command::new(sidecar);
         cmd.arg("--model")
-            .arg(&model_path)
+            .arg(model_path)
             .arg("--language")
             .arg(&self.language)
+            .args(segments.then_some("--segments"))
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
@@ -153,7 +613,9 @@ impl Transcriber {
             buf
         });
 
-        let timeout = transcription_timeout(samples.len());
+        let timeout = self
+            .timeout
+            .unwrap_or_else(|| transcription_timeout(samples.len()));
         let mut stdin = child.stdin.take().context("sidecar stdin missing")?;
         let write_start = Instant::now();
         let (status, write_result, timed_out) = std::thread::scope(|scope| -> Result<_> {
@@ -199,12 +661,12 @@ impl Transcriber {
                 .and_then(|result| result.ok())
                 .unwrap_or_default();
             let stderr_text = stderr_thread.join().unwrap_or_default();
-            anyhow::bail!(
+            return Err(anyhow::Error::new(SidecarTimedOut).context(format!(
                 "sidecar timed out after {:?}; stdout: {}; stderr: {}",
                 timeout,
                 stdout_text.trim(),
                 stderr_text.trim()
-            );
+            )));
         }
 
         let stdout_buf = stdout_thread
@@ -230,11 +692,66 @@ impl Transcriber {
         if !stderr_text.trim().is_empty() {
             tracing::debug!("sidecar stderr: {}", stderr_text.trim());
         }
-        tracing::info!("transcription took {:?}", total_start.elapsed());
-        Ok(stdout_buf.trim().to_string())
+        let elapsed = total_start.elapsed();
+        tracing::info!("transcription took {:?}", elapsed);
+        let (load_ms, decode_ms) = parse_timings(&stderr_text).unzip();
+        Ok(SidecarOutput {
+            text: stdout_buf.trim().to_string(),
+            elapsed,
+            load_ms,
+            decode_ms,
+        })
     }
 }
 
+/// Pulls `load_ms` / `decode_ms` out of the sidecar's final
+/// `flov-timings:` stderr line. `None` for sidecars built before the line
+/// existed — the caller then only has the wall-clock total.
+fn parse_timings(stderr: &str) -> Option<(u64, u64)> {
+    let line = stderr
+        .lines()
+        .rev()
+        .find_map(|l| l.trim().strip_prefix("flov-timings:"))?;
+    let mut load = None;
+    let mut decode = None;
+    for field in line.split_whitespace() {
+        match field.split_once('=') {
+            Some(("load_ms", v)) => load = v.parse().ok(),
+            Some(("decode_ms", v)) => decode = v.parse().ok(),
+            _ => {}
+        }
+    }
+    Some((load?, decode?))
+}
+
+/// Splits `--segments` stdout into the joined transcript and per-segment
+/// cues (seconds relative to the sidecar's input). The text is assembled
+/// from the untrimmed segment texts, exactly like the sidecar's plain
+/// output, so a timed transcript reads the same as an untimed one.
+fn parse_segments(stdout: &str) -> Result<(String, Vec<Cue>)> {
+    let mut text = String::new();
+    let mut cues = Vec::new();
+    for line in stdout.lines().filter(|l| !l.trim().is_empty()) {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(start), Some(end), Some(segment)) = (fields.next(), fields.next(), fields.next())
+        else {
+            anyhow::bail!("malformed sidecar segment line: {:?}", line);
+        };
+        let ms = |v: &str| {
+            v.trim()
+                .parse::<u64>()
+                .with_context(|| format!("bad segment time {:?} in {:?}", v, line))
+        };
+        text.push_str(segment);
+        cues.push(Cue {
+            start: ms(start)? as f64 / 1000.0,
+            end: ms(end)? as f64 / 1000.0,
+            text: segment.trim().to_string(),
+        });
+    }
+    Ok((text.trim().to_string(), cues))
+}
+
 fn write_samples_to_stdin<W: Write>(stdin: &mut W, samples: &[f32]) -> Result<()> {
     const CHUNK_SAMPLES: usize = 4096;
 
@@ -257,15 +774,26 @@ fn transcription_timeout(sample_count: usize) -> Duration {
     scaled.min(Duration::from_secs(10 * 60))
 }
 
-/// Picks the sidecar to spawn given a user choice.
-/// - "auto" → first available in BACKEND_PRIORITY
-/// - "cuda" / "vulkan" / "metal" / "cpu" → that one specifically (errors if
-///   missing — the menu greys out missing ones, so this only fires if the
-///   binary was deleted between startup and the click).
+/// Lists the sidecars to try, in order, given a user choice.
+/// - "auto" → every backend in `ranking` (see `rank_backends`) whose binary
+///   is still there — backends the probe found unusable are never tried.
+///   While the probe hasn't finished, the installed binaries in
+///   BACKEND_PRIORITY order instead, minus CUDA without its driver. Later
+///   entries are the runtime fallbacks for `Transcriber::transcribe`.
+/// - "cuda" / "vulkan" / "metal" / "cpu" → that one specifically, no
+///   fallback (errors if missing — the menu greys out missing ones, so
+///   this only fires if the binary was deleted between startup and the
+///   click).
 /// - FLOV_BACKEND env var, when set, overrides the choice — useful for
 ///   one-off comparisons without touching the menu.
-fn resolve_sidecar(choice: &str) -> Result<(String, PathBuf)> {
-    let dir = exe_dir()?;
+///
+/// Binaries are looked up in `dir`: the exe dir in the app, a temp dir
+/// holding the mock sidecar in tests/sidecar.rs.
+fn resolve_sidecars(
+    dir: &Path,
+    choice: &str,
+    ranking: Option<&[String]>,
+) -> Result<Vec<(String, PathBuf)>> {
     let effective = std::env::var("FLOV_BACKEND").unwrap_or_else(|_| choice.to_string());
 
     if effective != "auto" {
@@ -277,24 +805,66 @@ fn resolve_sidecar(choice: &str) -> Result<(String, PathBuf)> {
                 candidate
             );
         }
-        return Ok((effective, candidate));
+        return Ok(vec![(effective, candidate)]);
     }
 
+    let order: Vec<&str> = match ranking {
+        Some(ranked) => ranked.iter().map(String::as_str).collect(),
+        None => BACKEND_PRIORITY
+            .iter()
+            .copied()
+            .filter(|b| *b != "cuda" || cuda_runtime_present())
+            .collect(),
+    };
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
     let mut tried = Vec::new();
-    for backend in BACKEND_PRIORITY {
+    for backend in order {
         let candidate = dir.join(backend_bin_name(backend));
         if candidate.exists() {
-            return Ok(((*backend).to_string(), candidate));
+            found.push((backend.to_string(), candidate));
+        } else {
+            tried.push(candidate);
         }
-        tried.push(candidate);
     }
-    anyhow::bail!("no whisper sidecar found in {:?}; tried {:?}", dir, tried);
+    if found.is_empty() {
+        if ranking.is_some_and(|r| r.is_empty()) {
+            anyhow::bail!("no usable whisper backend: every sidecar failed its probe");
+        }
+        anyhow::bail!("no whisper sidecar found in {:?}; tried {:?}", dir, tried);
+    }
+    Ok(found)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(unix)]
+    #[test]
+    fn chatty_probe_does_not_fill_its_pipes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("flov-probe-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("flov-whisper-chatty");
+        // Well over a pipe buffer on both streams, then a failed probe.
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nhead -c 300000 /dev/zero | tr '\\0' x\n\
+             head -c 300000 /dev/zero | tr '\\0' y >&2\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let started = Instant::now();
+        let (devices, reason) = run_probe(&script).unwrap();
+
+        assert!(started.elapsed() < PROBE_TIMEOUT);
+        assert!(devices.is_empty());
+        assert_eq!(reason.map(|r| r.len()), Some(300_000));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn write_samples_to_stdin_serializes_little_endian_f32() {
         let mut out = Vec::new();
@@ -305,6 +875,42 @@ mod tests {
         assert_eq!(out, expected);
     }
 
+    #[test]
+    fn parse_timings_reads_last_timings_line() {
+        let stderr = "whisper_init_from_file: loading model\n\
+                      flov-timings: load_ms=812 decode_ms=1540\n";
+
+        assert_eq!(parse_timings(stderr), Some((812, 1540)));
+        assert_eq!(parse_timings("whisper_init: ok\n"), None);
+        assert_eq!(parse_timings("flov-timings: load_ms=12\n"), None);
+    }
+
+    #[test]
+    fn parse_segments_joins_text_and_converts_times() {
+        let stdout = "0\t1500\t Hello,\n1500\t3250\t world.\n";
+
+        let (text, cues) = parse_segments(stdout).unwrap();
+
+        assert_eq!(text, "Hello, world.");
+        assert_eq!(
+            cues,
+            vec![
+                Cue {
+                    start: 0.0,
+                    end: 1.5,
+                    text: "Hello,".into()
+                },
+                Cue {
+                    start: 1.5,
+                    end: 3.25,
+                    text: "world.".into()
+                },
+            ]
+        );
+        assert_eq!(parse_segments("").unwrap(), (String::new(), Vec::new()));
+        assert!(parse_segments("heard 10 samples").is_err());
+    }
+
     #[test]
     fn transcription_timeout_has_minimum_for_short_audio() {
         assert_eq!(transcription_timeout(0), Duration::from_secs(30));
@@ -321,6 +927,154 @@ mod tests {
         assert_eq!(transcription_timeout(samples), Duration::from_secs(60));
     }
 
+    fn probe(backend: &str, kind: &str) -> BackendProbe {
+        BackendProbe {
+            backend: backend.to_string(),
+            usable: true,
+            devices: vec![ProbeDevice {
+                kind: kind.to_string(),
+                initialised: true,
+                memory_total: 8_000_000_000,
+                memory_free: 6_000_000_000,
+                description: String::new(),
+            }],
+            skip_reason: None,
+            recommended_model: None,
+        }
+    }
+
+    #[test]
+    fn parse_probe_output_reads_tab_separated_devices() {
+        let out =
+            "gpu\t1\t8589934592\t7000000000\tNVIDIA GeForce RTX 3070\ncpu\t0\t0\t0\t\ngarbage\n";
+
+        let devices = parse_probe_output(out);
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].kind, "gpu");
+        assert!(devices[0].initialised);
+        assert_eq!(devices[0].memory_total, 8_589_934_592);
+        assert_eq!(devices[0].description, "NVIDIA GeForce RTX 3070");
+        assert!(!devices[1].initialised);
+    }
+
+    #[test]
+    fn rank_backends_prefers_discrete_gpu_over_priority_order() {
+        let mut skipped = probe("cuda", "gpu");
+        skipped.usable = false;
+        let probes = vec![
+            skipped,
+            probe("vulkan", "igpu"),
+            probe("metal", "gpu"),
+            probe("cpu", "cpu"),
+        ];
+
+        assert_eq!(rank_backends(&probes), vec!["metal", "vulkan", "cpu"]);
+    }
+
+    fn sidecar_dir_with(test: &str, backends: &[&str]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("flov-resolve-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for b in backends {
+            std::fs::write(dir.join(backend_bin_name(b)), b"").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn auto_falls_back_only_to_probed_backends() {
+        let dir = sidecar_dir_with("ranked", &["cuda", "vulkan", "cpu"]);
+        let mut cuda = probe("cuda", "gpu");
+        cuda.usable = false;
+        let ranking = rank_backends(&[cuda, probe("vulkan", "gpu"), probe("cpu", "cpu")]);
+
+        let found = resolve_sidecars(&dir, "auto", Some(&ranking)).unwrap();
+
+        assert_eq!(names(&found), vec!["vulkan", "cpu"]);
+        assert!(resolve_sidecars(&dir, "auto", Some(&[])).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn auto_before_the_probe_skips_cuda_without_its_driver() {
+        let dir = sidecar_dir_with("unprobed", &["cuda", "cpu"]);
+
+        let found = resolve_sidecars(&dir, "auto", None).unwrap();
+
+        assert_eq!(names(&found), vec!["cpu"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn primary_device_ignores_other_kinds_and_failed_init() {
+        let mut p = probe("vulkan", "gpu");
+        p.devices[0].initialised = false;
+        p.devices.push(ProbeDevice {
+            kind: "cpu".into(),
+            initialised: true,
+            memory_total: 1,
+            memory_free: 1,
+            description: String::new(),
+        });
+
+        assert!(p.primary_device().is_none());
+    }
+
+    fn candidates(names: &[&str]) -> Vec<(String, PathBuf)> {
+        names
+            .iter()
+            .map(|n| (n.to_string(), PathBuf::from(n)))
+            .collect()
+    }
+
+    fn names(candidates: &[(String, PathBuf)]) -> Vec<&str> {
+        candidates.iter().map(|(b, _)| b.as_str()).collect()
+    }
+
+    #[test]
+    fn backend_becomes_unhealthy_after_repeated_failures() {
+        let mut health = BackendHealth::default();
+
+        assert!(!health.record_failure("cuda"));
+        assert!(!health.is_unhealthy("cuda"));
+        assert!(health.record_failure("cuda"));
+        assert!(health.is_unhealthy("cuda"));
+        assert!(!health.record_failure("cuda"));
+    }
+
+    #[test]
+    fn success_resets_backend_health() {
+        let mut health = BackendHealth::default();
+        health.record_failure("vulkan");
+        health.record_failure("vulkan");
+
+        health.record_success("vulkan");
+
+        assert!(!health.is_unhealthy("vulkan"));
+    }
+
+    #[test]
+    fn unhealthy_backends_move_to_the_end_of_the_order() {
+        let mut health = BackendHealth::default();
+        health.record_failure("cuda");
+        health.record_failure("cuda");
+
+        let ordered = health.order(candidates(&["cuda", "vulkan", "cpu"]));
+
+        assert_eq!(names(&ordered), vec!["vulkan", "cpu", "cuda"]);
+    }
+
+    #[test]
+    fn timeout_marker_survives_context() {
+        let err = anyhow::Error::new(SidecarTimedOut).context("sidecar timed out after 30s");
+
+        assert!(err.downcast_ref::<SidecarTimedOut>().is_some());
+        assert_eq!(err.to_string(), "sidecar timed out after 30s");
+    }
+
     #[test]
     fn transcription_timeout_is_capped() {
         let samples = crate::audio::TRANSCRIBE_SAMPLE_RATE as usize * 120;
@@ -6,14 +6,25 @@
 //! - We poll the theme registry key every few seconds to react to user theme
 //!   switches without an extra Win32 message hook.
 //! - State is conveyed via tooltip — pill UI is the primary signal.
+//! - Pre-roll keeps the mic open while idle; the tooltip says so and the
+//!   "Keep Mic Open" check item turns it off.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 use tauri::image::Image;
-use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
 use tauri::tray::TrayIconBuilder;
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager, Wry};
 
 pub const TRAY_ID: &str = "flov-tray";
 
+static CURRENT_STATE: Mutex<TrayState> = Mutex::new(TrayState::Idle);
+static MIC_OPEN: AtomicBool = AtomicBool::new(false);
+
+/// Managed so `set_mic_open` can sync the check mark.
+struct PrerollItem(CheckMenuItem<Wry>);
+
 #[derive(Clone, Copy)]
 pub enum TrayState {
     Idle,
@@ -24,6 +35,9 @@ pub enum TrayState {
 impl TrayState {
     fn tooltip(self) -> &'static str {
         match self {
+            TrayState::Idle if MIC_OPEN.load(Ordering::SeqCst) => {
+                "flov — mic open (pre-roll) · hold Ctrl+Win to dictate"
+            }
             TrayState::Idle => "flov — hold Ctrl+Win to dictate",
             TrayState::Recording => "flov — recording…",
             TrayState::Transcribing => "flov — transcribing…",
@@ -130,11 +144,31 @@ fn load_themed_icon(dark_theme: bool) -> Image<'static> {
 pub fn setup(app: &AppHandle) -> tauri::Result<()> {
     // Tray menu is intentionally minimal: settings / stats / postprocess /
     // backend all live inside the Settings window now. Tray stays for
-    // open + quit only.
+    // open, file import (tray icons can't take drops), the pre-roll
+    // privacy switch and quit.
     let open_item = MenuItem::with_id(app, "open_settings", "Open Settings…", true, None::<&str>)?;
+    let import_item = MenuItem::with_id(
+        app,
+        "import_file",
+        "Transcribe Audio File…",
+        true,
+        None::<&str>,
+    )?;
+    let preroll_item = CheckMenuItem::with_id(
+        app,
+        "preroll",
+        "Keep Mic Open (Pre-roll)",
+        true,
+        MIC_OPEN.load(Ordering::SeqCst),
+        None::<&str>,
+    )?;
     let sep = PredefinedMenuItem::separator(app)?;
     let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-    let menu = Menu::with_items(app, &[&open_item, &sep, &quit_item])?;
+    let menu = Menu::with_items(
+        app,
+        &[&open_item, &import_item, &preroll_item, &sep, &quit_item],
+    )?;
+    app.manage(PrerollItem(preroll_item));
 
     let initial_dark = windows_uses_dark_theme();
 
@@ -151,6 +185,21 @@ pub fn setup(app: &AppHandle) -> tauri::Result<()> {
                         tracing::error!("open settings failed: {:#}", e);
                     }
                 }
+                "import_file" => {
+                    let state = app.state::<crate::import_cmd::ImportState>();
+                    crate::import_cmd::open_from_tray(app, &state);
+                }
+                "preroll" => {
+                    let state = app.state::<crate::state_cmd::AppState>();
+                    let ms = if state.recorder.preroll_ms() > 0 {
+                        0
+                    } else {
+                        crate::audio::DEFAULT_PREROLL_MS
+                    };
+                    if let Err(e) = crate::state_cmd::apply_preroll(app, &state.recorder, ms) {
+                        tracing::error!("pre-roll toggle failed: {:#}", e);
+                    }
+                }
                 _ => {}
             },
         );
@@ -194,7 +243,18 @@ pub fn setup(app: &AppHandle) -> tauri::Result<()> {
 }
 
 pub fn set_state(app: &AppHandle, state: TrayState) {
+    *CURRENT_STATE.lock().unwrap() = state;
     if let Some(tray) = app.tray_by_id(TRAY_ID) {
         let _ = tray.set_tooltip(Some(state.tooltip()));
     }
 }
+
+/// Privacy indicator for the pre-roll stream: idle tooltip plus the tray
+/// check mark.
+pub fn set_mic_open(app: &AppHandle, open: bool) {
+    MIC_OPEN.store(open, Ordering::SeqCst);
+    if let Some(item) = app.try_state::<PrerollItem>() {
+        let _ = item.0.set_checked(open);
+    }
+    set_state(app, *CURRENT_STATE.lock().unwrap());
+}
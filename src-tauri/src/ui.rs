@@ -23,6 +23,11 @@ pub enum PillState {
     Recording = 1,
     Transcribing = 2,
     Error = 3,
+    /// Neutral, self-dismissing message (e.g. "CUDA failed — used
+    /// Vulkan"). Shares the error text slot.
+    Notice = 4,
+    /// The transcript is with the OpenRouter post-processor.
+    Postprocessing = 5,
 }
 
 impl PillState {
@@ -31,6 +36,8 @@ impl PillState {
             1 => Self::Recording,
             2 => Self::Transcribing,
             3 => Self::Error,
+            4 => Self::Notice,
+            5 => Self::Postprocessing,
             _ => Self::Idle,
         }
     }
@@ -462,7 +469,7 @@ pub fn wait_for_frontend_reload_if_needed(timeout: Duration) -> bool {
 
 pub fn set_pill_state(state: PillState) {
     PILL_STATE.store(state as u8, Ordering::SeqCst);
-    if !matches!(state, PillState::Error) {
+    if !matches!(state, PillState::Error | PillState::Notice) {
         error_text().lock().unwrap().clear();
     }
     if !matches!(state, PillState::Idle) {
@@ -478,6 +485,12 @@ pub fn set_pill_error(message: &str) {
     set_overlay_active(true);
 }
 
+pub fn set_pill_notice(message: &str) {
+    PILL_STATE.store(PillState::Notice as u8, Ordering::SeqCst);
+    *error_text().lock().unwrap() = message.to_string();
+    set_overlay_active(true);
+}
+
 pub fn pill_snapshot() -> PillSnapshot {
     PillSnapshot {
         state: PillState::from_u8(PILL_STATE.load(Ordering::SeqCst)),
@@ -0,0 +1,160 @@
+// End-to-end tests for `Transcriber`: the real spawn / stdin / stdout /
+// timeout / fallback path, with tests/support/mock_sidecar.rs standing in
+// for whisper. Runs on any machine — no model, GPU or sidecar build needed.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use flov_lib::transcribe::{SidecarTimedOut, Transcriber};
+
+const MOCK: &str = env!("CARGO_BIN_EXE_flov-whisper-mock");
+
+struct Fixture {
+    dir: PathBuf,
+    model: PathBuf,
+}
+
+/// Fresh sidecar dir for one test, with the mock installed under each
+/// backend name and scripted by `mode` (see mock_sidecar.rs).
+fn fixture(test: &str, backends: &[(&str, &str)]) -> Fixture {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("sidecar")
+        .join(test);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (backend, mode) in backends {
+        let name = format!("flov-whisper-{}{}", backend, std::env::consts::EXE_SUFFIX);
+        std::fs::copy(MOCK, dir.join(&name)).unwrap();
+        std::fs::write(dir.join(format!("flov-whisper-{}.mode", backend)), mode).unwrap();
+    }
+    let model = dir.join("ggml-mock.bin");
+    std::fs::write(&model, b"mock").unwrap();
+    Fixture { dir, model }
+}
+
+impl Fixture {
+    fn transcriber(&self, choice: &str) -> Transcriber {
+        Transcriber::new(
+            Arc::new(Mutex::new(self.model.clone())),
+            "en".to_string(),
+            Arc::new(Mutex::new(choice.to_string())),
+            Arc::new(Mutex::new(None)),
+        )
+        .unwrap()
+        .with_sidecar_dir(self.dir.clone())
+        .with_timeout(Duration::from_secs(5))
+    }
+}
+
+fn samples(n: usize) -> Vec<f32> {
+    (0..n).map(|i| (i as f32 / 100.0).sin()).collect()
+}
+
+#[test]
+fn echo_sidecar_round_trips_samples_and_language() {
+    let fx = fixture("echo", &[("cpu", "echo")]);
+    let t = fx.transcriber("cpu");
+
+    let transcript = t.transcribe(&samples(16_000)).unwrap();
+
+    assert_eq!(transcript.text, "heard 16000 samples (en)");
+    assert_eq!(transcript.backend, "cpu");
+    assert!(transcript.failed_backends.is_empty());
+}
+
+#[test]
+fn timed_transcription_shifts_segments_by_the_window_offset() {
+    let fx = fixture("segments", &[("cpu", "echo")]);
+    let t = fx.transcriber("cpu");
+
+    let transcript = t.transcribe_timed(&samples(32_000), 30.0).unwrap();
+
+    assert_eq!(transcript.text, "heard 32000 samples (en)");
+    let times: Vec<(f64, f64)> = transcript
+        .segments
+        .iter()
+        .map(|c| (c.start, c.end))
+        .collect();
+    assert_eq!(times, vec![(30.0, 31.0), (31.0, 32.0)]);
+    assert_eq!(transcript.segments[1].text, "(en)");
+}
+
+#[test]
+fn transcribe_on_reports_sidecar_timings() {
+    let fx = fixture("timings", &[("cpu", "echo")]);
+    let t = fx.transcriber("auto");
+
+    let out = t.transcribe_on("cpu", &fx.model, &samples(10)).unwrap();
+
+    assert_eq!(out.text, "heard 10 samples (en)");
+    assert_eq!(out.load_ms, Some(1));
+    assert_eq!(out.decode_ms, Some(2));
+}
+
+#[test]
+fn timeout_kills_the_sidecar_and_skips_fallback() {
+    let fx = fixture("timeout", &[("vulkan", "sleep 30"), ("cpu", "echo")]);
+    let t = fx
+        .transcriber("auto")
+        .with_timeout(Duration::from_millis(500));
+
+    let start = Instant::now();
+    let err = t.transcribe(&samples(100)).unwrap_err();
+
+    assert!(err.downcast_ref::<SidecarTimedOut>().is_some(), "{err:#}");
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn crash_in_auto_mode_falls_back_to_the_next_backend() {
+    let fx = fixture("fallback", &[("vulkan", "crash"), ("cpu", "echo")]);
+    let t = fx.transcriber("auto");
+
+    let transcript = t.transcribe(&samples(3)).unwrap();
+
+    assert_eq!(transcript.backend, "cpu");
+    assert_eq!(transcript.failed_backends, vec!["vulkan".to_string()]);
+    assert_eq!(transcript.text, "heard 3 samples (en)");
+}
+
+#[test]
+fn crash_with_an_explicit_choice_surfaces_stderr() {
+    let fx = fixture("crash", &[("cuda", "crash"), ("cpu", "echo")]);
+    let t = fx.transcriber("cuda");
+
+    let err = format!("{:#}", t.transcribe(&samples(3)).unwrap_err());
+
+    assert!(err.contains("crashing on purpose"), "{err}");
+}
+
+#[test]
+fn garbage_on_stdout_is_an_error() {
+    let fx = fixture("garbage", &[("cpu", "garbage")]);
+    let t = fx.transcriber("cpu");
+
+    let err = format!("{:#}", t.transcribe(&samples(3)).unwrap_err());
+
+    assert!(err.contains("stdout"), "{err}");
+}
+
+#[test]
+fn partial_output_before_a_failure_is_discarded() {
+    let fx = fixture("partial", &[("cpu", "partial")]);
+    let t = fx.transcriber("cpu");
+
+    let err = format!("{:#}", t.transcribe(&samples(3)).unwrap_err());
+
+    assert!(err.contains("decoder died"), "{err}");
+    assert!(err.contains("Some(1)"), "{err}");
+}
+
+#[test]
+fn explicit_backend_without_a_binary_is_reported() {
+    let fx = fixture("missing", &[("cpu", "echo")]);
+    let t = fx.transcriber("vulkan");
+
+    let err = format!("{:#}", t.transcribe(&samples(3)).unwrap_err());
+
+    assert!(err.contains("backend 'vulkan' selected"), "{err}");
+}
@@ -0,0 +1,84 @@
+// flov-whisper-mock — test-only stand-in for a flov-whisper-* sidecar.
+//
+// tests/sidecar.rs copies this binary into a temp dir under a real sidecar
+// name (`flov-whisper-cpu`, …) and points `Transcriber::with_sidecar_dir` at
+// it. What the copy does is scripted by a `<binary name>.mode` file next
+// to it (extension replaced), so each backend in a test can misbehave
+// differently:
+//
+//   echo        read stdin, print "heard <n> samples (<language>)", exit 0
+//   sleep <s>   read stdin, sleep <s> seconds, then echo
+//   crash       read stdin, abort (killed by signal / no exit code)
+//   garbage     read stdin, print invalid UTF-8, exit 0
+//   partial     read stdin, print half a sentence, fail with exit 1
+//
+// No mode file means `echo`. `--probe` reports a single CPU device.
+// With `--segments` the echo comes back as two timed segment lines, split
+// at the middle of the input ("heard <n> samples" / "(<language>)").
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+fn main() {
+    let mut language = String::from("ru");
+    let mut segments = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--probe" => {
+                println!("cpu\t1\t0\t0\tMock CPU");
+                return;
+            }
+            "--language" => language = args.next().unwrap_or_default(),
+            "--segments" => segments = true,
+            _ => {}
+        }
+    }
+
+    let mode = std::env::current_exe()
+        .ok()
+        .and_then(|exe| std::fs::read_to_string(exe.with_extension("mode")).ok())
+        .unwrap_or_else(|| "echo".to_string());
+    let mut words = mode.split_whitespace();
+    let command = words.next().unwrap_or("echo");
+
+    let mut buf = Vec::new();
+    std::io::stdin().read_to_end(&mut buf).expect("read stdin");
+    let samples = buf.len() / 4;
+
+    let mut stdout = std::io::stdout();
+    match command {
+        "echo" => {}
+        "sleep" => {
+            let secs: f64 = words.next().and_then(|s| s.parse().ok()).unwrap_or(60.0);
+            std::thread::sleep(Duration::from_secs_f64(secs));
+        }
+        "crash" => {
+            eprintln!("mock: crashing on purpose");
+            std::process::abort();
+        }
+        "garbage" => {
+            stdout.write_all(&[0xff, 0xfe, 0x00, b'?']).unwrap();
+            return;
+        }
+        "partial" => {
+            stdout.write_all(b"heard half a").unwrap();
+            stdout.flush().unwrap();
+            eprintln!("mock: decoder died mid-segment");
+            std::process::exit(1);
+        }
+        other => {
+            eprintln!("mock: unknown mode {other:?}");
+            std::process::exit(2);
+        }
+    }
+    if segments {
+        let end_ms = samples as u64 * 1000 / 16_000;
+        let mid_ms = end_ms / 2;
+        writeln!(stdout, "0\t{mid_ms}\t heard {samples} samples").unwrap();
+        writeln!(stdout, "{mid_ms}\t{end_ms}\t ({language})").unwrap();
+    } else {
+        write!(stdout, "heard {samples} samples ({language})").unwrap();
+    }
+    eprintln!("flov-timings: load_ms=1 decode_ms=2");
+}